@@ -13,7 +13,10 @@ use crate::api::MAX_FILE_SIZE;
 use crate::core::AppError;
 use crate::core::Claims;
 use crate::core::UserError;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{
+    submission_class_id, ClassRole, ClassRoleGuard, LoggedInGuard, NotArchivedGuard,
+    ResourceOwnerGuard,
+};
 use async_graphql::ID;
 use async_graphql::{dataloader::DataLoader, Context, Object};
 use deadpool_redis::redis::AsyncCommands;
@@ -37,7 +40,9 @@ pub struct AssignmentMutation;
 #[Object]
 impl AssignmentMutation {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_assignment(
         &self,
         ctx: &Context<'_>,
@@ -93,7 +98,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn update_assignment(
         &self,
         ctx: &Context<'_>,
@@ -153,7 +160,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
     pub async fn delete_assignment(
         &self,
         ctx: &Context<'_>,
@@ -185,7 +194,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::Student)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_assignment_submission(
         &self,
         ctx: &Context<'_>,
@@ -261,7 +272,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::Student)).and(ResourceOwnerGuard::submission(input.id.clone())).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn update_assignment_submission(
         &self,
         ctx: &Context<'_>,
@@ -269,29 +282,9 @@ impl AssignmentMutation {
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let s3_bucket = ctx.data_unchecked::<s3::Bucket>();
-        let claims = ctx.data_unchecked::<Option<Claims>>();
         let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
         let mut conn = redis_pool.get().await?;
 
-        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
-        let assignment_submission =
-            AssignmentRepo::find_submission_by_id(data_loader, Uuid::parse_str(input.id.as_str())?)
-                .await?;
-
-        if assignment_submission.is_none() {
-            return Err(AppError::not_found(
-                "Assignment does not exist",
-                "assignment_submission",
-                "id",
-                &input.id.to_string(),
-            ));
-        }
-
-        let assignment_submission = assignment_submission.unwrap();
-        if assignment_submission.user_id != user_id {
-            return Err(AppError::auth("You are not the owner of this assignment"));
-        }
-
         let (model, new_files) = input.try_into_active_model()?;
         let old_files =
             FileRepo::find_by_assignment_submission_id(data_loader, model.id.to_owned().unwrap())
@@ -351,7 +344,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Student)).and(ResourceOwnerGuard::submission(assignment_submission_id.clone())).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
     pub async fn delete_assignment_submission(
         &self,
         ctx: &Context<'_>,
@@ -361,31 +356,12 @@ impl AssignmentMutation {
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let s3_bucket = ctx.data_unchecked::<s3::Bucket>();
-        let claims = ctx.data_unchecked::<Option<Claims>>();
         let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
         let mut conn = redis_pool.get().await?;
 
         let class_id = class_id.parse::<Uuid>()?;
         let assignment_submission_id = assignment_submission_id.parse::<Uuid>()?;
 
-        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
-        let assignment_submission =
-            AssignmentRepo::find_submission_by_id(data_loader, assignment_submission_id).await?;
-
-        if assignment_submission.is_none() {
-            return Err(AppError::not_found(
-                "Assignment does not exist",
-                "assignment_submission",
-                "id",
-                &assignment_submission_id.to_string(),
-            ));
-        }
-
-        let assignment_submission = assignment_submission.unwrap();
-        if assignment_submission.user_id != user_id {
-            return Err(AppError::auth("You are not the owner of this assignment"));
-        }
-
         let res =
             AssignmentRepo::delete_assignment_submission(data_loader, assignment_submission_id)
                 .await?;
@@ -424,7 +400,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::TeachingAssistant)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_assignment_submission_feedback(
         &self,
         ctx: &Context<'_>,
@@ -434,6 +412,20 @@ impl AssignmentMutation {
         let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
         let mut conn = redis_pool.get().await?;
 
+        // the guard only covers `class_id`, the submission could be from any class
+        let class_id = Uuid::parse_str(input.class_id.as_str())?;
+        let submission_id = Uuid::parse_str(input.assignment_submission_id.as_str())?;
+        if submission_class_id(data_loader, submission_id).await? != Some(class_id) {
+            return Err(AppError::auth("Submission was not handed in to this class"));
+        }
+        // an existing id turns the insert into an update of that feedback
+        if let Some(id) = &input.id {
+            let feedback = AssignmentRepo::find_feedback_by_id(data_loader, id.parse()?).await?;
+            if matches!(feedback, Some(f) if f.assignment_submission_id != submission_id) {
+                return Err(AppError::auth("Feedback belongs to another submission"));
+            }
+        }
+
         let assignment_id = Uuid::parse_str(input.assignment_id.clone().as_str())?;
         let model = input.try_into_active_model()?;
 
@@ -454,7 +446,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::TeachingAssistant)).and(ResourceOwnerGuard::feedback(assignment_submission_feedback_id.clone())).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
     pub async fn delete_assignment_submission_feedback(
         &self,
         ctx: &Context<'_>,
@@ -487,7 +481,9 @@ impl AssignmentMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::TeachingAssistant)).and(ResourceOwnerGuard::feedback(input.id.clone())).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn update_assignment_submission_feedback(
         &self,
         ctx: &Context<'_>,
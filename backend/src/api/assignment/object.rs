@@ -1,6 +1,9 @@
 use crate::api::file::FileRepo;
 use crate::api::user::{UserObject, UserRepo};
+use crate::core::email_templates::DEFAULT_LOCALE;
+use crate::core::localization;
 use crate::core::option_to_active_value;
+use crate::core::Claims;
 use crate::{api::file::FileObject, core::AppError};
 use async_graphql::{
     dataloader::DataLoader, ComplexObject, Context, InputObject, SimpleObject, Upload, ID,
@@ -94,6 +97,33 @@ impl AssignmentObject {
                 .expect("Id should be valid");
         Ok(submissions.into_iter().map(|f| f.into()).collect())
     }
+
+    /// `dueAt` in the timezone, locale and hour cycle of the current user.
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn formatted_due_at(&self, ctx: &Context<'_>) -> Result<Option<String>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let Some(due_at) = self.due_at else {
+            return Ok(None);
+        };
+        let viewer = match claims {
+            Some(claims) => {
+                UserRepo::find_by_id(data_loader, Uuid::parse_str(&claims.sub)?).await?
+            }
+            None => None,
+        };
+
+        Ok(Some(match viewer {
+            Some(viewer) => localization::format_datetime_for(due_at, &viewer),
+            None => localization::format_datetime(
+                due_at,
+                localization::DEFAULT_TIMEZONE,
+                DEFAULT_LOCALE,
+                None,
+            ),
+        }))
+    }
 }
 
 impl From<::entity::assignment::Model> for AssignmentObject {
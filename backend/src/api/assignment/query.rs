@@ -6,7 +6,7 @@ use tracing::instrument;
 use uuid::Uuid;
 
 use crate::api::class;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard};
 use crate::{
     api::user::UserObject,
     core::{AppError, Claims},
@@ -18,7 +18,9 @@ pub struct AssignmentQuery;
 #[Object]
 impl AssignmentQuery {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::TeachingAssistant))"
+    )]
     async fn assignment_submissions(
         &self,
         ctx: &Context<'_>,
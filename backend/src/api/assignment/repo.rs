@@ -144,6 +144,11 @@ pub trait AssignmentRepo {
         assignment_submission_id: Uuid,
     ) -> Result<Option<assignment_submission_feedback::Model>, Arc<DbErr>>;
 
+    async fn find_feedback_by_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<assignment_submission_feedback::Model>, DbErr>;
+
     async fn delete_assignment_feedback(
         &self,
         assignment_submission_feedback_id: Uuid,
@@ -650,6 +655,17 @@ impl AssignmentRepo for DataLoader<DatabaseConnection> {
         Ok(feedback)
     }
 
+    #[instrument(skip(self), err(Debug))]
+    async fn find_feedback_by_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<assignment_submission_feedback::Model>, DbErr> {
+        let feedback = AssignmentSubmissionFeedback::find_by_id(id)
+            .one(self.loader())
+            .await?;
+        Ok(feedback)
+    }
+
     #[instrument(skip(self), err(Debug))]
     async fn delete_assignment_feedback(
         &self,
@@ -0,0 +1,11 @@
+mod mutation;
+mod object;
+mod query;
+mod repo;
+mod rest;
+
+pub use mutation::CalendarMutation;
+pub use object::ClassEventObject;
+pub use query::CalendarQuery;
+pub use repo::CalendarRepo;
+pub use rest::CalendarRest;
@@ -0,0 +1,230 @@
+use async_graphql::{dataloader::DataLoader, Context, MaybeUndefined, Object, ID};
+use chrono::Utc;
+use deadpool_redis::redis::AsyncCommands;
+use sea_orm::{DatabaseConnection, IntoActiveModel, Set};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::api::class::{
+    ClassEventDeleteInfo, ClassResourceCreate, ClassResourceDelete, ClassResourceUpdate,
+    CLASS_RESOURCE_CREATED, CLASS_RESOURCE_DELETED, CLASS_RESOURCE_UPDATED,
+};
+use crate::api::UserRepo;
+use crate::core::localization::DEFAULT_TIMEZONE;
+use crate::core::{
+    generate_opaque_token, hash_opaque_token, maybe_undefined_to_active_value, AppError, Claims,
+    ClassRole, ClassRoleGuard, LoggedInGuard, NotArchivedGuard, NotImpersonatingGuard,
+    TokenScopeGuard,
+};
+use crate::HOST_URL;
+
+use super::object::{
+    check_schedule, CreateClassEventInput, CreatedCalendarFeed, UpdateClassEventInput,
+};
+use super::{CalendarRepo, ClassEventObject};
+
+#[derive(Default)]
+pub struct CalendarMutation;
+
+#[Object]
+impl CalendarMutation {
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
+    pub async fn create_class_event(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateClassEventInput,
+    ) -> Result<ClassEventObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
+        let mut conn = redis_pool.get().await?;
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(input.class_id.as_str())?;
+        let timezone = match input.timezone {
+            Some(timezone) => timezone,
+            None => UserRepo::find_by_id(data_loader, user_id)
+                .await?
+                .map(|u| u.timezone)
+                .unwrap_or(DEFAULT_TIMEZONE.into()),
+        };
+        let recurrence = check_schedule(
+            input.starts_at,
+            input.ends_at,
+            &timezone,
+            input.recurrence.as_deref(),
+        )?;
+
+        let model = ::entity::class_event::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            class_id: Set(class_id),
+            title: Set(input.title),
+            description: Set(input.description),
+            kind: Set(input.kind.into()),
+            location: Set(input.location),
+            starts_at: Set(input.starts_at),
+            ends_at: Set(input.ends_at),
+            timezone: Set(timezone),
+            recurrence: Set(recurrence),
+            created_by: Set(Some(user_id)),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+        let event = CalendarRepo::create_class_event(data_loader, model).await?;
+
+        let update_data = ClassResourceCreate::ClassEvent(event.clone().into());
+        conn.publish(
+            format!("{}:{}", CLASS_RESOURCE_CREATED, class_id),
+            serde_json::to_string(&update_data).expect("Class event should serialize"),
+        )
+        .await?;
+
+        Ok(event.into())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
+    pub async fn update_class_event(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateClassEventInput,
+    ) -> Result<ClassEventObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
+        let mut conn = redis_pool.get().await?;
+
+        let class_id = Uuid::parse_str(input.class_id.as_str())?;
+        let id = Uuid::parse_str(input.id.as_str())?;
+        let Some(event) = CalendarRepo::find_class_event(data_loader, class_id, id).await? else {
+            return Err(AppError::not_found(
+                "Class event not found".into(),
+                "class_event",
+                "id",
+                input.id.to_string(),
+            ));
+        };
+
+        let starts_at = input.starts_at.unwrap_or(event.starts_at);
+        let ends_at = input.ends_at.unwrap_or(event.ends_at);
+        let timezone = input.timezone.unwrap_or(event.timezone.clone());
+        let recurrence = match &input.recurrence {
+            MaybeUndefined::Value(rule) => Some(rule.as_str()),
+            MaybeUndefined::Null => None,
+            MaybeUndefined::Undefined => event.recurrence.as_deref(),
+        };
+        let recurrence = check_schedule(starts_at, ends_at, &timezone, recurrence)?;
+
+        let mut model = event.into_active_model();
+        if let Some(title) = input.title {
+            model.title = Set(title);
+        }
+        if let Some(kind) = input.kind {
+            model.kind = Set(kind.into());
+        }
+        model.description = maybe_undefined_to_active_value(input.description);
+        model.location = maybe_undefined_to_active_value(input.location);
+        model.starts_at = Set(starts_at);
+        model.ends_at = Set(ends_at);
+        model.timezone = Set(timezone);
+        model.recurrence = Set(recurrence);
+        let event = CalendarRepo::update_class_event(data_loader, model).await?;
+
+        let update_data = ClassResourceUpdate::ClassEvent(event.clone().into());
+        conn.publish(
+            format!("{}:{}", CLASS_RESOURCE_UPDATED, class_id),
+            serde_json::to_string(&update_data).expect("Class event should serialize"),
+        )
+        .await?;
+
+        Ok(event.into())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
+    pub async fn delete_class_event(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        event_id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<deadpool_redis::Pool>();
+        let mut conn = redis_pool.get().await?;
+
+        let class_uuid = Uuid::parse_str(class_id.as_str())?;
+        let id = Uuid::parse_str(event_id.as_str())?;
+        if !CalendarRepo::delete_class_event(data_loader, class_uuid, id).await? {
+            return Err(AppError::not_found(
+                "Class event not found".into(),
+                "class_event",
+                "id",
+                event_id.to_string(),
+            ));
+        }
+
+        let update_data = ClassResourceDelete::ClassEvent(ClassEventDeleteInfo { id: event_id });
+        conn.publish(
+            format!("{}:{}", CLASS_RESOURCE_DELETED, class_uuid),
+            serde_json::to_string(&update_data).expect("Class event should serialize"),
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Creates the `.ics` link to the events and due dates of all classes of the current
+    /// user. The link is returned only this once and any previous one stops working.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn create_calendar_feed(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<CreatedCalendarFeed, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        // the feed shows every class, so a token scoped to some of them can't hand it out
+        if claims.is_personal_access_token() {
+            return Err(AppError::auth(
+                "Personal access tokens cannot create calendar feeds",
+            ));
+        }
+        let user_id = Uuid::parse_str(&claims.sub)?;
+
+        let token = generate_opaque_token();
+        let stored =
+            CalendarRepo::set_calendar_token(data_loader, user_id, hash_opaque_token(&token))
+                .await?;
+
+        Ok(CreatedCalendarFeed {
+            url: format!("{}/api/v1/calendar/{token}.ics", HOST_URL.as_str()),
+            calendar_feed: stored.into(),
+        })
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn revoke_calendar_feed(&self, ctx: &Context<'_>) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        if !CalendarRepo::delete_calendar_token(data_loader, user_id).await? {
+            return Err(AppError::not_found(
+                "Calendar feed not found".into(),
+                "calendar_feed",
+                "user_id",
+                user_id.to_string(),
+            ));
+        }
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,191 @@
+use async_graphql::{Enum, InputObject, MaybeUndefined, SimpleObject, ID};
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use entity::sea_orm_active_enums;
+use serde::{Deserialize, Serialize};
+
+use crate::core::calendar::Recurrence;
+use crate::core::{AppError, UserError};
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ClassEventKind {
+    Lecture,
+    OfficeHours,
+    Exam,
+    Other,
+}
+
+impl ClassEventKind {
+    /// Shown as the category of the event in calendar apps.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Lecture => "Lecture",
+            Self::OfficeHours => "Office hours",
+            Self::Exam => "Exam",
+            Self::Other => "Other",
+        }
+    }
+}
+
+impl From<sea_orm_active_enums::ClassEventKind> for ClassEventKind {
+    fn from(e: sea_orm_active_enums::ClassEventKind) -> Self {
+        match e {
+            sea_orm_active_enums::ClassEventKind::Lecture => Self::Lecture,
+            sea_orm_active_enums::ClassEventKind::OfficeHours => Self::OfficeHours,
+            sea_orm_active_enums::ClassEventKind::Exam => Self::Exam,
+            sea_orm_active_enums::ClassEventKind::Other => Self::Other,
+        }
+    }
+}
+
+impl From<ClassEventKind> for sea_orm_active_enums::ClassEventKind {
+    fn from(e: ClassEventKind) -> Self {
+        match e {
+            ClassEventKind::Lecture => Self::Lecture,
+            ClassEventKind::OfficeHours => Self::OfficeHours,
+            ClassEventKind::Exam => Self::Exam,
+            ClassEventKind::Other => Self::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "ClassEvent")]
+pub struct ClassEventObject {
+    pub id: ID,
+    pub class_id: ID,
+    pub title: String,
+    pub description: Option<String>,
+    pub kind: ClassEventKind,
+    pub location: Option<String>,
+    /// Start of the first occurrence.
+    pub starts_at: NaiveDateTime,
+    /// End of the first occurrence.
+    pub ends_at: NaiveDateTime,
+    /// IANA name of the timezone the event repeats in.
+    pub timezone: String,
+    /// `RRULE` the event repeats by, like `FREQ=WEEKLY;BYDAY=MO,WE;COUNT=24`.
+    pub recurrence: Option<String>,
+    pub created_by: Option<ID>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<::entity::class_event::Model> for ClassEventObject {
+    fn from(e: ::entity::class_event::Model) -> Self {
+        Self {
+            id: ID::from(e.id),
+            class_id: ID::from(e.class_id),
+            title: e.title,
+            description: e.description,
+            kind: e.kind.into(),
+            location: e.location,
+            starts_at: e.starts_at,
+            ends_at: e.ends_at,
+            timezone: e.timezone,
+            recurrence: e.recurrence,
+            created_by: e.created_by.map(ID::from),
+            created_at: e.created_at,
+        }
+    }
+}
+
+/// A single time a class event takes place.
+#[derive(Debug, SimpleObject)]
+#[graphql(name = "ClassEventOccurrence")]
+pub struct ClassEventOccurrenceObject {
+    pub event: ClassEventObject,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+}
+
+/// The calendar feed of the current user. Its link is only shown when it is created.
+#[derive(Debug, SimpleObject)]
+#[graphql(name = "CalendarFeed")]
+pub struct CalendarFeedObject {
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl From<::entity::calendar_token::Model> for CalendarFeedObject {
+    fn from(t: ::entity::calendar_token::Model) -> Self {
+        Self {
+            created_at: t.created_at,
+            last_used_at: t.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct CreatedCalendarFeed {
+    /// `.ics` link to subscribe to from a calendar app.
+    pub url: String,
+    pub calendar_feed: CalendarFeedObject,
+}
+
+#[derive(Debug, InputObject)]
+pub struct CreateClassEventInput {
+    pub class_id: ID,
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    pub title: String,
+    #[graphql(validator(max_length = 1000))]
+    pub description: Option<String>,
+    pub kind: ClassEventKind,
+    #[graphql(validator(max_length = 200))]
+    pub location: Option<String>,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    /// IANA name like `Europe/Ljubljana`, the timezone of the creator when not given.
+    pub timezone: Option<String>,
+    /// `RRULE` with `FREQ` of `DAILY`, `WEEKLY` or `MONTHLY` and optionally `INTERVAL`,
+    /// `BYDAY` (weekly only), `COUNT` or `UNTIL`.
+    pub recurrence: Option<String>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct UpdateClassEventInput {
+    pub id: ID,
+    pub class_id: ID,
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    pub title: Option<String>,
+    #[graphql(validator(max_length = 1000))]
+    pub description: MaybeUndefined<String>,
+    pub kind: Option<ClassEventKind>,
+    #[graphql(validator(max_length = 200))]
+    pub location: MaybeUndefined<String>,
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+    pub timezone: Option<String>,
+    pub recurrence: MaybeUndefined<String>,
+}
+
+/// Checks when an event takes place. Returns the recurrence rule in the form it is stored in.
+pub fn check_schedule(
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    timezone: &str,
+    recurrence: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    if ends_at <= starts_at {
+        return Err(AppError::user(
+            "Event has to end after it starts",
+            UserError::BadInput {
+                parameter: "ends_at",
+                given_value: ends_at.to_string(),
+            },
+        ));
+    }
+    let Ok(timezone) = timezone.parse::<Tz>() else {
+        return Err(AppError::user(
+            "Unknown timezone",
+            UserError::BadInput {
+                parameter: "timezone",
+                given_value: timezone.to_owned(),
+            },
+        ));
+    };
+
+    match recurrence {
+        Some(rule) => Ok(Some(Recurrence::parse(rule, timezone)?.to_string())),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,110 @@
+use async_graphql::{dataloader::DataLoader, Context, Object, ID};
+use chrono::{Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use sea_orm::DatabaseConnection;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::core::calendar::{self, Recurrence};
+use crate::core::{
+    AppError, Claims, ClassRole, ClassRoleGuard, LoggedInGuard, TokenScopeGuard, UserError,
+};
+
+use super::object::{CalendarFeedObject, ClassEventOccurrenceObject};
+use super::{CalendarRepo, ClassEventObject};
+
+/// Longest range `classEventOccurrences` expands events over.
+const MAX_OCCURRENCE_RANGE_DAYS: i64 = 366;
+
+#[derive(Default)]
+pub struct CalendarQuery;
+
+#[Object]
+impl CalendarQuery {
+    /// Events of the class as they were scheduled, with their recurrence rules.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
+    async fn class_events(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<Vec<ClassEventObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let events = CalendarRepo::find_class_events(data_loader, class_id).await?;
+        Ok(events.into_iter().map(|e| e.into()).collect())
+    }
+
+    /// Every time an event of the class takes place between `from` and `to`, in order.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
+    async fn class_event_occurrences(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<ClassEventOccurrenceObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        if to <= from || to - from > Duration::days(MAX_OCCURRENCE_RANGE_DAYS) {
+            return Err(AppError::user(
+                "Range has to end after it starts and be at most a year long",
+                UserError::BadInput {
+                    parameter: "to",
+                    given_value: to.to_string(),
+                },
+            ));
+        }
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let events = CalendarRepo::find_class_events(data_loader, class_id).await?;
+
+        let mut occurrences = Vec::new();
+        for event in events {
+            let timezone = event.timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+            // Rules are checked when they are saved
+            let recurrence = event
+                .recurrence
+                .as_deref()
+                .and_then(|r| Recurrence::parse(r, timezone).ok());
+            for (starts_at, ends_at) in calendar::occurrences(
+                event.starts_at,
+                event.ends_at,
+                timezone,
+                recurrence.as_ref(),
+                from,
+                to,
+            ) {
+                occurrences.push(ClassEventOccurrenceObject {
+                    event: event.clone().into(),
+                    starts_at,
+                    ends_at,
+                });
+            }
+        }
+        occurrences.sort_by_key(|o| o.starts_at);
+
+        Ok(occurrences)
+    }
+
+    /// The calendar feed of the current user, if they created one.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn calendar_feed(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<CalendarFeedObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let token = CalendarRepo::find_calendar_token(data_loader, user_id).await?;
+        Ok(token.map(|t| t.into()))
+    }
+}
@@ -0,0 +1,241 @@
+use ::entity::{
+    assignment, assignment::Entity as Assignment, calendar_token,
+    calendar_token::Entity as CalendarToken, class, class::Entity as Class, class_event,
+    class_event::Entity as ClassEvent, membership, user, user::Entity as User,
+};
+use async_graphql::dataloader::DataLoader;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sea_orm::sea_query::{OnConflict, Query};
+use sea_orm::*;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// One-off events and assignments that ended longer ago than this are left out of feeds.
+const FEED_HISTORY_DAYS: i64 = 365;
+
+/// Classes `user_id` is a member of that are not deleted.
+fn in_classes_of(user_id: Uuid, class_id: impl ColumnTrait) -> Condition {
+    Condition::all()
+        .add(
+            class_id.in_subquery(
+                Query::select()
+                    .column(membership::Column::ClassId)
+                    .from(membership::Entity)
+                    .and_where(membership::Column::UserId.eq(user_id))
+                    .to_owned(),
+            ),
+        )
+        .add(class::Column::DeletedAt.is_null())
+}
+
+#[async_trait]
+pub trait CalendarRepo {
+    async fn find_class_events(&self, class_id: Uuid) -> Result<Vec<class_event::Model>, DbErr>;
+    async fn find_class_event(
+        &self,
+        class_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<class_event::Model>, DbErr>;
+    async fn create_class_event(
+        &self,
+        model: class_event::ActiveModel,
+    ) -> Result<class_event::Model, DbErr>;
+    async fn update_class_event(
+        &self,
+        model: class_event::ActiveModel,
+    ) -> Result<class_event::Model, DbErr>;
+    async fn delete_class_event(&self, class_id: Uuid, id: Uuid) -> Result<bool, DbErr>;
+    async fn find_calendar_token(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<calendar_token::Model>, DbErr>;
+    async fn set_calendar_token(
+        &self,
+        user_id: Uuid,
+        token_hash: String,
+    ) -> Result<calendar_token::Model, DbErr>;
+    async fn delete_calendar_token(&self, user_id: Uuid) -> Result<bool, DbErr>;
+    async fn find_user_by_calendar_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr>;
+    async fn find_feed_events(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<(class_event::Model, class::Model)>, DbErr>;
+    async fn find_feed_assignments(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<(assignment::Model, class::Model)>, DbErr>;
+}
+
+#[async_trait]
+impl CalendarRepo for DataLoader<DatabaseConnection> {
+    #[instrument(skip(self), err(Debug))]
+    async fn find_class_events(&self, class_id: Uuid) -> Result<Vec<class_event::Model>, DbErr> {
+        let events = ClassEvent::find()
+            .filter(class_event::Column::ClassId.eq(class_id))
+            .order_by_asc(class_event::Column::StartsAt)
+            .order_by_asc(class_event::Column::Id)
+            .all(self.loader())
+            .await?;
+        Ok(events)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_class_event(
+        &self,
+        class_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<class_event::Model>, DbErr> {
+        let event = ClassEvent::find_by_id(id)
+            .filter(class_event::Column::ClassId.eq(class_id))
+            .one(self.loader())
+            .await?;
+        Ok(event)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn create_class_event(
+        &self,
+        model: class_event::ActiveModel,
+    ) -> Result<class_event::Model, DbErr> {
+        let event = model.insert(self.loader()).await?;
+        Ok(event)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn update_class_event(
+        &self,
+        model: class_event::ActiveModel,
+    ) -> Result<class_event::Model, DbErr> {
+        let event = model.update(self.loader()).await?;
+        Ok(event)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_class_event(&self, class_id: Uuid, id: Uuid) -> Result<bool, DbErr> {
+        let res = ClassEvent::delete_many()
+            .filter(class_event::Column::Id.eq(id))
+            .filter(class_event::Column::ClassId.eq(class_id))
+            .exec(self.loader())
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_calendar_token(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<calendar_token::Model>, DbErr> {
+        let token = CalendarToken::find_by_id(user_id)
+            .one(self.loader())
+            .await?;
+        Ok(token)
+    }
+
+    /// Replaces the token of the user, so a leaked feed link can be rotated.
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn set_calendar_token(
+        &self,
+        user_id: Uuid,
+        token_hash: String,
+    ) -> Result<calendar_token::Model, DbErr> {
+        let model = calendar_token::ActiveModel {
+            user_id: Set(user_id),
+            token_hash: Set(token_hash),
+            created_at: Set(Utc::now().naive_utc()),
+            last_used_at: Set(None),
+        };
+        CalendarToken::insert(model)
+            .on_conflict(
+                OnConflict::column(calendar_token::Column::UserId)
+                    .update_columns([
+                        calendar_token::Column::TokenHash,
+                        calendar_token::Column::CreatedAt,
+                        calendar_token::Column::LastUsedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(self.loader())
+            .await?;
+
+        CalendarToken::find_by_id(user_id)
+            .one(self.loader())
+            .await?
+            .ok_or_else(|| DbErr::RecordNotFound("calendar token not found".into()))
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_calendar_token(&self, user_id: Uuid) -> Result<bool, DbErr> {
+        let res = CalendarToken::delete_by_id(user_id)
+            .exec(self.loader())
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Also records when the feed was last fetched.
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn find_user_by_calendar_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr> {
+        let Some((token, user)) = CalendarToken::find()
+            .filter(calendar_token::Column::TokenHash.eq(token_hash))
+            .find_also_related(User)
+            .one(self.loader())
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut active = token.into_active_model();
+        active.last_used_at = Set(Some(Utc::now().naive_utc()));
+        active.update(self.loader()).await?;
+
+        Ok(user)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_feed_events(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<(class_event::Model, class::Model)>, DbErr> {
+        let since = Utc::now().naive_utc() - Duration::days(FEED_HISTORY_DAYS);
+        let events = ClassEvent::find()
+            .find_also_related(Class)
+            .filter(in_classes_of(user_id, class_event::Column::ClassId))
+            .filter(
+                Condition::any()
+                    .add(class_event::Column::Recurrence.is_not_null())
+                    .add(class_event::Column::EndsAt.gte(since)),
+            )
+            .order_by_asc(class_event::Column::StartsAt)
+            .all(self.loader())
+            .await?
+            .into_iter()
+            .map(|(e, c)| (e, c.expect("class should be present")))
+            .collect();
+        Ok(events)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_feed_assignments(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<(assignment::Model, class::Model)>, DbErr> {
+        let since = Utc::now().naive_utc() - Duration::days(FEED_HISTORY_DAYS);
+        let assignments = Assignment::find()
+            .find_also_related(Class)
+            .filter(in_classes_of(user_id, assignment::Column::ClassId))
+            .filter(assignment::Column::DueAt.gte(since))
+            .order_by_asc(assignment::Column::DueAt)
+            .all(self.loader())
+            .await?
+            .into_iter()
+            .map(|(a, c)| (a, c.expect("class should be present")))
+            .collect();
+        Ok(assignments)
+    }
+}
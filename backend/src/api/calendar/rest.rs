@@ -0,0 +1,93 @@
+use async_graphql::dataloader::DataLoader;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::Response,
+    response::IntoResponse,
+};
+use chrono_tz::Tz;
+use sea_orm::DatabaseConnection;
+use tracing::instrument;
+
+use crate::core::calendar::{Calendar, CalendarEvent, Recurrence};
+use crate::core::{hash_opaque_token, AppError};
+use crate::HOST_URL;
+
+use super::object::ClassEventKind;
+use super::CalendarRepo;
+
+pub struct CalendarRest;
+
+/// Globally unique id of a feed entry, which calendar apps use to recognize updates.
+fn uid(kind: &str, id: uuid::Uuid) -> String {
+    let host = HOST_URL.split("://").last().unwrap_or_default();
+    format!("{}-{}@{}", kind, id, host.trim_end_matches('/'))
+}
+
+impl CalendarRest {
+    /// Events and assignment due dates of every class of the user owning the token, as an
+    /// iCalendar file for calendar apps to subscribe to.
+    #[instrument(skip(token, conn), err(Debug))]
+    pub async fn feed(
+        Path(token): Path<String>,
+        State(conn): State<DatabaseConnection>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+
+        let token = token.strip_suffix(".ics").unwrap_or(&token);
+        let user =
+            match CalendarRepo::find_user_by_calendar_token(&data_loader, hash_opaque_token(token))
+                .await?
+            {
+                Some(user) if user.active && user.deleted_at.is_none() => user,
+                _ => return Err(AppError::auth("Unknown calendar feed")),
+            };
+
+        let mut calendar = Calendar::new("Go e-Learn");
+        for (event, class) in CalendarRepo::find_feed_events(&data_loader, user.id).await? {
+            let timezone = event.timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+            calendar.add_event(&CalendarEvent {
+                uid: uid("class-event", event.id),
+                summary: format!("{} ({})", event.title, class.name),
+                description: event.description,
+                location: event.location,
+                url: Some(format!("{}/class/{}", HOST_URL.as_str(), class.id)),
+                categories: Some(ClassEventKind::from(event.kind).label().to_owned()),
+                starts_at: event.starts_at,
+                ends_at: Some(event.ends_at),
+                timezone,
+                // Rules are checked when they are saved
+                recurrence: event
+                    .recurrence
+                    .as_deref()
+                    .and_then(|r| Recurrence::parse(r, timezone).ok()),
+            });
+        }
+        for (assignment, class) in
+            CalendarRepo::find_feed_assignments(&data_loader, user.id).await?
+        {
+            let Some(due_at) = assignment.due_at else {
+                continue;
+            };
+            calendar.add_event(&CalendarEvent {
+                uid: uid("assignment", assignment.id),
+                summary: format!("Due: {} ({})", assignment.name, class.name),
+                description: Some(assignment.content).filter(|c| !c.is_empty()),
+                location: None,
+                url: Some(format!("{}/class/{}", HOST_URL.as_str(), class.id)),
+                categories: Some("Assignment".into()),
+                starts_at: due_at,
+                ends_at: None,
+                timezone: Tz::UTC,
+                recurrence: None,
+            });
+        }
+
+        let response = Response::builder()
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("Cache-Control", "private, max-age=900")
+            .body(Body::from(calendar.finish()))
+            .unwrap();
+        Ok(response)
+    }
+}
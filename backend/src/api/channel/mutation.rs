@@ -13,7 +13,7 @@ use uuid::Uuid;
 use super::object::{CreateChannelInput, UpdateChannelInput};
 use super::ChannelObject;
 use crate::api::channel::repo::ChannelRepo;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard, NotArchivedGuard};
 
 #[derive(Default)]
 pub struct ChannelMutation;
@@ -21,7 +21,9 @@ pub struct ChannelMutation;
 #[Object]
 impl ChannelMutation {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_channel(
         &self,
         ctx: &Context<'_>,
@@ -47,7 +49,9 @@ impl ChannelMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn update_channel(
         &self,
         ctx: &Context<'_>,
@@ -73,7 +77,9 @@ impl ChannelMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
     pub async fn delete_channel(
         &self,
         ctx: &Context<'_>,
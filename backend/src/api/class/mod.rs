@@ -6,6 +6,7 @@ mod subscription;
 
 pub use mutation::ClassMutation;
 pub use object::ClassObject;
+pub use object::MemberObject;
 pub use query::ClassQuery;
 pub use repo::ClassRepo;
 pub use subscription::ClassSubscription;
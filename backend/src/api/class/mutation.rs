@@ -1,22 +1,34 @@
 use crate::api::user::UserRepo;
 use crate::api::MAX_FILE_SIZE;
-use crate::core::{auth, AppError, UserError};
-use crate::core::{AdminGuard, ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::email_templates::{self, EmailTemplate};
+use crate::core::mailer::DynMailer;
+use crate::core::{auth, invites, rate_limit, tags, AppError, UserError};
+use crate::core::{
+    class_role, ensure_not_archived, AdminGuard, ClassRole, ClassRoleGuard, LoggedInGuard,
+    NotArchivedGuard, TokenScopeGuard,
+};
+use crate::jobs;
+use crate::HOST_URL;
 use async_graphql::{dataloader::DataLoader, Context, Object, ID};
 use auth::Claims;
+use chrono::Utc;
+use entity::{class, invite, join_request, sea_orm_active_enums, user};
 
 use deadpool_redis::redis::AsyncCommands;
 use deadpool_redis::Pool;
-use sea_orm::DatabaseConnection;
+use sea_orm::{DatabaseConnection, Set};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tracing::instrument;
 use uuid::Uuid;
 
-use super::object::{CreateClassInput, CreateInviteInput, InviteObject, UpdateClassInput};
+use super::object::{
+    CloneClassInput, CreateClassInput, CreateEmailInvitesInput, CreateInviteInput, InviteObject,
+    JoinRequestObject, MemberObject, OwnershipTransferObject, UpdateClassInput,
+};
 use super::{
     ClassDelete, ClassObject, ClassRepo, ClassResourceCreate, ClassResourceDelete,
-    ClassResourceUpdate, CLASS_DELETED, CLASS_RESOURCE_CREATED, CLASS_RESOURCE_DELETED,
-    CLASS_RESOURCE_UPDATED,
+    ClassResourceUpdate, CLASS_CLONE_REQUESTER, CLASS_DELETED, CLASS_RESOURCE_CREATED,
+    CLASS_RESOURCE_DELETED, CLASS_RESOURCE_UPDATED,
 };
 
 #[derive(Default)]
@@ -25,7 +37,7 @@ pub struct ClassMutation;
 #[Object]
 impl ClassMutation {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
     pub async fn create_class(
         &self,
         ctx: &Context<'_>,
@@ -37,6 +49,7 @@ impl ClassMutation {
 
         let id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
         let image = input.image.take();
+        input.tags = tags::normalize_tags(&input.tags)?.join(" ");
 
         let model = input.into_active_model(id, image.is_some());
         let class_id = model.id.clone().into_value().expect("id was just set");
@@ -70,67 +83,178 @@ impl ClassMutation {
         Ok(class.into())
     }
 
+    /// Creates a class owned by the caller and copies channels, files and assignments of
+    /// `class_id` into it in the background, see `classCloneProgress`. Members, messages and
+    /// submissions are not copied.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
-    pub async fn join_class(
+    #[graphql(
+        guard = "LoggedInGuard.and(TokenScopeGuard::account()).and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
+    pub async fn clone_class(
         &self,
         ctx: &Context<'_>,
         class_id: ID,
-        invite_id: Option<ID>,
-    ) -> Result<ID, AppError> {
+        input: CloneClassInput,
+    ) -> Result<ClassObject, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
+        let s3_bucket = ctx.data_unchecked::<s3::Bucket>();
         let redis_pool = ctx.data_unchecked::<Pool>();
-        let mut conn = redis_pool.get().await?;
 
-        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
-        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let owner_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let source_id = Uuid::parse_str(class_id.as_str())?;
+        let source = ClassRepo::find_by_id(data_loader, source_id)
+            .await?
+            .expect("Guard ensures the class exists");
 
-        let class = ClassRepo::find_by_id(data_loader, class_id).await?;
-        let class = match class {
-            Some(class) => class,
-            None => {
-                return Err(AppError::not_found(
-                    "Class not found".into(),
-                    "class",
-                    "id",
-                    class_id.to_string(),
-                ))
+        let due_offset = input
+            .due_date_offset_days
+            .map(|days| chrono::Duration::days(days.into()));
+        let model = input.into_active_model(&source, owner_id);
+        let target_id = model.id.clone().unwrap();
+        let image_path = format!("class-images/{target_id}");
+        if source.has_image {
+            s3_bucket
+                .copy_object_internal(format!("class-images/{source_id}"), image_path.clone())
+                .await?;
+        }
+        let class = match ClassRepo::create_class(data_loader, model).await {
+            Ok(class) => class,
+            Err(err) => {
+                // without the class nothing would ever point at the copied image
+                if source.has_image {
+                    if let Err(err) = s3_bucket.delete_object(&image_path).await {
+                        tracing::error!("Failed to remove image of class {target_id}: {err:?}");
+                    }
+                }
+                return Err(err.into());
             }
         };
 
-        if !class.public {
-            if invite_id.is_none() {
-                return Err(AppError::user(
-                    "You cannot join private class without explicit invite",
-                    UserError::BadInput {
-                        parameter: "class_id",
-                        given_value: class_id.to_string(),
-                    },
-                ));
-            }
+        // the job lets the key expire once it is done
+        let mut conn = redis_pool.get().await?;
+        let _: () = conn
+            .set(
+                format!("{CLASS_CLONE_REQUESTER}:{target_id}"),
+                owner_id.to_string(),
+            )
+            .await?;
 
-            let invite_id = Uuid::parse_str(invite_id.unwrap().as_str())?;
-            let is_valid = ClassRepo::is_valid_invite(data_loader, invite_id, class_id).await?;
+        tokio::spawn(jobs::clone_class(
+            data_loader.loader().clone(),
+            s3_bucket.clone(),
+            redis_pool.clone(),
+            source_id,
+            target_id,
+            due_offset,
+        ));
 
-            if !is_valid {
-                return Err(AppError::user(
-                    "You cannot join class with invalid invite",
-                    UserError::BadInput {
-                        parameter: "invite_id",
-                        given_value: invite_id.to_string(),
-                    },
-                ));
+        Ok(class.into())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn join_class(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        invite_id: Option<ID>,
+    ) -> Result<ID, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let invite = match invite_id {
+            Some(invite_id) => {
+                let invite_id = Uuid::parse_str(invite_id.as_str())?;
+                let invite = ClassRepo::find_invite(data_loader, invite_id).await?;
+                let Some(invite) = invite else {
+                    return Err(AppError::user(
+                        "You cannot join class with invalid invite",
+                        UserError::BadInput {
+                            parameter: "invite_id",
+                            given_value: invite_id.to_string(),
+                        },
+                    ));
+                };
+                Some(invite)
             }
-        }
+            None => None,
+        };
+
+        join_class(ctx, class_id, invite).await
+    }
+
+    /// Joins the class of the invite with the join code `code`, like `XXXX-XXXX`.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn join_class_by_code(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> Result<ID, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        invites::throttle_join_code(redis_pool, user_id).await?;
 
-        let members = UserRepo::find_by_class_id(data_loader, class_id)
+        let code = invites::normalize_join_code(&code);
+        let invite = ClassRepo::find_invite_by_code(data_loader, code.clone())
             .await?
-            .expect("class id is valid");
+            .ok_or_else(|| {
+                AppError::not_found("Invite not found".into(), "invite", "code", code)
+            })?;
+
+        join_class(ctx, invite.class_id, Some(invite)).await
+    }
+
+    /// Asks the teachers of a private class to let the caller in, the owner is emailed
+    /// about it. Decided with `approveJoinRequest` or `denyJoinRequest`.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn request_to_join_class(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        #[graphql(validator(max_length = 500))] note: Option<String>,
+    ) -> Result<JoinRequestObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let Some(class) = ClassRepo::find_by_id(data_loader, class_id).await? else {
+            return Err(AppError::not_found(
+                "Class not found".into(),
+                "class",
+                "id",
+                class_id.to_string(),
+            ));
+        };
+        ensure_not_archived(data_loader, class_id).await?;
 
-        if members.iter().any(|m| m.id == user_id) {
+        let problem = if class.public {
+            Some("Public classes can be joined directly")
+        } else if class_role(data_loader, user_id, class_id).await?.is_some() {
+            Some("You are already a member of this class")
+        } else if ClassRepo::get_user_bans(data_loader, user_id)
+            .await?
+            .contains(&class_id)
+        {
+            Some("You cannot join class you're banned from")
+        } else if matches!(
+            ClassRepo::find_latest_join_request(data_loader, class_id, user_id).await?,
+            Some(request) if request.status == sea_orm_active_enums::JoinRequestStatus::Pending
+        ) {
+            Some("You already asked to join this class")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
             return Err(AppError::user(
-                "You cannot join class your're already member of",
+                problem,
                 UserError::BadInput {
                     parameter: "class_id",
                     given_value: class_id.to_string(),
@@ -138,34 +262,153 @@ impl ClassMutation {
             ));
         }
 
-        let bans = ClassRepo::get_user_bans(data_loader, user_id).await?;
-        if bans.iter().any(|b| *b == class_id) {
+        let request = ClassRepo::create_join_request(
+            data_loader,
+            join_request::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                class_id: Set(class_id),
+                user_id: Set(user_id),
+                note: Set(note
+                    .map(|note| note.trim().to_string())
+                    .filter(|note| !note.is_empty())),
+                status: Set(sea_orm_active_enums::JoinRequestStatus::Pending),
+                created_at: Set(Utc::now().naive_utc()),
+                ..Default::default()
+            },
+        )
+        .await?;
+        let user = UserRepo::find_by_id(data_loader, user_id)
+            .await?
+            .expect("user exists");
+        // the request stands even if the owner can't be told about it
+        if let Err(err) =
+            send_join_request_email(data_loader, mailer, &class, &user, &request).await
+        {
+            tracing::error!(
+                "Failed to email the owner of {class_id} about a join request: {err:?}"
+            );
+        }
+
+        Ok(JoinRequestObject::new(request, user))
+    }
+
+    /// Withdraws the caller's pending request to join the class.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn cancel_join_request(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        match ClassRepo::find_latest_join_request(data_loader, class_id, user_id).await? {
+            Some(request) if request.status == sea_orm_active_enums::JoinRequestStatus::Pending => {
+                ClassRepo::delete_join_request(data_loader, request.id).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Lets the user of a pending join request in as a student and emails them about it.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
+    pub async fn approve_join_request(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        request_id: ID,
+    ) -> Result<JoinRequestObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+        let mut conn = redis_pool.get().await?;
+
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let request = pending_join_request(data_loader, class_id, request_id).await?;
+        let user = UserRepo::find_by_id(data_loader, request.user_id)
+            .await?
+            .expect("Requests are removed with their user");
+
+        if ClassRepo::get_user_bans(data_loader, user.id)
+            .await?
+            .contains(&class_id)
+        {
             return Err(AppError::user(
-                "You cannot join class you're banned from",
+                "User is banned from this class",
                 UserError::BadInput {
-                    parameter: "class_id",
-                    given_value: class_id.to_string(),
+                    parameter: "request_id",
+                    given_value: request.id.to_string(),
                 },
             ));
         }
+        let Some((request, joined)) =
+            ClassRepo::approve_join_request(data_loader, request.id, caller_id).await?
+        else {
+            return Err(already_decided(request.id));
+        };
+        if joined {
+            let update_data = ClassResourceCreate::Member(user.clone().into());
+            conn.publish(
+                format!("{}:{}", CLASS_RESOURCE_CREATED, class_id),
+                serde_json::to_string(&update_data).expect("User should serialize"),
+            )
+            .await?;
+        }
+        send_join_decision_email(data_loader, mailer, &request, &user).await;
 
-        let user = UserRepo::find_by_id(data_loader, user_id)
+        Ok(JoinRequestObject::new(request, user))
+    }
+
+    /// Turns down a pending join request and emails the user about it.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
+    pub async fn deny_join_request(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        request_id: ID,
+    ) -> Result<JoinRequestObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let request = pending_join_request(data_loader, class_id, request_id).await?;
+        let user = UserRepo::find_by_id(data_loader, request.user_id)
             .await?
-            .expect("user exists");
-        ClassRepo::join_user_to_class(data_loader, user_id, class_id).await?;
+            .expect("Requests are removed with their user");
 
-        let update_data = ClassResourceCreate::Member(user.into());
-        conn.publish(
-            format!("{}:{}", CLASS_RESOURCE_CREATED, class_id),
-            serde_json::to_string(&update_data).expect("User should serialize"),
+        let Some(request) = ClassRepo::decide_join_request(
+            data_loader,
+            request.id,
+            sea_orm_active_enums::JoinRequestStatus::Denied,
+            caller_id,
         )
-        .await?;
+        .await?
+        else {
+            return Err(already_decided(request.id));
+        };
+        send_join_decision_email(data_loader, mailer, &request, &user).await;
 
-        Ok(ID::from(class_id))
+        Ok(JoinRequestObject::new(request, user))
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     pub async fn leave_class(&self, ctx: &Context<'_>, class_id: ID) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
@@ -188,7 +431,9 @@ impl ClassMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
     pub async fn ban_member(
         &self,
         ctx: &Context<'_>,
@@ -196,12 +441,20 @@ impl ClassMutation {
         user_id: ID,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
         let redis_pool = ctx.data_unchecked::<Pool>();
         let mut conn = redis_pool.get().await?;
 
         let original_id = user_id.clone();
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
         let class_id = Uuid::parse_str(class_id.as_str())?;
         let user_id = Uuid::parse_str(user_id.as_str())?;
+        let caller_role = class_role(data_loader, caller_id, class_id).await?;
+        if class_role(data_loader, user_id, class_id).await? >= caller_role {
+            return Err(AppError::auth(
+                "Members can only be banned by someone with a higher role",
+            ));
+        }
         ClassRepo::ban_member(data_loader, class_id, user_id).await?;
 
         let update_data = ClassResourceDelete::Member(super::MemberDeleteInfo { id: original_id });
@@ -215,7 +468,9 @@ impl ClassMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
     pub async fn unban_member(
         &self,
         ctx: &Context<'_>,
@@ -230,8 +485,40 @@ impl ClassMutation {
         Ok(true)
     }
 
+    /// Gives a member a more privileged role. Co-teachers can be made only by the owner.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()).or(AdminGuard))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
+    pub async fn promote_member(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        user_id: ID,
+        role: ClassRole,
+    ) -> Result<MemberObject, AppError> {
+        change_member_role(ctx, class_id, user_id, role, true).await
+    }
+
+    /// Gives a member a less privileged role. Co-teachers can be demoted only by the owner.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
+    pub async fn demote_member(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        user_id: ID,
+        role: ClassRole,
+    ) -> Result<MemberObject, AppError> {
+        change_member_role(ctx, class_id, user_id, role, false).await
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner).or(AdminGuard))"
+    )]
     pub async fn delete_class(&self, ctx: &Context<'_>, class_id: ID) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let redis_pool = ctx.data_unchecked::<Pool>();
@@ -277,18 +564,24 @@ impl ClassMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner))")]
     pub async fn update_class(
         &self,
         ctx: &Context<'_>,
         class_id: ID,
-        class_input: UpdateClassInput,
+        mut class_input: UpdateClassInput,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let redis_pool = ctx.data_unchecked::<Pool>();
         let mut conn = redis_pool.get().await?;
 
         let class_id = Uuid::parse_str(class_id.as_str())?;
+        class_input.tags = class_input
+            .tags
+            .as_deref()
+            .map(tags::normalize_tags)
+            .transpose()?
+            .map(|t| t.join(" "));
         let update_data = class_input.into_active_model();
         let updated = ClassRepo::update_class(data_loader, class_id, update_data).await?;
         let updated = ClassObject::from(updated);
@@ -303,23 +596,245 @@ impl ClassMutation {
         Ok(true)
     }
 
+    /// Freezes the class at the end of a term. Members can still read everything in it,
+    /// but nothing new can be added until the owner restores it.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner))")]
+    pub async fn archive_class(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<ClassObject, AppError> {
+        set_archived(ctx, class_id, true).await
+    }
+
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner))")]
+    pub async fn restore_class(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<ClassObject, AppError> {
+        set_archived(ctx, class_id, false).await
+    }
+
+    /// Offers ownership of the class to one of its members, replacing an earlier offer.
+    /// Nothing changes until they accept it with `acceptOwnershipTransfer`.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner))")]
+    pub async fn propose_ownership_transfer(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        user_id: ID,
+    ) -> Result<OwnershipTransferObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let user_id = Uuid::parse_str(user_id.as_str())?;
+
+        if user_id == caller_id {
+            return Err(AppError::user(
+                "You already own this class",
+                UserError::BadInput {
+                    parameter: "user_id",
+                    given_value: user_id.to_string(),
+                },
+            ));
+        }
+        if class_role(data_loader, user_id, class_id).await?.is_none() {
+            return Err(AppError::not_found(
+                "Member not found".into(),
+                "membership",
+                "user_id",
+                user_id.to_string(),
+            ));
+        }
+
+        let transfer =
+            ClassRepo::propose_ownership_transfer(data_loader, class_id, user_id).await?;
+
+        Ok(transfer.into())
+    }
+
+    /// Withdraws the pending offer, returns whether there was one.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Owner))")]
+    pub async fn cancel_ownership_transfer(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let deleted = ClassRepo::delete_ownership_transfer(data_loader, class_id).await?;
+
+        Ok(deleted)
+    }
+
+    /// Takes over the class offered to the caller. The previous owner stays on as a co-teacher.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
+    pub async fn accept_ownership_transfer(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<ClassObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let id = Uuid::parse_str(class_id.as_str())?;
+        pending_transfer_to(data_loader, id, caller_id).await?;
+
+        transfer_ownership(ctx, id, caller_id).await
+    }
+
+    /// Turns down the ownership offered to the caller.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
+    pub async fn decline_ownership_transfer(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        pending_transfer_to(data_loader, class_id, caller_id).await?;
+        ClassRepo::delete_ownership_transfer(data_loader, class_id).await?;
+
+        Ok(true)
+    }
+
+    /// Hands the class to any user without their consent, for classes whose owner left.
+    /// The user doesn't have to be a member yet.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    pub async fn admin_transfer_ownership(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        user_id: ID,
+    ) -> Result<ClassObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let user_id = Uuid::parse_str(user_id.as_str())?;
+        if ClassRepo::find_by_id(data_loader, class_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::not_found(
+                "Class not found".into(),
+                "class",
+                "class_id",
+                class_id.to_string(),
+            ));
+        }
+        if UserRepo::find_by_id(data_loader, user_id).await?.is_none() {
+            return Err(AppError::not_found(
+                "User not found".into(),
+                "user",
+                "user_id",
+                user_id.to_string(),
+            ));
+        }
+
+        transfer_ownership(ctx, class_id, user_id).await
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_invite(
         &self,
         ctx: &Context<'_>,
         input: CreateInviteInput,
     ) -> Result<InviteObject, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
 
-        let model = input.try_into_active_model()?;
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let model = input.try_into_active_model(user_id)?;
         let invite = ClassRepo::create_invite(data_loader, model).await?;
 
         Ok(invite.into())
     }
 
+    /// Creates a single use invite for each address and emails it out. Each invite can
+    /// only be used by the user with that email address.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::CoTeacher)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
+    pub async fn create_email_invites(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateEmailInvitesInput,
+    ) -> Result<Vec<InviteObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        if input.emails.len() > invites::MAX_EMAIL_INVITES {
+            return Err(AppError::user(
+                format!(
+                    "At most {} addresses can be invited at once",
+                    invites::MAX_EMAIL_INVITES
+                ),
+                UserError::BadInput {
+                    parameter: "emails",
+                    given_value: input.emails.len().to_string(),
+                },
+            ));
+        }
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let key = format!("email_invites:{user_id}");
+        if !rate_limit::hit(redis_pool, &key, invites::EMAIL_INVITE_LIMIT, 60 * 60).await? {
+            return Err(AppError::user(
+                "Too many invites sent",
+                UserError::TooManyRequests,
+            ));
+        }
+        let class_id = Uuid::parse_str(input.class_id.as_str())?;
+        let inviter = UserRepo::find_by_id(data_loader, user_id)
+            .await?
+            .expect("Guard ensures the user exists");
+        let class = ClassRepo::find_by_id(data_loader, class_id)
+            .await?
+            .expect("Guard ensures the class exists");
+
+        let invites =
+            ClassRepo::create_invites(data_loader, input.try_into_active_models(user_id)?).await?;
+        // the invites stand even if some can't be emailed, they are listed for the teachers
+        for invite in &invites {
+            if let Err(err) = send_invite_email(data_loader, mailer, &inviter, &class, invite).await
+            {
+                tracing::error!("Failed to email invite {}: {err:?}", invite.id);
+            }
+        }
+
+        Ok(invites.into_iter().map(|invite| invite.into()).collect())
+    }
+
+    /// Revokes the invite. It stays in `invites` together with everyone who used it.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
     pub async fn delete_invite(
         &self,
         ctx: &Context<'_>,
@@ -327,10 +842,386 @@ impl ClassMutation {
         invite_id: ID,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
 
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
         let invite_id = Uuid::parse_str(invite_id.as_str())?;
-        ClassRepo::delete_invite(data_loader, invite_id).await?;
+        let revoked = ClassRepo::revoke_invite(data_loader, class_id, invite_id, user_id).await?;
 
-        Ok(true)
+        Ok(revoked)
+    }
+}
+
+/// Emails the invite in the language of the invited user, or of the inviter if the address
+/// doesn't belong to anyone yet.
+async fn send_invite_email(
+    data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
+    inviter: &user::Model,
+    class: &class::Model,
+    invite: &invite::Model,
+) -> Result<(), AppError> {
+    let to = invite.email.clone().expect("Email invites have an address");
+    let locale = UserRepo::find_by_email(data_loader, to.clone())
+        .await?
+        .map(|user| user.locale)
+        .unwrap_or_else(|| inviter.locale.clone());
+
+    let mut context = tera::Context::new();
+    context.insert(
+        "inviter",
+        &format!("{} {}", inviter.first_name, inviter.last_name),
+    );
+    context.insert("class_name", &class.name);
+    context.insert(
+        "link",
+        &format!("{}/invite/{}", HOST_URL.as_str(), invite.id),
+    );
+    context.insert("code", &invite.code);
+    let email = email_templates::render(EmailTemplate::ClassInvite, &locale, to, &context)?;
+    mailer.send(email).await?;
+
+    Ok(())
+}
+
+/// The pending join request `request_id` of the class.
+async fn pending_join_request(
+    data_loader: &DataLoader<DatabaseConnection>,
+    class_id: Uuid,
+    request_id: ID,
+) -> Result<join_request::Model, AppError> {
+    let request_id = Uuid::parse_str(request_id.as_str())?;
+    match ClassRepo::find_join_request(data_loader, request_id).await? {
+        Some(request) if request.class_id == class_id => {
+            if request.status != sea_orm_active_enums::JoinRequestStatus::Pending {
+                return Err(already_decided(request_id));
+            }
+            Ok(request)
+        }
+        _ => Err(AppError::not_found(
+            "Join request not found".into(),
+            "join_request",
+            "id",
+            request_id.to_string(),
+        )),
     }
 }
+
+fn already_decided(request_id: Uuid) -> AppError {
+    AppError::user(
+        "Join request was already decided",
+        UserError::BadInput {
+            parameter: "request_id",
+            given_value: request_id.to_string(),
+        },
+    )
+}
+
+/// Emails the user of a decided request. The decision stands even if the email can't be sent.
+async fn send_join_decision_email(
+    data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
+    request: &join_request::Model,
+    user: &user::Model,
+) {
+    let res = async {
+        let class = ClassRepo::find_by_id(data_loader, request.class_id)
+            .await?
+            .expect("Guard ensures the class exists");
+
+        let mut context = tera::Context::new();
+        context.insert("username", &user.username);
+        context.insert("class_name", &class.name);
+        context.insert(
+            "approved",
+            &(request.status == sea_orm_active_enums::JoinRequestStatus::Approved),
+        );
+        context.insert("link", &format!("{}/class/{}", HOST_URL.as_str(), class.id));
+        let email = email_templates::render(
+            EmailTemplate::JoinRequestDecision,
+            &user.locale,
+            user.email.clone(),
+            &context,
+        )?;
+        mailer.send(email).await
+    }
+    .await;
+
+    if let Err(err) = res {
+        tracing::error!(
+            "Failed to email {} about join request {}: {err:?}",
+            user.id,
+            request.id
+        );
+    }
+}
+
+/// Tells the owner of the class about a new join request.
+async fn send_join_request_email(
+    data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
+    class: &class::Model,
+    requester: &user::Model,
+    request: &join_request::Model,
+) -> Result<(), AppError> {
+    let owner = UserRepo::find_by_id(data_loader, class.owner_id)
+        .await?
+        .expect("Class owner exists");
+
+    let mut context = tera::Context::new();
+    context.insert("username", &owner.username);
+    context.insert(
+        "requester",
+        &format!("{} {}", requester.first_name, requester.last_name),
+    );
+    context.insert("class_name", &class.name);
+    context.insert("note", &request.note);
+    context.insert("link", &format!("{}/class/{}", HOST_URL.as_str(), class.id));
+    let email = email_templates::render(
+        EmailTemplate::JoinRequest,
+        &owner.locale,
+        owner.email,
+        &context,
+    )?;
+    mailer.send(email).await?;
+
+    Ok(())
+}
+
+/// Joins the caller to the class, through `invite` when it is given. Private classes can
+/// only be joined with a usable invite, public ones skip invites that can't be used.
+async fn join_class(
+    ctx: &Context<'_>,
+    class_id: Uuid,
+    invite: Option<invite::Model>,
+) -> Result<ID, AppError> {
+    let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+    let claims = ctx.data_unchecked::<Option<Claims>>();
+    let redis_pool = ctx.data_unchecked::<Pool>();
+    let mut conn = redis_pool.get().await?;
+
+    let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+
+    let class = ClassRepo::find_by_id(data_loader, class_id).await?;
+    let class = match class {
+        Some(class) => class,
+        None => {
+            return Err(AppError::not_found(
+                "Class not found".into(),
+                "class",
+                "id",
+                class_id.to_string(),
+            ))
+        }
+    };
+
+    ensure_not_archived(data_loader, class_id).await?;
+
+    let user = UserRepo::find_by_id(data_loader, user_id)
+        .await?
+        .expect("user exists");
+    let now = Utc::now().naive_utc();
+    let invite = match invite {
+        Some(invite) => match invites::check_invite(&invite, class_id, &user.email, now) {
+            Ok(()) => Some(invite),
+            Err(_) if class.public => None,
+            Err(err) => return Err(err),
+        },
+        None => None,
+    };
+
+    if !class.public && invite.is_none() {
+        return Err(AppError::user(
+            "You cannot join private class without explicit invite",
+            UserError::BadInput {
+                parameter: "class_id",
+                given_value: class_id.to_string(),
+            },
+        ));
+    }
+
+    let members = UserRepo::find_by_class_id(data_loader, class_id)
+        .await?
+        .expect("class id is valid");
+
+    if members.iter().any(|m| m.id == user_id) {
+        return Err(AppError::user(
+            "You cannot join class your're already member of",
+            UserError::BadInput {
+                parameter: "class_id",
+                given_value: class_id.to_string(),
+            },
+        ));
+    }
+
+    let bans = ClassRepo::get_user_bans(data_loader, user_id).await?;
+    if bans.iter().any(|b| *b == class_id) {
+        return Err(AppError::user(
+            "You cannot join class you're banned from",
+            UserError::BadInput {
+                parameter: "class_id",
+                given_value: class_id.to_string(),
+            },
+        ));
+    }
+
+    match invite {
+        Some(invite) => {
+            let invite_id = invite.id;
+            if !ClassRepo::redeem_invite(data_loader, invite, user_id).await? {
+                return Err(AppError::user(
+                    "Invite has been used up",
+                    UserError::BadInput {
+                        parameter: "invite_id",
+                        given_value: invite_id.to_string(),
+                    },
+                ));
+            }
+        }
+        None => {
+            ClassRepo::join_user_to_class(data_loader, user_id, class_id).await?;
+        }
+    }
+
+    let update_data = ClassResourceCreate::Member(user.into());
+    conn.publish(
+        format!("{}:{}", CLASS_RESOURCE_CREATED, class_id),
+        serde_json::to_string(&update_data).expect("User should serialize"),
+    )
+    .await?;
+
+    Ok(ID::from(class_id))
+}
+
+/// Fails unless the pending transfer of the class was offered to `user_id`.
+async fn pending_transfer_to(
+    data_loader: &DataLoader<DatabaseConnection>,
+    class_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    match ClassRepo::find_ownership_transfer(data_loader, class_id).await? {
+        Some(transfer) if transfer.user_id == user_id => Ok(()),
+        _ => Err(AppError::not_found(
+            "No ownership transfer was offered to you".into(),
+            "ownership_transfer",
+            "class_id",
+            class_id.to_string(),
+        )),
+    }
+}
+
+async fn transfer_ownership(
+    ctx: &Context<'_>,
+    class_id: Uuid,
+    user_id: Uuid,
+) -> Result<ClassObject, AppError> {
+    let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+    let redis_pool = ctx.data_unchecked::<Pool>();
+    let mut conn = redis_pool.get().await?;
+
+    let updated = ClassRepo::transfer_ownership(data_loader, class_id, user_id).await?;
+    let updated = ClassObject::from(updated);
+
+    let update_data = ClassResourceUpdate::Class(updated.clone());
+    conn.publish(
+        format!("{}:{}", CLASS_RESOURCE_UPDATED, class_id),
+        serde_json::to_string(&update_data).expect("Class should serialize"),
+    )
+    .await?;
+
+    Ok(updated)
+}
+
+async fn set_archived(
+    ctx: &Context<'_>,
+    class_id: ID,
+    archived: bool,
+) -> Result<ClassObject, AppError> {
+    let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+    let redis_pool = ctx.data_unchecked::<Pool>();
+    let mut conn = redis_pool.get().await?;
+
+    let class_id = Uuid::parse_str(class_id.as_str())?;
+    let updated = ClassRepo::set_archived(data_loader, class_id, archived).await?;
+    let updated = ClassObject::from(updated);
+
+    let update_data = ClassResourceUpdate::Class(updated.clone());
+    conn.publish(
+        format!("{}:{}", CLASS_RESOURCE_UPDATED, class_id),
+        serde_json::to_string(&update_data).expect("Class should serialize"),
+    )
+    .await?;
+
+    Ok(updated)
+}
+
+/// Moves a member to `role`, which has to be above their current role when promoting and
+/// below it when demoting. Nobody can change the role of someone at or above their own role,
+/// or hand out a role at or above their own.
+async fn change_member_role(
+    ctx: &Context<'_>,
+    class_id: ID,
+    user_id: ID,
+    role: ClassRole,
+    promote: bool,
+) -> Result<MemberObject, AppError> {
+    let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+    let claims = ctx.data_unchecked::<Option<Claims>>();
+    let redis_pool = ctx.data_unchecked::<Pool>();
+    let mut conn = redis_pool.get().await?;
+
+    let caller_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+    let class_id = Uuid::parse_str(class_id.as_str())?;
+    let user_id = Uuid::parse_str(user_id.as_str())?;
+
+    let caller_role = class_role(data_loader, caller_id, class_id)
+        .await?
+        .expect("Guard ensures membership");
+    let current = class_role(data_loader, user_id, class_id)
+        .await?
+        .ok_or_else(|| {
+            AppError::not_found(
+                "Member not found".into(),
+                "membership",
+                "user_id",
+                user_id.to_string(),
+            )
+        })?;
+    if current >= caller_role || role >= caller_role {
+        return Err(AppError::auth(
+            "Roles can only be changed below your own role",
+        ));
+    }
+
+    let moves_the_right_way = if promote {
+        role > current
+    } else {
+        role < current
+    };
+    let member_role = role.member_role().filter(|_| moves_the_right_way);
+    let Some(member_role) = member_role else {
+        return Err(AppError::user(
+            format!("Member is {current:?}, cannot change their role to {role:?}"),
+            UserError::BadInput {
+                parameter: "role",
+                given_value: format!("{role:?}"),
+            },
+        ));
+    };
+
+    ClassRepo::set_member_role(data_loader, class_id, user_id, member_role).await?;
+    let user = UserRepo::find_by_id(data_loader, user_id)
+        .await?
+        .expect("Member must exist");
+    let member = MemberObject::new(class_id, user, role);
+
+    let update_data = ClassResourceUpdate::Member(member.clone());
+    conn.publish(
+        format!("{}:{}", CLASS_RESOURCE_UPDATED, class_id),
+        serde_json::to_string(&update_data).expect("Member should serialize"),
+    )
+    .await?;
+
+    Ok(member)
+}
@@ -2,27 +2,39 @@ use crate::api::assignment::AssignmentObject;
 use crate::api::assignment::AssignmentRepo;
 use crate::api::channel::ChannelObject;
 use crate::api::channel::ChannelRepo;
+use crate::api::class::repo::{ClassRecommendation, ClassSearchHit};
+use crate::api::class::ClassRepo;
 use crate::api::file::FileObject;
 use crate::api::file::FileRepo;
 use crate::api::user::UserObject;
 use crate::api::user::UserRepo;
+use crate::core::class_role;
+use crate::core::invites;
 use crate::core::option_to_active_value;
+use crate::core::pagination::{contains_pattern, OrderDirection};
+use crate::core::recommendations;
+use crate::core::search;
 use crate::core::AppError;
 use crate::core::Claims;
+use crate::core::ClassRole;
+use crate::core::ClassRoleGuard;
 use crate::core::LoggedInGuard;
+use crate::core::TokenScopeGuard;
 use async_graphql::Upload;
 use async_graphql::{
-    dataloader::DataLoader, ComplexObject, Context, InputObject, SimpleObject, ID,
+    dataloader::DataLoader, ComplexObject, Context, Enum, InputObject, SimpleObject, ID,
 };
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use deadpool_redis::redis;
+use entity::sea_orm_active_enums;
 use partialdebug::placeholder::PartialDebug;
 use redis::FromRedisValue;
 use redis::RedisResult;
 use redis::RedisWrite;
 use redis::ToRedisArgs;
-use sea_orm::{DatabaseConnection, Set};
+use sea_orm::sea_query::{Expr, Func, Query, SimpleExpr};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, Set};
 use serde::Deserialize;
 use serde::Serialize;
 use tracing::instrument;
@@ -40,12 +52,14 @@ pub struct ClassObject {
     pub tags: Vec<String>,
     pub has_image: bool,
     pub deleted_at: Option<NaiveDateTime>,
+    /// Archived classes stay readable to their members, but nothing new can be added to them.
+    pub archived_at: Option<NaiveDateTime>,
 }
 
 #[ComplexObject]
 impl ClassObject {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
     async fn channels(&self, ctx: &Context<'_>) -> Result<Vec<ChannelObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let channels = ChannelRepo::find_by_class_id(data_loader, Uuid::parse_str(&self.id)?)
@@ -56,7 +70,7 @@ impl ClassObject {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
     async fn members(&self, ctx: &Context<'_>) -> Result<Vec<UserObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let class_id = Uuid::parse_str(&self.id)?;
@@ -67,16 +81,43 @@ impl ClassObject {
         Ok(users.into_iter().map(UserObject::from).collect())
     }
 
+    /// Members together with their role, the owner included.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(self.id.clone(), ClassRole::Observer))"
+    )]
+    async fn memberships(&self, ctx: &Context<'_>) -> Result<Vec<MemberObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let class_id = Uuid::parse_str(&self.id)?;
+        let owner_id = Uuid::parse_str(&self.owner_id)?;
+        let memberships = ClassRepo::get_memberships(data_loader, class_id).await?;
+
+        Ok(memberships
+            .into_iter()
+            .map(|(membership, user)| {
+                let role = if user.id == owner_id {
+                    ClassRole::Owner
+                } else {
+                    membership.role.into()
+                };
+                MemberObject::new(class_id, user, role)
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
     async fn files(&self, ctx: &Context<'_>) -> Result<Vec<FileObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
         let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
 
-        let is_owner = user_id == Uuid::parse_str(&self.owner_id)?;
         let class_id = Uuid::parse_str(&self.id)?;
-        let files = FileRepo::find_by_class_id(data_loader, class_id, is_owner)
+        let with_private = matches!(
+            class_role(data_loader, user_id, class_id).await?,
+            Some(role) if role >= ClassRole::TeachingAssistant
+        );
+        let files = FileRepo::find_by_class_id(data_loader, class_id, with_private)
             .await?
             .expect("Id should be valid");
 
@@ -84,7 +125,7 @@ impl ClassObject {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
     async fn owner(&self, ctx: &Context<'_>) -> Result<UserObject, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
 
@@ -96,8 +137,39 @@ impl ClassObject {
         Ok(UserObject::from(user))
     }
 
+    /// Only visible to the owner, the user the class is offered to and admins.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
+    async fn pending_ownership_transfer(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<OwnershipTransferObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        let user_id = Uuid::parse_str(&claims.sub)?;
+        let class_id = Uuid::parse_str(&self.id)?;
+        let Some(transfer) = ClassRepo::find_ownership_transfer(data_loader, class_id).await?
+        else {
+            return Ok(None);
+        };
+
+        let owner_id = Uuid::parse_str(&self.owner_id)?;
+        let is_involved = user_id == owner_id || user_id == transfer.user_id;
+        let is_admin = !claims.is_class_scoped()
+            && matches!(
+                UserRepo::find_by_id(data_loader, user_id).await?,
+                Some(user) if user.user_type == sea_orm_active_enums::UserType::Admin
+            );
+        match is_involved || is_admin {
+            true => Ok(Some(transfer.into())),
+            false => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.id.clone()))")]
     async fn assignments(&self, ctx: &Context<'_>) -> Result<Vec<AssignmentObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
 
@@ -124,6 +196,7 @@ impl From<::entity::class::Model> for ClassObject {
             tags: c.tags.split(' ').map(|s| s.to_string()).collect(),
             has_image: c.has_image,
             deleted_at: c.deleted_at,
+            archived_at: c.archived_at,
         }
     }
 }
@@ -144,6 +217,9 @@ impl ToRedisArgs for ClassObject {
             self.deleted_at
                 .map(|d| d.timestamp().to_string())
                 .unwrap_or("".into()),
+            self.archived_at
+                .map(|d| d.timestamp().to_string())
+                .unwrap_or("".into()),
         ];
         vec.write_redis_args(out)
     }
@@ -168,10 +244,36 @@ impl FromRedisValue for ClassObject {
                     0,
                 ))
             },
+            archived_at: if vec[8].is_empty() {
+                None
+            } else {
+                Some(chrono::NaiveDateTime::from_timestamp(
+                    vec[8].parse::<i64>().unwrap(),
+                    0,
+                ))
+            },
         })
     }
 }
 
+#[derive(Clone, Debug, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "Member")]
+pub struct MemberObject {
+    pub class_id: ID,
+    pub user: UserObject,
+    pub role: ClassRole,
+}
+
+impl MemberObject {
+    pub fn new(class_id: Uuid, user: ::entity::user::Model, role: ClassRole) -> Self {
+        Self {
+            class_id: ID::from(class_id),
+            user: user.into(),
+            role,
+        }
+    }
+}
+
 #[derive(InputObject, PartialDebug)]
 pub struct CreateClassInput {
     #[graphql(validator(min_length = 1, max_length = 35))]
@@ -198,6 +300,40 @@ impl CreateClassInput {
             tags: Set(self.tags),
             has_image: Set(has_image),
             deleted_at: Set(None),
+            archived_at: Set(None),
+        }
+    }
+}
+
+#[derive(InputObject, Debug)]
+pub struct CloneClassInput {
+    /// Defaults to the name of the cloned class.
+    #[graphql(validator(min_length = 1, max_length = 35))]
+    pub name: Option<String>,
+    /// Defaults to the visibility of the cloned class.
+    pub public: Option<bool>,
+    /// Moves every due date by this many days, negative values move them back.
+    /// At most ten years either way.
+    #[graphql(validator(minimum = -3650, maximum = 3650))]
+    pub due_date_offset_days: Option<i32>,
+}
+
+impl CloneClassInput {
+    pub fn into_active_model(
+        self,
+        source: &::entity::class::Model,
+        owner_id: Uuid,
+    ) -> ::entity::class::ActiveModel {
+        ::entity::class::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            name: Set(self.name.unwrap_or_else(|| source.name.clone())),
+            description: Set(source.description.clone()),
+            owner_id: Set(owner_id),
+            public: Set(self.public.unwrap_or(source.public)),
+            tags: Set(source.tags.clone()),
+            has_image: Set(source.has_image),
+            deleted_at: Set(None),
+            archived_at: Set(None),
         }
     }
 }
@@ -225,16 +361,53 @@ impl UpdateClassInput {
             tags: option_to_active_value(self.tags),
             has_image: NotSet,
             deleted_at: NotSet,
+            archived_at: NotSet,
         }
     }
 }
 
 #[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
 pub struct InviteObject {
     pub id: ID,
     pub class_id: ID,
     pub multiuse: bool,
     pub expires_at: Option<NaiveDateTime>,
+    /// Short code that can be typed in instead of following the invite link.
+    pub code: Option<String>,
+    /// Only the user with this email address can use the invite.
+    pub email: Option<String>,
+    /// `None` for multiuse invites without a limit.
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created_by: Option<ID>,
+    pub created_at: NaiveDateTime,
+    pub revoked_by: Option<ID>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[ComplexObject]
+impl InviteObject {
+    /// Who joined through this invite and when, oldest first.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::class(self.class_id.clone()))")]
+    async fn redemptions(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<InviteRedemptionObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let invite_id = Uuid::parse_str(&self.id)?;
+        let redemptions = ClassRepo::get_invite_redemptions(data_loader, invite_id).await?;
+
+        Ok(redemptions
+            .into_iter()
+            .map(|(redemption, user)| InviteRedemptionObject {
+                user: user.into(),
+                redeemed_at: redemption.redeemed_at,
+            })
+            .collect())
+    }
 }
 
 impl From<::entity::invite::Model> for InviteObject {
@@ -244,6 +417,91 @@ impl From<::entity::invite::Model> for InviteObject {
             class_id: ID::from(i.class_id),
             multiuse: i.multiuse,
             expires_at: i.expires_at,
+            max_uses: invites::max_uses(&i),
+            code: i.code,
+            email: i.email,
+            uses: i.uses,
+            created_by: i.created_by.map(ID::from),
+            created_at: i.created_at,
+            revoked_by: i.revoked_by.map(ID::from),
+            revoked_at: i.revoked_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct InviteRedemptionObject {
+    pub user: UserObject,
+    pub redeemed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
+pub enum JoinRequestStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl From<sea_orm_active_enums::JoinRequestStatus> for JoinRequestStatus {
+    fn from(e: sea_orm_active_enums::JoinRequestStatus) -> Self {
+        match e {
+            sea_orm_active_enums::JoinRequestStatus::Pending => Self::Pending,
+            sea_orm_active_enums::JoinRequestStatus::Approved => Self::Approved,
+            sea_orm_active_enums::JoinRequestStatus::Denied => Self::Denied,
+        }
+    }
+}
+
+impl From<JoinRequestStatus> for sea_orm_active_enums::JoinRequestStatus {
+    fn from(e: JoinRequestStatus) -> Self {
+        match e {
+            JoinRequestStatus::Pending => Self::Pending,
+            JoinRequestStatus::Approved => Self::Approved,
+            JoinRequestStatus::Denied => Self::Denied,
+        }
+    }
+}
+
+/// A request to join a private class without an invite, decided by its teachers.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct JoinRequestObject {
+    pub id: ID,
+    pub class_id: ID,
+    pub user: UserObject,
+    pub note: Option<String>,
+    pub status: JoinRequestStatus,
+    pub created_at: NaiveDateTime,
+    pub decided_at: Option<NaiveDateTime>,
+}
+
+impl JoinRequestObject {
+    pub fn new(request: ::entity::join_request::Model, user: ::entity::user::Model) -> Self {
+        Self {
+            id: ID::from(request.id),
+            class_id: ID::from(request.class_id),
+            user: user.into(),
+            note: request.note,
+            status: request.status.into(),
+            created_at: request.created_at,
+            decided_at: request.decided_at,
+        }
+    }
+}
+
+/// Ownership the owner offered to a member, waiting for them to accept it.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct OwnershipTransferObject {
+    pub class_id: ID,
+    pub user_id: ID,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<::entity::ownership_transfer::Model> for OwnershipTransferObject {
+    fn from(t: ::entity::ownership_transfer::Model) -> Self {
+        Self {
+            class_id: ID::from(t.class_id),
+            user_id: ID::from(t.user_id),
+            created_at: t.created_at,
         }
     }
 }
@@ -253,16 +511,264 @@ pub struct CreateInviteInput {
     pub class_id: ID,
     pub multiuse: bool,
     pub expires_at: Option<NaiveDateTime>,
+    /// Limits how often a multiuse invite can be used.
+    #[graphql(validator(minimum = 1))]
+    pub max_uses: Option<i32>,
 }
 
 impl CreateInviteInput {
-    pub fn try_into_active_model(self) -> Result<::entity::invite::ActiveModel, AppError> {
+    pub fn try_into_active_model(
+        self,
+        created_by: Uuid,
+    ) -> Result<::entity::invite::ActiveModel, AppError> {
         Ok(::entity::invite::ActiveModel {
             id: Set(Uuid::new_v4()),
             class_id: Set(Uuid::parse_str(&self.class_id)?),
             multiuse: Set(self.multiuse),
             expires_at: Set(self.expires_at),
+            code: Set(Some(invites::generate_join_code())),
+            max_uses: Set(self.max_uses.filter(|_| self.multiuse)),
+            created_by: Set(Some(created_by)),
+            created_at: Set(Utc::now().naive_utc()),
             ..Default::default()
         })
     }
 }
+
+/// Single use invites, each addressed to and emailed to one of `emails`.
+#[derive(Clone, Debug, InputObject)]
+pub struct CreateEmailInvitesInput {
+    pub class_id: ID,
+    #[graphql(validator(list, email))]
+    pub emails: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl CreateEmailInvitesInput {
+    pub fn try_into_active_models(
+        self,
+        created_by: Uuid,
+    ) -> Result<Vec<::entity::invite::ActiveModel>, AppError> {
+        let class_id = Uuid::parse_str(&self.class_id)?;
+        let now = Utc::now().naive_utc();
+
+        Ok(self
+            .emails
+            .into_iter()
+            .map(|email| ::entity::invite::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                class_id: Set(class_id),
+                multiuse: Set(false),
+                expires_at: Set(self.expires_at),
+                code: Set(Some(invites::generate_join_code())),
+                email: Set(Some(email.trim().to_lowercase())),
+                created_by: Set(Some(created_by)),
+                created_at: Set(now),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct ClassFilter {
+    pub public: Option<bool>,
+    pub deleted: Option<bool>,
+    pub archived: Option<bool>,
+    pub owner_id: Option<ID>,
+    /// Matched against the name, description and tags.
+    pub search: Option<String>,
+}
+
+impl ClassFilter {
+    pub fn try_into_condition(self) -> Result<Condition, AppError> {
+        let mut condition = Condition::all();
+        if let Some(public) = self.public {
+            condition = condition.add(::entity::class::Column::Public.eq(public));
+        }
+        if let Some(deleted) = self.deleted {
+            condition = condition.add(match deleted {
+                true => ::entity::class::Column::DeletedAt.is_not_null(),
+                false => ::entity::class::Column::DeletedAt.is_null(),
+            });
+        }
+        if let Some(archived) = self.archived {
+            condition = condition.add(match archived {
+                true => ::entity::class::Column::ArchivedAt.is_not_null(),
+                false => ::entity::class::Column::ArchivedAt.is_null(),
+            });
+        }
+        if let Some(owner_id) = self.owner_id {
+            condition =
+                condition.add(::entity::class::Column::OwnerId.eq(Uuid::parse_str(&owner_id)?));
+        }
+        if let Some(search) = self.search.filter(|s| !s.trim().is_empty()) {
+            let pattern = contains_pattern(&search);
+            let mut any = Condition::any();
+            for column in [
+                ::entity::class::Column::Name,
+                ::entity::class::Column::Description,
+                ::entity::class::Column::Tags,
+            ] {
+                any = any.add(Expr::expr(Func::lower(Expr::col(column))).like(pattern.as_str()));
+            }
+            condition = condition.add(any);
+        }
+        Ok(condition)
+    }
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClassOrderField {
+    Name,
+    Public,
+}
+
+impl From<ClassOrderField> for ::entity::class::Column {
+    fn from(field: ClassOrderField) -> Self {
+        match field {
+            ClassOrderField::Name => Self::Name,
+            ClassOrderField::Public => Self::Public,
+        }
+    }
+}
+
+/// Classes are ordered by name when no order is given.
+#[derive(Copy, Clone, Debug, InputObject)]
+pub struct ClassOrder {
+    pub field: ClassOrderField,
+    pub direction: OrderDirection,
+}
+
+impl Default for ClassOrder {
+    fn default() -> Self {
+        Self {
+            field: ClassOrderField::Name,
+            direction: OrderDirection::Asc,
+        }
+    }
+}
+
+/// Narrows down `searchClasses`. Only public classes that are neither deleted nor archived
+/// are ever searched.
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct ClassSearchFilter {
+    /// Only classes having all of these tags.
+    pub tags: Option<Vec<String>>,
+    pub owner_id: Option<ID>,
+}
+
+impl ClassSearchFilter {
+    /// Condition for classes matching `query` and the filter, leaving out `exceptions`.
+    /// An empty query matches every class.
+    pub fn try_into_condition(
+        self,
+        query: &str,
+        exceptions: Vec<Uuid>,
+    ) -> Result<Condition, AppError> {
+        let mut condition = Condition::all()
+            .add(::entity::class::Column::DeletedAt.is_null())
+            .add(::entity::class::Column::ArchivedAt.is_null())
+            .add(::entity::class::Column::Public.eq(true))
+            .add(::entity::class::Column::Id.is_not_in(exceptions));
+        if !query.trim().is_empty() {
+            condition = condition.add(search::matches(query));
+        }
+        let tags = self
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>();
+        if !tags.is_empty() {
+            condition = condition.add(search::has_tags(tags));
+        }
+        if let Some(owner_id) = self.owner_id {
+            condition =
+                condition.add(::entity::class::Column::OwnerId.eq(Uuid::parse_str(&owner_id)?));
+        }
+        Ok(condition)
+    }
+}
+
+/// Classes linked to the tag called `name`.
+pub fn tagged_with(name: String) -> SimpleExpr {
+    ::entity::class::Column::Id.in_subquery(
+        Query::select()
+            .column((
+                ::entity::class_tag::Entity,
+                ::entity::class_tag::Column::ClassId,
+            ))
+            .from(::entity::class_tag::Entity)
+            .inner_join(
+                ::entity::tag::Entity,
+                Expr::col((::entity::tag::Entity, ::entity::tag::Column::Id)).equals((
+                    ::entity::class_tag::Entity,
+                    ::entity::class_tag::Column::TagId,
+                )),
+            )
+            .and_where(Expr::col((::entity::tag::Entity, ::entity::tag::Column::Name)).eq(name))
+            .to_owned(),
+    )
+}
+
+/// A piece of a highlighted text, `matched` when it matched the search query.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "Highlight")]
+pub struct HighlightObject {
+    pub text: String,
+    pub matched: bool,
+}
+
+fn highlights(headline: &str) -> Vec<HighlightObject> {
+    search::split_highlights(headline)
+        .into_iter()
+        .map(|(text, matched)| HighlightObject { text, matched })
+        .collect()
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "ClassSearchResult")]
+pub struct ClassSearchResultObject {
+    pub class: ClassObject,
+    /// The whole name of the class.
+    pub name: Vec<HighlightObject>,
+    /// Up to two passages of the description around the matches.
+    pub snippet: Vec<HighlightObject>,
+}
+
+impl From<ClassSearchHit> for ClassSearchResultObject {
+    fn from(hit: ClassSearchHit) -> Self {
+        Self {
+            name: highlights(&hit.name_headline),
+            snippet: highlights(&hit.description_headline),
+            class: hit.class.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "ClassRecommendation")]
+pub struct ClassRecommendationObject {
+    pub class: ClassObject,
+    /// Why the class was recommended, like `Tagged rust like your classes`.
+    pub reason: String,
+    /// Tags of the class that also appear on the classes of the viewer.
+    pub shared_tags: Vec<String>,
+}
+
+impl From<ClassRecommendation> for ClassRecommendationObject {
+    fn from(r: ClassRecommendation) -> Self {
+        Self {
+            reason: recommendations::reason(
+                &r.shared_tags,
+                r.new_members,
+                r.recent_messages,
+                r.members,
+            ),
+            shared_tags: r.shared_tags,
+            class: r.class.into(),
+        }
+    }
+}
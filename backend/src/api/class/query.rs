@@ -1,17 +1,27 @@
-use crate::{api::UserRepo, core::ClassOwnerGuard};
+use crate::{api::UserRepo, core::ClassRoleGuard};
+use async_graphql::connection::{self, Connection, Edge};
 use async_graphql::{dataloader::DataLoader, Context, Object, ID};
+use chrono::Utc;
+use deadpool_redis::Pool;
 
 use entity::sea_orm_active_enums::UserType;
-use sea_orm::DatabaseConnection;
+use sea_orm::{DatabaseConnection, Order};
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::{
     api::user::UserObject,
-    core::{AppError, Claims, ClassMemberGuard, LoggedInGuard},
+    core::invites,
+    core::pagination::{self, TotalCount},
+    core::tags,
+    core::{AdminGuard, AppError, Claims, ClassRole, LoggedInGuard, TokenScopeGuard},
 };
 
-use super::{object::InviteObject, ClassObject, ClassRepo};
+use super::object::{
+    tagged_with, ClassFilter, ClassOrder, ClassRecommendationObject, ClassSearchFilter,
+    ClassSearchResultObject, InviteObject, JoinRequestObject, JoinRequestStatus,
+};
+use super::{ClassObject, ClassRepo};
 
 #[derive(Default)]
 pub struct ClassQuery;
@@ -19,7 +29,7 @@ pub struct ClassQuery;
 #[Object]
 impl ClassQuery {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(id.clone()))")]
+    #[graphql(guard = "LoggedInGuard.and(ClassRoleGuard::new(id.clone(), ClassRole::Observer))")]
     async fn class_by_id(
         &self,
         ctx: &Context<'_>,
@@ -32,8 +42,12 @@ impl ClassQuery {
         Ok(c.map(|c| c.into()))
     }
 
+    /// First page of `recommendedClasses`.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(
+        guard = "LoggedInGuard.and(TokenScopeGuard::account())",
+        deprecation = "Use `recommendedClasses`, which explains its picks and pages through them"
+    )]
     async fn random_classes(&self, ctx: &Context<'_>) -> Result<Vec<ClassObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
@@ -45,14 +59,69 @@ impl ClassQuery {
                 .sub
                 .as_str(),
         )?;
-        let banned_in = ClassRepo::get_user_bans(data_loader, user_id).await?;
-        let c = ClassRepo::find_random(data_loader, 10, banned_in).await?;
+        let c = ClassRepo::find_recommendations(data_loader, user_id, 0, 10).await?;
 
-        Ok(c.into_iter().map(|c| c.into()).collect())
+        Ok(c.into_iter().map(|r| r.class.into()).collect())
+    }
+
+    /// Public classes the caller isn't in, ranked by the tags they share with the caller's
+    /// classes, how many people joined them lately and how active they are.
+    /// 25 per page unless `first` or `last` is given.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn recommended_classes(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, ClassRecommendationObject, TotalCount>, async_graphql::Error>
+    {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total = ClassRepo::count_recommendations(data_loader, user_id).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let recommendations = ClassRepo::find_recommendations(
+                    data_loader,
+                    user_id,
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = recommendations
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, r)| Edge::new(page.offset + i, ClassRecommendationObject::from(r)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
     }
 
+    /// First page of `searchClasses`, or of `recommendedClasses` for an empty query.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(
+        guard = "LoggedInGuard.and(TokenScopeGuard::account())",
+        deprecation = "Use `searchClasses`, which pages through all results"
+    )]
     async fn classes_by_search(
         &self,
         ctx: &Context<'_>,
@@ -69,29 +138,203 @@ impl ClassQuery {
                 .as_str(),
         )?;
 
-        let user = UserRepo::find_by_id(data_loader, user_id)
-            .await?
-            .expect("User must exist");
-
-        match user.user_type {
-            UserType::Admin => {
-                let c = ClassRepo::find_all(data_loader).await?;
-                return Ok(c.into_iter().map(|c| c.into()).collect());
-            }
+        let c = match query.trim() {
+            "" => ClassRepo::find_recommendations(data_loader, user_id, 0, 10)
+                .await?
+                .into_iter()
+                .map(|r| r.class)
+                .collect(),
             _ => {
-                let banned_in = ClassRepo::get_user_bans(data_loader, user_id).await?;
-                let c = match query.as_str() {
-                    "" => ClassRepo::find_random(data_loader, 10, banned_in).await?,
-                    _ => ClassRepo::find_by_query(data_loader, query, banned_in).await?,
-                };
-
-                Ok(c.into_iter().map(|c| c.into()).collect())
+                let banned_in = search_exceptions(data_loader, user_id).await?;
+                let condition =
+                    ClassSearchFilter::default().try_into_condition(&query, banned_in)?;
+                ClassRepo::search_classes(
+                    data_loader,
+                    query,
+                    condition,
+                    0,
+                    pagination::DEFAULT_PAGE_SIZE as u64,
+                )
+                .await?
+                .into_iter()
+                .map(|hit| hit.class)
+                .collect()
             }
-        }
+        };
+
+        Ok(c.into_iter().map(|c| c.into()).collect())
+    }
+
+    /// Public classes matching `query`, best matches first, 25 per page unless `first` or
+    /// `last` is given. The query uses web search syntax like `rust -beginner "linear algebra"`
+    /// and an empty one lists every class passing the filter.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn search_classes(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        filter: Option<ClassSearchFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, ClassSearchResultObject, TotalCount>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let banned_in = search_exceptions(data_loader, user_id).await?;
+        let condition = filter
+            .unwrap_or_default()
+            .try_into_condition(&query, banned_in)?;
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total = ClassRepo::count_search_results(data_loader, condition.clone()).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let hits = ClassRepo::search_classes(
+                    data_loader,
+                    query,
+                    condition,
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = hits
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, hit)| Edge::new(page.offset + i, ClassSearchResultObject::from(hit)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
+    }
+
+    /// Public classes tagged with `tag`, by name, 25 per page unless `first` or `last` is given.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn classes_by_tag(
+        &self,
+        ctx: &Context<'_>,
+        tag: String,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, ClassObject, TotalCount>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let banned_in = search_exceptions(data_loader, user_id).await?;
+        let condition = ClassSearchFilter::default()
+            .try_into_condition("", banned_in)?
+            .add(tagged_with(tags::normalize_tag(&tag).unwrap_or_default()));
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total = ClassRepo::count_classes(data_loader, condition.clone()).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let classes = ClassRepo::find_classes(
+                    data_loader,
+                    condition,
+                    ::entity::class::Column::Name,
+                    Order::Asc,
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = classes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, c)| Edge::new(page.offset + i, ClassObject::from(c)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
     }
 
+    /// Directory of all classes for admins, 25 per page unless `first` or `last` is given.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    async fn classes(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ClassFilter>,
+        order: Option<ClassOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, ClassObject, TotalCount>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let condition = filter.unwrap_or_default().try_into_condition()?;
+        let order = order.unwrap_or_default();
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total = ClassRepo::count_classes(data_loader, condition.clone()).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let classes = ClassRepo::find_classes(
+                    data_loader,
+                    condition,
+                    order.field.into(),
+                    order.direction.into(),
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = classes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, c)| Edge::new(page.offset + i, ClassObject::from(c)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
     pub async fn banned_members(
         &self,
         ctx: &Context<'_>,
@@ -105,7 +348,9 @@ impl ClassQuery {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
     pub async fn invites(
         &self,
         ctx: &Context<'_>,
@@ -118,8 +363,54 @@ impl ClassQuery {
         Ok(invites.into_iter().map(|i| i.into()).collect())
     }
 
+    /// Join requests of the class, oldest first. All of them when `status` is left out.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::CoTeacher))"
+    )]
+    pub async fn join_requests(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<JoinRequestObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let requests =
+            ClassRepo::get_join_requests(data_loader, class_id, status.map(|s| s.into())).await?;
+        Ok(requests
+            .into_iter()
+            .map(|(request, user)| JoinRequestObject::new(request, user))
+            .collect())
+    }
+
+    /// The caller's latest request to join the class.
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn own_join_request(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<Option<JoinRequestObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(class_id.as_str())?;
+        let Some(request) =
+            ClassRepo::find_latest_join_request(data_loader, class_id, user_id).await?
+        else {
+            return Ok(None);
+        };
+        let user = UserRepo::find_by_id(data_loader, user_id)
+            .await?
+            .expect("user exists");
+        Ok(Some(JoinRequestObject::new(request, user)))
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
     pub async fn class_by_invite_id(
         &self,
         ctx: &Context<'_>,
@@ -131,4 +422,53 @@ impl ClassQuery {
         let class = ClassRepo::find_by_invite_id(data_loader, invite_id).await?;
         Ok(class.map(|c| c.into()))
     }
+
+    /// The class a join code leads to, to show it before joining. Fails like joining would
+    /// if the invite can't be used by the caller.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    pub async fn class_by_join_code(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> Result<Option<ClassObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        invites::throttle_join_code(redis_pool, user_id).await?;
+
+        let code = invites::normalize_join_code(&code);
+        let Some(invite) = ClassRepo::find_invite_by_code(data_loader, code).await? else {
+            return Ok(None);
+        };
+        let user = UserRepo::find_by_id(data_loader, user_id)
+            .await?
+            .expect("Guard ensures the user exists");
+        invites::check_invite(
+            &invite,
+            invite.class_id,
+            &user.email,
+            Utc::now().naive_utc(),
+        )?;
+        let class = ClassRepo::find_by_id(data_loader, invite.class_id).await?;
+        Ok(class.map(|c| c.into()))
+    }
+}
+
+/// Classes left out of a user's search results. Admins look through every public class,
+/// including the ones they are banned from.
+async fn search_exceptions(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user_id: Uuid,
+) -> Result<Vec<Uuid>, AppError> {
+    let user = UserRepo::find_by_id(data_loader, user_id)
+        .await?
+        .expect("User must exist");
+    let banned_in = match user.user_type {
+        UserType::Admin => Vec::new(),
+        _ => ClassRepo::get_user_bans(data_loader, user_id).await?,
+    };
+    Ok(banned_in)
 }
@@ -1,6 +1,10 @@
 use ::entity::{
-    channel, class_blacklist, class_blacklist::Entity as ClassBlacklist, file,
-    invite::Entity as Invite, membership, membership::Entity as Membership, sea_orm_active_enums,
+    assignment, assignment_file, channel, class_blacklist,
+    class_blacklist::Entity as ClassBlacklist, file, invite::Entity as Invite, invite_redemption,
+    invite_redemption::Entity as InviteRedemption, join_request,
+    join_request::Entity as JoinRequest, membership, membership::Entity as Membership,
+    ownership_transfer, ownership_transfer::Entity as OwnershipTransfer, sea_orm_active_enums,
+    sea_orm_active_enums::JoinRequestStatus, sea_orm_active_enums::MemberRole,
     user::Entity as User,
 };
 use ::entity::{class, class::Entity as Class};
@@ -9,13 +13,73 @@ use async_graphql::dataloader::{DataLoader, Loader};
 use async_trait::async_trait;
 use chrono::Utc;
 
+use crate::api::tag::set_class_tags;
+use crate::core::{recommendations, search, tags};
 use sea_orm::DatabaseConnection;
 use sea_orm::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::instrument;
 use uuid::Uuid;
 
+/// Root directory holding the files handed in with submissions, see `create_class`.
+const SUBMISSION_FILES_DIR: &str = "Assignment submission files";
+
+/// Rows per statement when copying a class, well below the bind parameter limit of Postgres.
+const COPY_BATCH_SIZE: usize = 1000;
+
+/// A class found by `search_classes`, with the matches in its name and description marked.
+#[derive(Debug)]
+pub struct ClassSearchHit {
+    pub class: class::Model,
+    pub name_headline: String,
+    pub description_headline: String,
+}
+
+impl FromQueryResult for ClassSearchHit {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            class: class::Model::from_query_result(res, pre)?,
+            name_headline: res.try_get(pre, "name_headline")?,
+            description_headline: res.try_get(pre, "description_headline")?,
+        })
+    }
+}
+
+/// A public class recommended by `find_recommendations`, with the signals it was ranked by.
+#[derive(Debug)]
+pub struct ClassRecommendation {
+    pub class: class::Model,
+    /// Tags of the class that also appear on the classes the user is in.
+    pub shared_tags: Vec<String>,
+    pub new_members: i64,
+    pub recent_messages: i64,
+    pub members: i64,
+}
+
+impl FromQueryResult for ClassRecommendation {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            class: class::Model::from_query_result(res, pre)?,
+            shared_tags: res.try_get(pre, "shared_tags")?,
+            new_members: res.try_get(pre, "new_members")?,
+            recent_messages: res.try_get(pre, "recent_messages")?,
+            members: res.try_get(pre, "members")?,
+        })
+    }
+}
+
+/// Public classes that can be recommended to the user `$1`: not deleted, archived,
+/// already joined or banned from.
+const RECOMMENDATION_CANDIDATES: &str = r#"
+    from "class" c
+    where c.public = true
+    and c.deleted_at is null
+    and c.archived_at is null
+    and not exists (select 1 from membership m where m.class_id = c.id and m.user_id = $1)
+    and not exists (select 1 from class_blacklist b where b.class_id = c.id and b.user_id = $1)
+"#;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 struct ClassById(Uuid);
 
@@ -120,16 +184,21 @@ pub trait ClassRepo {
         user_id: Uuid,
         class_id: Uuid,
     ) -> Result<membership::Model, DbErr>;
-    async fn find_random(
+    async fn count_search_results(&self, filter: Condition) -> Result<u64, DbErr>;
+    async fn search_classes(
         &self,
+        query: String,
+        filter: Condition,
+        offset: u64,
         limit: u64,
-        exceptions: Vec<Uuid>,
-    ) -> Result<Vec<class::Model>, TransactionError<DbErr>>;
-    async fn find_by_query(
+    ) -> Result<Vec<ClassSearchHit>, DbErr>;
+    async fn count_recommendations(&self, user_id: Uuid) -> Result<u64, DbErr>;
+    async fn find_recommendations(
         &self,
-        query: String,
-        exceptions: Vec<Uuid>,
-    ) -> Result<Vec<class::Model>, DbErr>;
+        user_id: Uuid,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<ClassRecommendation>, DbErr>;
     async fn create_class(
         &self,
         model: class::ActiveModel,
@@ -140,7 +209,7 @@ pub trait ClassRepo {
         &self,
         class_id: Uuid,
         model: class::ActiveModel,
-    ) -> Result<class::Model, DbErr>;
+    ) -> Result<class::Model, TransactionError<DbErr>>;
 
     async fn find_by_user_id(&self, user_id: Uuid)
         -> Result<Option<Vec<class::Model>>, Arc<DbErr>>;
@@ -172,13 +241,111 @@ pub trait ClassRepo {
     async fn get_user_bans(&self, user_id: Uuid) -> Result<Vec<Uuid>, DbErr>;
     async fn get_class_bans(&self, class_id: Uuid) -> Result<Vec<user::Model>, DbErr>;
     async fn create_invite(&self, model: invite::ActiveModel) -> Result<invite::Model, DbErr>;
-    async fn delete_invite(&self, invite_id: Uuid) -> Result<(), DbErr>;
+    async fn create_invites(
+        &self,
+        models: Vec<invite::ActiveModel>,
+    ) -> Result<Vec<invite::Model>, TransactionError<DbErr>>;
+    async fn revoke_invite(
+        &self,
+        class_id: Uuid,
+        invite_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, DbErr>;
     async fn get_invites(&self, class_id: Uuid) -> Result<Vec<invite::Model>, DbErr>;
-    async fn is_valid_invite(&self, invite_id: Uuid, class_id: Uuid) -> Result<bool, DbErr>;
+    async fn find_invite(&self, invite_id: Uuid) -> Result<Option<invite::Model>, DbErr>;
+    async fn find_invite_by_code(&self, code: String) -> Result<Option<invite::Model>, DbErr>;
+    async fn redeem_invite(
+        &self,
+        invite: invite::Model,
+        user_id: Uuid,
+    ) -> Result<bool, TransactionError<DbErr>>;
+    async fn get_invite_redemptions(
+        &self,
+        invite_id: Uuid,
+    ) -> Result<Vec<(invite_redemption::Model, user::Model)>, DbErr>;
     async fn get_members(&self, class_id: Uuid) -> Result<Vec<user::Model>, DbErr>;
+    async fn get_memberships(
+        &self,
+        class_id: Uuid,
+    ) -> Result<Vec<(membership::Model, user::Model)>, DbErr>;
+    async fn find_membership(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<membership::Model>, DbErr>;
+    async fn set_member_role(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+        role: MemberRole,
+    ) -> Result<membership::Model, DbErr>;
     async fn find_by_invite_id(&self, invite_id: Uuid) -> Result<Option<class::Model>, DbErr>;
-    async fn find_all(&self) -> Result<Vec<class::Model>, DbErr>;
+    async fn count_classes(&self, filter: Condition) -> Result<u64, DbErr>;
+    async fn find_classes(
+        &self,
+        filter: Condition,
+        order_by: class::Column,
+        order: Order,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<class::Model>, DbErr>;
     async fn admin_delete_class(&self, class_id: Uuid, deleted_state: bool) -> Result<(), DbErr>;
+    async fn set_archived(&self, class_id: Uuid, archived: bool) -> Result<class::Model, DbErr>;
+    async fn copy_channels(&self, source_id: Uuid, target_id: Uuid) -> Result<usize, DbErr>;
+    async fn copy_files(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<Vec<(file::Model, Uuid)>, TransactionError<DbErr>>;
+    async fn copy_assignments(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+        file_ids: HashMap<Uuid, Uuid>,
+        due_offset: Option<chrono::Duration>,
+    ) -> Result<usize, TransactionError<DbErr>>;
+    async fn propose_ownership_transfer(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<ownership_transfer::Model, TransactionError<DbErr>>;
+    async fn find_ownership_transfer(
+        &self,
+        class_id: Uuid,
+    ) -> Result<Option<ownership_transfer::Model>, DbErr>;
+    async fn delete_ownership_transfer(&self, class_id: Uuid) -> Result<bool, DbErr>;
+    async fn transfer_ownership(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<class::Model, TransactionError<DbErr>>;
+    async fn create_join_request(
+        &self,
+        model: join_request::ActiveModel,
+    ) -> Result<join_request::Model, DbErr>;
+    async fn find_join_request(&self, id: Uuid) -> Result<Option<join_request::Model>, DbErr>;
+    async fn find_latest_join_request(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<join_request::Model>, DbErr>;
+    async fn get_join_requests(
+        &self,
+        class_id: Uuid,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<(join_request::Model, user::Model)>, DbErr>;
+    async fn decide_join_request(
+        &self,
+        id: Uuid,
+        status: JoinRequestStatus,
+        decided_by: Uuid,
+    ) -> Result<Option<join_request::Model>, DbErr>;
+    async fn approve_join_request(
+        &self,
+        id: Uuid,
+        decided_by: Uuid,
+    ) -> Result<Option<(join_request::Model, bool)>, TransactionError<DbErr>>;
+    async fn delete_join_request(&self, id: Uuid) -> Result<(), DbErr>;
 }
 
 #[async_trait]
@@ -193,6 +360,7 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
             .transaction::<_, class::Model, DbErr>(|txn| {
                 Box::pin(async move {
                     let class = model.insert(txn).await?;
+                    set_class_tags(txn, class.id, &tags::split_class_tags(&class.tags)).await?;
                     let main_channel = channel::ActiveModel {
                         id: Set(Uuid::new_v4()),
                         name: Set("Main".to_string()),
@@ -204,6 +372,9 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
                     let member = membership::ActiveModel {
                         user_id: Set(class.owner_id),
                         class_id: Set(class.id),
+                        role: Set(MemberRole::CoTeacher),
+                        joined_at: Set(Some(Utc::now().naive_utc())),
+                        ..Default::default()
                     };
                     member.insert(txn).await?;
 
@@ -229,7 +400,7 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
 
                     let submission_files = file::ActiveModel {
                         id: Set(Uuid::new_v4()),
-                        name: Set("Assignment submission files".to_string()),
+                        name: Set(SUBMISSION_FILES_DIR.to_string()),
                         class_id: Set(class.id),
                         file_type: Set(sea_orm_active_enums::FileType::Directory),
                         public: Set(false),
@@ -254,27 +425,6 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         Ok(class)
     }
 
-    #[instrument(skip(self), err(Debug))]
-    async fn find_random(
-        &self,
-        limit: u64,
-        exceptions: Vec<Uuid>,
-    ) -> Result<Vec<class::Model>, TransactionError<DbErr>> {
-        let condidion = Condition::all()
-            .add(class::Column::DeletedAt.is_null())
-            .add(class::Column::Public.eq(true))
-            .add(class::Column::Id.is_not_in(exceptions));
-
-        let classes = Class::find()
-            .order_by_asc(class::Column::Id)
-            .filter(condidion)
-            .limit(Some(limit))
-            .all(self.loader())
-            .await?;
-
-        Ok(classes)
-    }
-
     #[instrument(skip(self), err(Debug))]
     async fn join_user_to_class(
         &self,
@@ -284,39 +434,136 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         let member = membership::ActiveModel {
             user_id: Set(user_id),
             class_id: Set(class_id),
+            role: Set(MemberRole::Student),
+            joined_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
         };
         let member = member.insert(self.loader()).await?;
         Ok(member)
     }
 
     #[instrument(skip(self), err(Debug))]
-    async fn find_by_query(
+    async fn count_search_results(&self, filter: Condition) -> Result<u64, DbErr> {
+        let count = Class::find().filter(filter).count(self.loader()).await?;
+        Ok(count)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn search_classes(
         &self,
         query: String,
-        exceptions: Vec<Uuid>,
-    ) -> Result<Vec<class::Model>, DbErr> {
-        // FIXME: `and id != ALL($2::uuid[])` is not working
-        let classes = Class::find()
-            .from_raw_sql(Statement::from_sql_and_values(
+        filter: Condition,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<ClassSearchHit>, DbErr> {
+        // Ties are broken by id so that offsets stay stable between pages
+        let hits = Class::find()
+            .column_as(search::headline("name", &query, 0), "name_headline")
+            .column_as(
+                search::headline("description", &query, 2),
+                "description_headline",
+            )
+            .filter(filter)
+            .order_by(search::rank(&query), Order::Desc)
+            .order_by_asc(class::Column::Id)
+            .offset(offset)
+            .limit(limit)
+            .into_model::<ClassSearchHit>()
+            .all(self.loader())
+            .await?;
+        Ok(hits)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn count_recommendations(&self, user_id: Uuid) -> Result<u64, DbErr> {
+        let count = self
+            .loader()
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                &format!("select count(*) as count {}", RECOMMENDATION_CANDIDATES),
+                [user_id.into()],
+            ))
+            .await?
+            .map(|row| row.try_get::<i64>("", "count"))
+            .transpose()?
+            .unwrap_or(0);
+        Ok(count as u64)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_recommendations(
+        &self,
+        user_id: Uuid,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<ClassRecommendation>, DbErr> {
+        let now = Utc::now().naive_utc();
+        let growth_since = now - chrono::Duration::days(recommendations::GROWTH_WINDOW_DAYS);
+        let activity_since = now - chrono::Duration::days(recommendations::ACTIVITY_WINDOW_DAYS);
+
+        // Ties are broken by id so that offsets stay stable between pages
+        let sql = format!(
+            r#"
+            with user_tags as (
+                select distinct lower(t) as tag
+                from membership m
+                join "class" c on c.id = m.class_id
+                cross join unnest(string_to_array(c.tags, ' ')) t
+                where m.user_id = $1
+                and c.deleted_at is null
+                and t <> ''
+            )
+            select *,
+                3 * cardinality(shared_tags)
+                + 2 * ln(1 + new_members)
+                + ln(1 + recent_messages)
+                + 0.1 * ln(1 + members) as score
+            from (
+                select c.*,
+                    array(
+                        select distinct lower(t)
+                        from unnest(string_to_array(c.tags, ' ')) t
+                        where lower(t) in (select tag from user_tags)
+                        order by 1
+                    ) as shared_tags,
+                    (
+                        select count(*) from membership m
+                        where m.class_id = c.id
+                        and m.user_id <> c.owner_id
+                        and m.joined_at > $2
+                    ) as new_members,
+                    (
+                        select count(*) from message msg
+                        join channel ch on ch.id = msg.channel_id
+                        where ch.class_id = c.id
+                        and ch.deleted_at is null
+                        and msg.created_at > $3
+                    ) as recent_messages,
+                    (select count(*) from membership m where m.class_id = c.id) as members
+                {}
+            ) candidate
+            order by score desc, id
+            offset $4
+            limit $5;
+            "#,
+            RECOMMENDATION_CANDIDATES
+        );
+
+        let recommendations =
+            ClassRecommendation::find_by_statement(Statement::from_sql_and_values(
                 DbBackend::Postgres,
-                r#"
-                select *,
-                    ts_rank(search, websearch_to_tsquery('english', $1)) + 
-                    ts_rank(search, websearch_to_tsquery('simple', $1)) as rank
-                from "class"
-                where search @@ websearch_to_tsquery('english', $1)
-                or search @@ websearch_to_tsquery('simple', $1)
-                and deleted_at is null
-                and public = true
-                and id != ALL($2::uuid[])
-                order by rank desc;
-                "#,
-                [query.into(), exceptions.into()],
+                &sql,
+                [
+                    user_id.into(),
+                    growth_since.into(),
+                    activity_since.into(),
+                    (offset as i64).into(),
+                    (limit as i64).into(),
+                ],
             ))
             .all(self.loader())
             .await?;
-
-        Ok(classes)
+        Ok(recommendations)
     }
 
     #[instrument(skip(self), err(Debug))]
@@ -353,19 +600,28 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         &self,
         class_id: Uuid,
         model: class::ActiveModel,
-    ) -> Result<class::Model, DbErr> {
-        let class = Class::find_by_id(class_id).one(self.loader()).await?;
-        if let Some(class) = class {
-            let mut active = class.into_active_model();
-            active.name = model.name;
-            active.description = model.description;
-            active.public = model.public;
-            active.tags = model.tags;
-            let updated = active.update(self.loader()).await?;
-            Ok(updated)
-        } else {
-            Err(DbErr::RecordNotFound("class not found".into()))
-        }
+    ) -> Result<class::Model, TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, class::Model, DbErr>(|txn| {
+                Box::pin(async move {
+                    let Some(class) = Class::find_by_id(class_id).one(txn).await? else {
+                        return Err(DbErr::RecordNotFound("class not found".into()));
+                    };
+                    let tags_changed = model.tags.is_set();
+                    let mut active = class.into_active_model();
+                    active.name = model.name;
+                    active.description = model.description;
+                    active.public = model.public;
+                    active.tags = model.tags;
+                    let updated = active.update(txn).await?;
+                    if tags_changed {
+                        set_class_tags(txn, class_id, &tags::split_class_tags(&updated.tags))
+                            .await?;
+                    }
+                    Ok(updated)
+                })
+            })
+            .await
     }
 
     #[instrument(skip(self), err(Debug))]
@@ -462,40 +718,133 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         Ok(invite)
     }
 
-    async fn delete_invite(&self, invite_id: Uuid) -> Result<(), DbErr> {
-        Invite::delete_by_id(invite_id).exec(self.loader()).await?;
-        Ok(())
+    /// Creates all of the invites or none of them.
+    #[instrument(skip(self), err(Debug))]
+    async fn create_invites(
+        &self,
+        models: Vec<invite::ActiveModel>,
+    ) -> Result<Vec<invite::Model>, TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, Vec<invite::Model>, DbErr>(|txn| {
+                Box::pin(async move {
+                    let mut invites = Vec::with_capacity(models.len());
+                    for model in models {
+                        invites.push(model.insert(txn).await?);
+                    }
+                    Ok(invites)
+                })
+            })
+            .await
+    }
+
+    /// Revoked invites are kept, so owners can still see who joined through them.
+    #[instrument(skip(self), err(Debug))]
+    async fn revoke_invite(
+        &self,
+        class_id: Uuid,
+        invite_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, DbErr> {
+        let res = Invite::update_many()
+            .col_expr(
+                invite::Column::RevokedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(invite::Column::RevokedBy, sea_query::Expr::value(user_id))
+            .filter(invite::Column::Id.eq(invite_id))
+            .filter(invite::Column::ClassId.eq(class_id))
+            .filter(invite::Column::RevokedAt.is_null())
+            .exec(self.loader())
+            .await?;
+        Ok(res.rows_affected > 0)
     }
 
     async fn get_invites(&self, class_id: Uuid) -> Result<Vec<invite::Model>, DbErr> {
         let invites = Invite::find()
             .filter(invite::Column::ClassId.eq(class_id))
+            .order_by_desc(invite::Column::CreatedAt)
             .all(self.loader())
             .await?;
         Ok(invites)
     }
 
-    async fn is_valid_invite(&self, invite_id: Uuid, class_id: Uuid) -> Result<bool, DbErr> {
-        let invite = Invite::find_by_id(invite_id).one(self.loader()).await?;
+    #[instrument(skip(self), err(Debug))]
+    async fn find_invite(&self, invite_id: Uuid) -> Result<Option<invite::Model>, DbErr> {
+        Invite::find_by_id(invite_id).one(self.loader()).await
+    }
 
-        let Some(invite) = invite else {
-            return Ok(false);
-        };
+    #[instrument(skip(self), err(Debug))]
+    async fn find_invite_by_code(&self, code: String) -> Result<Option<invite::Model>, DbErr> {
+        Invite::find()
+            .filter(invite::Column::Code.eq(code))
+            .one(self.loader())
+            .await
+    }
 
-        let is_expired = invite
-            .expires_at
-            .map(|e| e < Utc::now().naive_utc())
-            .unwrap_or(false);
-        if is_expired {
-            Invite::delete_by_id(invite_id).exec(self.loader()).await?;
-            return Ok(false);
-        }
+    /// Joins the user to the class of the invite and logs the redemption. Returns `false`
+    /// without joining when someone else used the invite since it was read.
+    #[instrument(skip(self), err(Debug))]
+    async fn redeem_invite(
+        &self,
+        invite: invite::Model,
+        user_id: Uuid,
+    ) -> Result<bool, TransactionError<DbErr>> {
+        let redeemed = self
+            .loader()
+            .transaction::<_, bool, DbErr>(|txn| {
+                Box::pin(async move {
+                    let res = Invite::update_many()
+                        .col_expr(
+                            invite::Column::Uses,
+                            sea_query::Expr::value(invite.uses + 1),
+                        )
+                        .filter(invite::Column::Id.eq(invite.id))
+                        .filter(invite::Column::Uses.eq(invite.uses))
+                        .exec(txn)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Ok(false);
+                    }
 
-        if invite.class_id != class_id {
-            return Ok(false);
-        }
+                    let member = membership::ActiveModel {
+                        user_id: Set(user_id),
+                        class_id: Set(invite.class_id),
+                        role: Set(MemberRole::Student),
+                        joined_at: Set(Some(Utc::now().naive_utc())),
+                        ..Default::default()
+                    };
+                    member.insert(txn).await?;
+                    let redemption = invite_redemption::ActiveModel {
+                        id: Set(Uuid::new_v4()),
+                        invite_id: Set(invite.id),
+                        user_id: Set(user_id),
+                        redeemed_at: Set(Utc::now().naive_utc()),
+                    };
+                    redemption.insert(txn).await?;
 
-        Ok(true)
+                    Ok(true)
+                })
+            })
+            .await?;
+
+        Ok(redeemed)
+    }
+
+    async fn get_invite_redemptions(
+        &self,
+        invite_id: Uuid,
+    ) -> Result<Vec<(invite_redemption::Model, user::Model)>, DbErr> {
+        let redemptions = InviteRedemption::find()
+            .filter(invite_redemption::Column::InviteId.eq(invite_id))
+            .order_by_asc(invite_redemption::Column::RedeemedAt)
+            .find_also_related(User)
+            .all(self.loader())
+            .await?;
+
+        Ok(redemptions
+            .into_iter()
+            .map(|(r, u)| (r, u.expect("relation is not optional")))
+            .collect())
     }
 
     async fn get_members(&self, class_id: Uuid) -> Result<Vec<user::Model>, DbErr> {
@@ -511,6 +860,50 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
             .collect())
     }
 
+    async fn get_memberships(
+        &self,
+        class_id: Uuid,
+    ) -> Result<Vec<(membership::Model, user::Model)>, DbErr> {
+        let members = Membership::find()
+            .filter(membership::Column::ClassId.eq(class_id))
+            .find_also_related(User)
+            .all(self.loader())
+            .await?;
+
+        Ok(members
+            .into_iter()
+            .map(|(m, u)| (m, u.expect("relation is not optional")))
+            .collect())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_membership(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<membership::Model>, DbErr> {
+        let membership = Membership::find_by_id((user_id, class_id))
+            .one(self.loader())
+            .await?;
+        Ok(membership)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn set_member_role(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+        role: MemberRole,
+    ) -> Result<membership::Model, DbErr> {
+        let model = membership::ActiveModel {
+            user_id: Unchanged(user_id),
+            class_id: Unchanged(class_id),
+            role: Set(role),
+            ..Default::default()
+        };
+        model.update(self.loader()).await
+    }
+
     async fn find_by_invite_id(&self, invite_id: Uuid) -> Result<Option<class::Model>, DbErr> {
         let invite = Invite::find_by_id(invite_id).one(self.loader()).await?;
 
@@ -544,8 +937,30 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         Ok(classes)
     }
 
-    async fn find_all(&self) -> Result<Vec<class::Model>, DbErr> {
-        let classes = Class::find().all(self.loader()).await?;
+    #[instrument(skip(self), err(Debug))]
+    async fn count_classes(&self, filter: Condition) -> Result<u64, DbErr> {
+        let count = Class::find().filter(filter).count(self.loader()).await?;
+        Ok(count)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_classes(
+        &self,
+        filter: Condition,
+        order_by: class::Column,
+        order: Order,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<class::Model>, DbErr> {
+        // Ties are broken by id so that offsets stay stable between pages
+        let classes = Class::find()
+            .filter(filter)
+            .order_by(order_by, order)
+            .order_by_asc(class::Column::Id)
+            .offset(offset)
+            .limit(limit)
+            .all(self.loader())
+            .await?;
         Ok(classes)
     }
 
@@ -567,4 +982,721 @@ impl ClassRepo for DataLoader<DatabaseConnection> {
         }
         Ok(())
     }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn set_archived(&self, class_id: Uuid, archived: bool) -> Result<class::Model, DbErr> {
+        let class = Class::find_by_id(class_id).one(self.loader()).await?;
+        if let Some(class) = class {
+            let mut active = class.into_active_model();
+            active.archived_at = Set(archived.then(|| Utc::now().naive_utc()));
+            let updated = active.update(self.loader()).await?;
+            Ok(updated)
+        } else {
+            Err(DbErr::RecordNotFound("class not found".into()))
+        }
+    }
+
+    /// Copies the channels of one class into another, skipping names the target already has.
+    #[instrument(skip(self), err(Debug))]
+    async fn copy_channels(&self, source_id: Uuid, target_id: Uuid) -> Result<usize, DbErr> {
+        let existing = channel::Entity::find()
+            .filter(channel::Column::ClassId.eq(target_id))
+            .filter(channel::Column::DeletedAt.is_null())
+            .all(self.loader())
+            .await?
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<HashSet<_>>();
+
+        let channels = channel::Entity::find()
+            .filter(channel::Column::ClassId.eq(source_id))
+            .filter(channel::Column::DeletedAt.is_null())
+            .all(self.loader())
+            .await?
+            .into_iter()
+            .filter(|c| !existing.contains(&c.name))
+            .map(|c| channel::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                name: Set(c.name),
+                description: Set(c.description),
+                allow_members_to_post: Set(c.allow_members_to_post),
+                class_id: Set(target_id),
+                deleted_at: Set(None),
+            })
+            .collect::<Vec<_>>();
+
+        let copied = channels.len();
+        if !channels.is_empty() {
+            channel::Entity::insert_many(channels)
+                .exec(self.loader())
+                .await?;
+        }
+
+        Ok(copied)
+    }
+
+    /// Copies the file tree of one class into another and returns every copied file together
+    /// with the id of its copy. Root directories are merged with the ones the target already
+    /// has, chat attachments and submitted files stay behind.
+    #[instrument(skip(self), err(Debug))]
+    async fn copy_files(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<Vec<(file::Model, Uuid)>, TransactionError<DbErr>> {
+        let copied = self
+            .loader()
+            .transaction::<_, Vec<(file::Model, Uuid)>, DbErr>(|txn| {
+                Box::pin(async move {
+                    let target_roots = file::Entity::find()
+                        .filter(file::Column::ClassId.eq(target_id))
+                        .filter(file::Column::ParentId.is_null())
+                        .all(txn)
+                        .await?;
+                    let files = file::Entity::find()
+                        .filter(file::Column::ClassId.eq(source_id))
+                        .filter(file::Column::MessageId.is_null())
+                        .all(txn)
+                        .await?;
+
+                    let mut children = HashMap::<Option<Uuid>, Vec<file::Model>>::new();
+                    for file in files {
+                        children.entry(file.parent_id).or_default().push(file);
+                    }
+
+                    let mut ids = HashMap::new();
+                    let mut pending = Vec::new();
+                    for root in children.remove(&None).unwrap_or_default() {
+                        if root.name == SUBMISSION_FILES_DIR {
+                            continue;
+                        }
+                        match target_roots.iter().find(|r| r.name == root.name) {
+                            Some(existing) => {
+                                ids.insert(root.id, existing.id);
+                            }
+                            None => pending.push(root),
+                        }
+                    }
+                    let mut stack = ids.keys().copied().collect::<Vec<_>>();
+                    let mut copied = Vec::new();
+                    loop {
+                        // parents are always copied before their children
+                        for file in pending.drain(..) {
+                            let id = Uuid::new_v4();
+                            ids.insert(file.id, id);
+                            stack.push(file.id);
+                            copied.push((file, id));
+                        }
+                        match stack.pop() {
+                            Some(parent_id) => {
+                                pending = children.remove(&Some(parent_id)).unwrap_or_default();
+                            }
+                            None => break,
+                        }
+                    }
+
+                    let models = copied
+                        .iter()
+                        .map(|(file, id)| file::ActiveModel {
+                            id: Set(*id),
+                            name: Set(file.name.clone()),
+                            public: Set(file.public),
+                            file_type: Set(file.file_type.clone()),
+                            parent_id: Set(file.parent_id.map(|parent_id| ids[&parent_id])),
+                            class_id: Set(target_id),
+                            message_id: Set(None),
+                        })
+                        .collect::<Vec<_>>();
+                    for batch in models.chunks(COPY_BATCH_SIZE) {
+                        file::Entity::insert_many(batch.to_vec()).exec(txn).await?;
+                    }
+
+                    Ok(copied)
+                })
+            })
+            .await?;
+
+        Ok(copied)
+    }
+
+    /// Copies the assignments of one class into another, linking them to the copies of their
+    /// files and moving due dates by `due_offset`.
+    #[instrument(skip(self, file_ids), err(Debug))]
+    async fn copy_assignments(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+        file_ids: HashMap<Uuid, Uuid>,
+        due_offset: Option<chrono::Duration>,
+    ) -> Result<usize, TransactionError<DbErr>> {
+        let copied = self
+            .loader()
+            .transaction::<_, usize, DbErr>(|txn| {
+                Box::pin(async move {
+                    let assignments = assignment::Entity::find()
+                        .filter(assignment::Column::ClassId.eq(source_id))
+                        .all(txn)
+                        .await?;
+                    if assignments.is_empty() {
+                        return Ok(0);
+                    }
+
+                    let links = assignment_file::Entity::find()
+                        .filter(
+                            assignment_file::Column::AssignmentId
+                                .is_in(assignments.iter().map(|a| a.id)),
+                        )
+                        .all(txn)
+                        .await?;
+
+                    let now = Utc::now().naive_utc();
+                    let mut ids = HashMap::new();
+                    let models = assignments
+                        .into_iter()
+                        .map(|a| {
+                            let due_at = match (a.due_at, due_offset) {
+                                (Some(due_at), Some(offset)) => {
+                                    Some(due_at.checked_add_signed(offset).ok_or_else(|| {
+                                        DbErr::Custom(format!(
+                                            "Due date of assignment {} is out of range",
+                                            a.id
+                                        ))
+                                    })?)
+                                }
+                                (due_at, _) => due_at,
+                            };
+                            let id = Uuid::new_v4();
+                            ids.insert(a.id, id);
+                            Ok(assignment::ActiveModel {
+                                id: Set(id),
+                                name: Set(a.name),
+                                content: Set(a.content),
+                                created_at: Set(now),
+                                due_at: Set(due_at),
+                                class_id: Set(target_id),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, DbErr>>()?;
+                    let copied = models.len();
+                    for batch in models.chunks(COPY_BATCH_SIZE) {
+                        assignment::Entity::insert_many(batch.to_vec())
+                            .exec(txn)
+                            .await?;
+                    }
+
+                    let links = links
+                        .into_iter()
+                        .filter_map(|link| {
+                            Some(assignment_file::ActiveModel {
+                                assignment_id: Set(ids[&link.assignment_id]),
+                                file_id: Set(*file_ids.get(&link.file_id)?),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    for batch in links.chunks(COPY_BATCH_SIZE) {
+                        assignment_file::Entity::insert_many(batch.to_vec())
+                            .exec(txn)
+                            .await?;
+                    }
+
+                    Ok(copied)
+                })
+            })
+            .await?;
+
+        Ok(copied)
+    }
+
+    /// Replaces the pending transfer of the class, a class has at most one.
+    #[instrument(skip(self), err(Debug))]
+    async fn propose_ownership_transfer(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<ownership_transfer::Model, TransactionError<DbErr>> {
+        let transfer = self
+            .loader()
+            .transaction::<_, ownership_transfer::Model, DbErr>(|txn| {
+                Box::pin(async move {
+                    OwnershipTransfer::delete_by_id(class_id).exec(txn).await?;
+                    let transfer = ownership_transfer::ActiveModel {
+                        class_id: Set(class_id),
+                        user_id: Set(user_id),
+                        created_at: Set(Utc::now().naive_utc()),
+                    };
+                    transfer.insert(txn).await
+                })
+            })
+            .await?;
+
+        Ok(transfer)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_ownership_transfer(
+        &self,
+        class_id: Uuid,
+    ) -> Result<Option<ownership_transfer::Model>, DbErr> {
+        OwnershipTransfer::find_by_id(class_id)
+            .one(self.loader())
+            .await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_ownership_transfer(&self, class_id: Uuid) -> Result<bool, DbErr> {
+        let res = OwnershipTransfer::delete_by_id(class_id)
+            .exec(self.loader())
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Makes `user_id` the owner of the class and drops its pending transfer. Both the previous
+    /// and the new owner are stored as co-teachers, like the owner of a new class.
+    #[instrument(skip(self), err(Debug))]
+    async fn transfer_ownership(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<class::Model, TransactionError<DbErr>> {
+        let class = self
+            .loader()
+            .transaction::<_, class::Model, DbErr>(|txn| {
+                Box::pin(async move {
+                    let class = Class::find_by_id(class_id)
+                        .one(txn)
+                        .await?
+                        .ok_or_else(|| DbErr::RecordNotFound("class not found".into()))?;
+                    let previous_owner_id = class.owner_id;
+
+                    for member_id in [previous_owner_id, user_id] {
+                        let membership = membership::ActiveModel {
+                            user_id: Set(member_id),
+                            class_id: Set(class_id),
+                            role: Set(MemberRole::CoTeacher),
+                            joined_at: Set(Some(Utc::now().naive_utc())),
+                            ..Default::default()
+                        };
+                        Membership::insert(membership)
+                            .on_conflict(
+                                sea_query::OnConflict::columns([
+                                    membership::Column::UserId,
+                                    membership::Column::ClassId,
+                                ])
+                                .update_column(membership::Column::Role)
+                                .to_owned(),
+                            )
+                            .exec(txn)
+                            .await?;
+                    }
+
+                    let mut active = class.into_active_model();
+                    active.owner_id = Set(user_id);
+                    let class = active.update(txn).await?;
+                    OwnershipTransfer::delete_by_id(class_id).exec(txn).await?;
+
+                    Ok(class)
+                })
+            })
+            .await?;
+
+        Ok(class)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn create_join_request(
+        &self,
+        model: join_request::ActiveModel,
+    ) -> Result<join_request::Model, DbErr> {
+        model.insert(self.loader()).await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_join_request(&self, id: Uuid) -> Result<Option<join_request::Model>, DbErr> {
+        JoinRequest::find_by_id(id).one(self.loader()).await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_latest_join_request(
+        &self,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<join_request::Model>, DbErr> {
+        JoinRequest::find()
+            .filter(join_request::Column::ClassId.eq(class_id))
+            .filter(join_request::Column::UserId.eq(user_id))
+            .order_by_desc(join_request::Column::CreatedAt)
+            .one(self.loader())
+            .await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn get_join_requests(
+        &self,
+        class_id: Uuid,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<(join_request::Model, user::Model)>, DbErr> {
+        let mut condition = Condition::all().add(join_request::Column::ClassId.eq(class_id));
+        if let Some(status) = status {
+            condition = condition.add(join_request::Column::Status.eq(status));
+        }
+
+        let requests = JoinRequest::find()
+            .filter(condition)
+            .order_by_asc(join_request::Column::CreatedAt)
+            .select_also(User)
+            .join(JoinType::InnerJoin, join_request::Relation::User1.def())
+            .all(self.loader())
+            .await?;
+
+        Ok(requests
+            .into_iter()
+            .map(|(r, u)| (r, u.expect("relation is not optional")))
+            .collect())
+    }
+
+    /// Settles a pending request. Returns `None` when it was already decided or withdrawn.
+    #[instrument(skip(self), err(Debug))]
+    async fn decide_join_request(
+        &self,
+        id: Uuid,
+        status: JoinRequestStatus,
+        decided_by: Uuid,
+    ) -> Result<Option<join_request::Model>, DbErr> {
+        let decision = join_request::ActiveModel {
+            status: Set(status),
+            decided_by: Set(Some(decided_by)),
+            decided_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        };
+        let res = JoinRequest::update_many()
+            .set(decision)
+            .filter(join_request::Column::Id.eq(id))
+            .filter(join_request::Column::Status.eq(JoinRequestStatus::Pending))
+            .exec(self.loader())
+            .await?;
+        if res.rows_affected == 0 {
+            return Ok(None);
+        }
+
+        JoinRequest::find_by_id(id).one(self.loader()).await
+    }
+
+    /// Claims the request while it is still pending and only then lets its user in as a
+    /// student, both or neither. Returns `None` if the request was already decided, and
+    /// whether the user joined, as they may have joined through an invite in the meantime.
+    #[instrument(skip(self), err(Debug))]
+    async fn approve_join_request(
+        &self,
+        id: Uuid,
+        decided_by: Uuid,
+    ) -> Result<Option<(join_request::Model, bool)>, TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, Option<(join_request::Model, bool)>, DbErr>(|txn| {
+                Box::pin(async move {
+                    let decision = join_request::ActiveModel {
+                        status: Set(JoinRequestStatus::Approved),
+                        decided_by: Set(Some(decided_by)),
+                        decided_at: Set(Some(Utc::now().naive_utc())),
+                        ..Default::default()
+                    };
+                    let res = JoinRequest::update_many()
+                        .set(decision)
+                        .filter(join_request::Column::Id.eq(id))
+                        .filter(join_request::Column::Status.eq(JoinRequestStatus::Pending))
+                        .exec(txn)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Ok(None);
+                    }
+                    let request = JoinRequest::find_by_id(id)
+                        .one(txn)
+                        .await?
+                        .ok_or_else(|| DbErr::RecordNotFound("join request not found".into()))?;
+
+                    let member = membership::ActiveModel {
+                        user_id: Set(request.user_id),
+                        class_id: Set(request.class_id),
+                        role: Set(MemberRole::Student),
+                        joined_at: Set(Some(Utc::now().naive_utc())),
+                        ..Default::default()
+                    };
+                    let joined = Membership::insert(member)
+                        .on_conflict(
+                            sea_query::OnConflict::columns([
+                                membership::Column::UserId,
+                                membership::Column::ClassId,
+                            ])
+                            .do_nothing()
+                            .to_owned(),
+                        )
+                        .exec_without_returning(txn)
+                        .await?;
+
+                    Ok(Some((request, joined > 0)))
+                })
+            })
+            .await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_join_request(&self, id: Uuid) -> Result<(), DbErr> {
+        JoinRequest::delete_by_id(id).exec(self.loader()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::auth::{authorize_class_role, class_role, ensure_not_archived, ClassRole};
+    use crate::test_utils::{connect, insert_class, insert_member, insert_user};
+    use ::entity::sea_orm_active_enums::FileType;
+    use chrono::{Duration, NaiveDate};
+
+    fn pending_request(class_id: Uuid, user_id: Uuid) -> join_request::ActiveModel {
+        join_request::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            class_id: Set(class_id),
+            user_id: Set(user_id),
+            status: Set(JoinRequestStatus::Pending),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+    }
+
+    async fn insert_file(conn: &DatabaseConnection, class_id: Uuid) -> file::Model {
+        file::Model {
+            id: Uuid::new_v4(),
+            name: "sheet.pdf".into(),
+            public: true,
+            file_type: FileType::File,
+            parent_id: None,
+            class_id,
+            message_id: None,
+        }
+        .into_active_model()
+        .insert(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn assignments_are_copied_with_moved_due_dates() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        let owner_id = insert_user(&conn).await.id;
+        let source = insert_class(&conn, owner_id).await;
+        let target = insert_class(&conn, owner_id).await;
+
+        let due_at = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let original = assignment::Model {
+            id: Uuid::new_v4(),
+            name: "Homework".into(),
+            content: "Solve the sheet".into(),
+            created_at: Utc::now().naive_utc(),
+            due_at: Some(due_at),
+            class_id: source.id,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let source_file = insert_file(&conn, source.id).await;
+        assignment_file::Model {
+            assignment_id: original.id,
+            file_id: source_file.id,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let target_file = insert_file(&conn, target.id).await;
+
+        let copied = ClassRepo::copy_assignments(
+            &data_loader,
+            source.id,
+            target.id,
+            HashMap::from([(source_file.id, target_file.id)]),
+            Some(Duration::days(7)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(copied, 1);
+
+        let copies = assignment::Entity::find()
+            .filter(assignment::Column::ClassId.eq(target.id))
+            .all(&conn)
+            .await
+            .unwrap();
+        assert_eq!(copies.len(), 1);
+        assert_ne!(copies[0].id, original.id);
+        assert_eq!(copies[0].name, original.name);
+        assert_eq!(copies[0].due_at, Some(due_at + Duration::days(7)));
+        let links = assignment_file::Entity::find()
+            .filter(assignment_file::Column::AssignmentId.eq(copies[0].id))
+            .all(&conn)
+            .await
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].file_id, target_file.id);
+
+        // a due date that can't be moved fails the whole copy
+        let other_target = insert_class(&conn, owner_id).await;
+        let res = ClassRepo::copy_assignments(
+            &data_loader,
+            source.id,
+            other_target.id,
+            HashMap::new(),
+            Some(Duration::days(i32::MAX.into())),
+        )
+        .await;
+        assert!(res.is_err());
+        let copies = assignment::Entity::find()
+            .filter(assignment::Column::ClassId.eq(other_target.id))
+            .count(&conn)
+            .await
+            .unwrap();
+        assert_eq!(copies, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn join_requests_are_approved_once() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        let owner = insert_user(&conn).await;
+        let student = insert_user(&conn).await;
+        let class = insert_class(&conn, owner.id).await;
+        let request =
+            ClassRepo::create_join_request(&data_loader, pending_request(class.id, student.id))
+                .await
+                .unwrap();
+
+        let (approved, joined) =
+            ClassRepo::approve_join_request(&data_loader, request.id, owner.id)
+                .await
+                .unwrap()
+                .unwrap();
+        assert!(joined);
+        assert_eq!(approved.status, JoinRequestStatus::Approved);
+        assert_eq!(approved.decided_by, Some(owner.id));
+        let member = Membership::find_by_id((student.id, class.id))
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(member.role, MemberRole::Student);
+
+        // neither a second approval nor a denial can settle it again
+        assert!(
+            ClassRepo::approve_join_request(&data_loader, request.id, owner.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(ClassRepo::decide_join_request(
+            &data_loader,
+            request.id,
+            JoinRequestStatus::Denied,
+            owner.id,
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+        // someone who joined in the meantime keeps their membership
+        let other = insert_user(&conn).await;
+        let request =
+            ClassRepo::create_join_request(&data_loader, pending_request(class.id, other.id))
+                .await
+                .unwrap();
+        membership::ActiveModel {
+            user_id: Set(other.id),
+            class_id: Set(class.id),
+            role: Set(MemberRole::CoTeacher),
+            joined_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .insert(&conn)
+        .await
+        .unwrap();
+        let (_, joined) = ClassRepo::approve_join_request(&data_loader, request.id, owner.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!joined);
+        let member = Membership::find_by_id((other.id, class.id))
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(member.role, MemberRole::CoTeacher);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn archived_classes_keep_their_members() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        let owner = insert_user(&conn).await;
+        let student = insert_user(&conn).await;
+        let class = insert_class(&conn, owner.id).await;
+        insert_member(&conn, class.id, student.id, MemberRole::Student).await;
+
+        assert!(ensure_not_archived(&data_loader, class.id).await.is_ok());
+        ClassRepo::set_archived(&data_loader, class.id, true)
+            .await
+            .unwrap();
+        let res = ensure_not_archived(&data_loader, class.id).await;
+        assert!(matches!(res, Err(err) if err.to_string().starts_with("User error")));
+        assert_eq!(
+            class_role(&data_loader, student.id, class.id)
+                .await
+                .unwrap(),
+            Some(ClassRole::Student)
+        );
+
+        ClassRepo::set_archived(&data_loader, class.id, false)
+            .await
+            .unwrap();
+        assert!(ensure_not_archived(&data_loader, class.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn transferred_classes_keep_the_old_owner_as_co_teacher() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        let owner = insert_user(&conn).await;
+        let assistant = insert_user(&conn).await;
+        let class = insert_class(&conn, owner.id).await;
+        insert_member(&conn, class.id, owner.id, MemberRole::CoTeacher).await;
+        insert_member(&conn, class.id, assistant.id, MemberRole::TeachingAssistant).await;
+
+        ClassRepo::transfer_ownership(&data_loader, class.id, assistant.id)
+            .await
+            .unwrap();
+        assert!(
+            authorize_class_role(&data_loader, assistant.id, class.id, ClassRole::Owner)
+                .await
+                .is_ok()
+        );
+        assert!(
+            authorize_class_role(&data_loader, owner.id, class.id, ClassRole::Owner)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            class_role(&data_loader, owner.id, class.id).await.unwrap(),
+            Some(ClassRole::CoTeacher)
+        );
+    }
 }
@@ -1,14 +1,18 @@
 use crate::api::assignment::AssignmentObject;
+use crate::api::calendar::ClassEventObject;
 use crate::api::channel::ChannelObject;
 use crate::api::class::ClassObject;
+use crate::api::class::MemberObject;
 use crate::api::file::FileObject;
 
 use crate::api::user::UserObject;
+use crate::core::auth::Claims;
 use crate::core::AppError;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
-use async_graphql::futures_util::StreamExt;
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard};
+use async_graphql::futures_util::{self, StreamExt};
 use async_graphql::{futures_util::Stream, Context, Subscription};
-use async_graphql::{SimpleObject, Union, ID};
+use async_graphql::{Enum, SimpleObject, Union, ID};
+use deadpool_redis::redis::AsyncCommands;
 use deadpool_redis::Pool;
 use paste::paste;
 use serde::de::DeserializeOwned;
@@ -19,6 +23,9 @@ pub const CLASS_RESOURCE_CREATED: &str = "class_resource_created";
 pub const CLASS_RESOURCE_UPDATED: &str = "class_resource_updated";
 pub const CLASS_RESOURCE_DELETED: &str = "class_resource_deleted";
 pub const CLASS_DELETED: &str = "class_deleted";
+pub const CLASS_CLONE_PROGRESS: &str = "class_clone_progress";
+/// Holds the id of the user who started a clone, keyed by the new class.
+pub const CLASS_CLONE_REQUESTER: &str = "class_clone_requester";
 
 #[derive(Default)]
 pub struct ClassSubscription;
@@ -26,7 +33,9 @@ pub struct ClassSubscription;
 #[Subscription]
 impl ClassSubscription {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn class_resource_created(
         &self,
         ctx: &Context<'_>,
@@ -40,7 +49,9 @@ impl ClassSubscription {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn class_resource_updated(
         &self,
         ctx: &Context<'_>,
@@ -54,7 +65,9 @@ impl ClassSubscription {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn class_resource_deleted(
         &self,
         ctx: &Context<'_>,
@@ -68,7 +81,9 @@ impl ClassSubscription {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn class_deleted(
         &self,
         ctx: &Context<'_>,
@@ -76,6 +91,36 @@ impl ClassSubscription {
     ) -> Result<impl Stream<Item = ClassDelete>, AppError> {
         make_subscription(ctx, format!("{}:{}", CLASS_DELETED, class_id.as_str())).await
     }
+
+    /// Progress of `cloneClass` for the new class, starting with the latest update so
+    /// nothing is missed by subscribing after the mutation returned. Only the user who
+    /// started the clone can follow it, a failed clone has no members left to check.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn class_clone_progress(
+        &self,
+        ctx: &Context<'_>,
+        class_id: ID,
+    ) -> Result<impl Stream<Item = ClassCloneProgress>, AppError> {
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+
+        let mut conn = redis_pool.get().await?;
+        let requester: Option<String> = conn
+            .get(format!("{}:{}", CLASS_CLONE_REQUESTER, class_id.as_str()))
+            .await?;
+        if requester.as_deref() != Some(claims.sub.as_str()) {
+            return Err(AppError::auth("You did not clone this class"));
+        }
+
+        let channel = format!("{}:{}", CLASS_CLONE_PROGRESS, class_id.as_str());
+        let updates = make_subscription(ctx, channel.clone()).await?;
+        let latest: Option<String> = conn.get(&channel).await?;
+        let latest = latest.and_then(|s| serde_json::from_str(s.as_str()).ok());
+
+        Ok(futures_util::stream::iter(latest).chain(updates))
+    }
 }
 
 async fn make_subscription<T: DeserializeOwned>(
@@ -94,6 +139,25 @@ async fn make_subscription<T: DeserializeOwned>(
     }))
 }
 
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CloneStage {
+    Channels,
+    Files,
+    Assignments,
+    Done,
+    /// Nothing more is copied and the new class is deleted again.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ClassCloneProgress {
+    pub class_id: ID,
+    pub stage: CloneStage,
+    /// Items of the current stage copied so far.
+    pub completed: u64,
+    pub total: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, SimpleObject)]
 pub struct ClassDelete {
     pub id: ID,
@@ -106,6 +170,7 @@ pub enum ClassResourceCreate {
     File(FileObject),
     FileBatch(FileBatch),
     Assignment(AssignmentObject),
+    ClassEvent(ClassEventObject),
 }
 
 #[derive(Debug, Serialize, Deserialize, SimpleObject)]
@@ -118,6 +183,9 @@ pub enum ClassResourceUpdate {
     Class(ClassObject),
     Channel(ChannelObject),
     Assignment(AssignmentObject),
+    /// A member's role changed.
+    Member(MemberObject),
+    ClassEvent(ClassEventObject),
 }
 
 #[derive(Debug, Serialize, Deserialize, Union)]
@@ -126,6 +194,8 @@ pub enum ClassResourceDelete {
     Member(MemberDeleteInfo),
     File(FileDeleteInfo),
     Assignment(AssignmentDeleteInfo),
+    ClassEvent(ClassEventDeleteInfo),
+    Message(MessageDeleteInfo),
 }
 
 macro_rules! make_a_struct {
@@ -149,3 +219,5 @@ make_a_struct!(Channel, entity::channel::Model);
 make_a_struct!(File, entity::file::Model);
 make_a_struct!(Assignment, entity::assignment::Model);
 make_a_struct!(Member, entity::user::Model);
+make_a_struct!(ClassEvent, entity::class_event::Model);
+make_a_struct!(Message, entity::message::Model);
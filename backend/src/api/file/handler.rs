@@ -16,7 +16,7 @@ use zip::CompressionMethod;
 
 use crate::{
     api::file::FileRepo,
-    core::{is_class_member, AppError, Claims, ClassMemberGuard},
+    core::{is_class_member, AppError, Claims},
 };
 
 pub struct FileHandler;
@@ -96,7 +96,8 @@ impl FileHandler {
         let data_loader = DataLoader::new(conn, tokio::spawn);
 
         let user_id = Uuid::parse_str(claims.sub.as_str())?;
-        let is_member = is_class_member(&data_loader, user_id, class_id).await;
+        let is_member =
+            claims.allows_class(class_id) && is_class_member(&data_loader, user_id, class_id).await;
         if !is_member {
             return Err(AppError::auth("User is not a member of this class").into());
         }
@@ -137,7 +138,8 @@ impl FileHandler {
         let data_loader = DataLoader::new(conn, tokio::spawn);
 
         let user_id = Uuid::parse_str(claims.sub.as_str())?;
-        let is_member = is_class_member(&data_loader, user_id, class_id).await;
+        let is_member =
+            claims.allows_class(class_id) && is_class_member(&data_loader, user_id, class_id).await;
         if !is_member {
             return Err(AppError::auth("User is not a member of this class").into());
         }
@@ -1,5 +1,5 @@
 use crate::api::class;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard, NotArchivedGuard};
 use crate::{
     api::{
         class::{
@@ -31,7 +31,9 @@ pub struct FileMutation;
 #[Object]
 impl FileMutation {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::TeachingAssistant)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn upload_files(
         &self,
         ctx: &Context<'_>,
@@ -111,7 +113,9 @@ impl FileMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::TeachingAssistant)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_direcotry(
         &self,
         ctx: &Context<'_>,
@@ -133,7 +137,9 @@ impl FileMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::TeachingAssistant)).and(NotArchivedGuard::new(class_id.clone()))"
+    )]
     pub async fn delete_files(
         &self,
         ctx: &Context<'_>,
@@ -173,7 +179,9 @@ impl FileMutation {
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassOwnerGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::TeachingAssistant)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn update_file(
         &self,
         ctx: &Context<'_>,
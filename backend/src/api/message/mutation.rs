@@ -1,6 +1,11 @@
-use crate::core::{auth, AppError};
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
-use async_graphql::{dataloader::DataLoader, Context, Object};
+use crate::api::channel::ChannelRepo;
+use crate::api::class::{ClassResourceDelete, MessageDeleteInfo, CLASS_RESOURCE_DELETED};
+use crate::core::{auth, authorize_class_role, AppError};
+use crate::core::{
+    ClassRole, ClassRoleGuard, LoggedInGuard, ModGuard, NotArchivedGuard, ResourceOwnerGuard,
+    TokenScopeGuard,
+};
+use async_graphql::{dataloader::DataLoader, Context, Object, ID};
 use auth::Claims;
 use deadpool_redis::{redis, Pool};
 use redis::AsyncCommands;
@@ -17,7 +22,9 @@ pub struct MessageMutation;
 #[Object]
 impl MessageMutation {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(input.class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(input.class_id.clone(), ClassRole::Student)).and(NotArchivedGuard::new(input.class_id.clone()))"
+    )]
     pub async fn create_message(
         &self,
         ctx: &Context<'_>,
@@ -29,7 +36,26 @@ impl MessageMutation {
         let mut conn = redis_pool.get().await?;
 
         let id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let class_id = Uuid::parse_str(input.class_id.as_str())?;
         let channel_id = input.channel_id.clone();
+        let channel = ChannelRepo::find_by_class_id(data_loader, class_id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|c| c.id.to_string() == channel_id.as_str())
+            .ok_or_else(|| {
+                AppError::not_found(
+                    "Channel not found".into(),
+                    "channel",
+                    "id",
+                    channel_id.to_string(),
+                )
+            })?;
+        // read-only channels are open to everyone who teaches the class
+        if !channel.allow_members_to_post {
+            authorize_class_role(data_loader, id, class_id, ClassRole::TeachingAssistant).await?;
+        }
+
         let model = input.try_into_active_model(id)?;
         let message: MessageObject = MessageRepo::create_message(data_loader, model)
             .await?
@@ -42,4 +68,32 @@ impl MessageMutation {
 
         Ok(message)
     }
+
+    /// Authors can delete their own messages, moderators any message.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(ResourceOwnerGuard::message(id.clone()).or(ModGuard)).and(NotArchivedGuard::message(id.clone())).and(TokenScopeGuard::account())"
+    )]
+    pub async fn delete_message(&self, ctx: &Context<'_>, id: ID) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mut conn = redis_pool.get().await?;
+
+        let id = Uuid::parse_str(&id)?;
+        let class_id = MessageRepo::find_class_id(data_loader, id)
+            .await?
+            .ok_or_else(|| {
+                AppError::not_found("Message not found".into(), "message", "id", id.to_string())
+            })?;
+        MessageRepo::delete_message(data_loader, id).await?;
+
+        let update_data = ClassResourceDelete::Message(MessageDeleteInfo { id: id.into() });
+        conn.publish(
+            format!("{}:{}", CLASS_RESOURCE_DELETED, class_id),
+            serde_json::to_string(&update_data).expect("Message should serialize"),
+        )
+        .await?;
+
+        Ok(true)
+    }
 }
@@ -1,4 +1,4 @@
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard};
 use async_graphql::{connection::Connection, dataloader::DataLoader, Context, Object, ID};
 use sea_orm::DatabaseConnection;
 use tracing::instrument;
@@ -14,7 +14,9 @@ pub struct MessageQuery;
 #[Object]
 impl MessageQuery {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn messages(
         &self,
         ctx: &Context<'_>,
@@ -1,4 +1,4 @@
-use ::entity::{message, message::Entity as Message};
+use ::entity::{channel::Entity as Channel, message, message::Entity as Message};
 use async_graphql::dataloader::DataLoader;
 use async_trait::async_trait;
 
@@ -20,6 +20,10 @@ pub trait MessageRepo {
         start: NaiveDateTime,
         end: NaiveDateTime,
     ) -> Result<Vec<message::Model>, DbErr>;
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<message::Model>, DbErr>;
+    async fn find_class_id(&self, id: Uuid) -> Result<Option<Uuid>, DbErr>;
+    async fn delete_message(&self, id: Uuid) -> Result<(), DbErr>;
 }
 
 #[async_trait]
@@ -56,4 +60,28 @@ impl MessageRepo for DataLoader<DatabaseConnection> {
 
         Ok(messages)
     }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<message::Model>, DbErr> {
+        let message = Message::find_by_id(id).one(self.loader()).await?;
+        Ok(message)
+    }
+
+    /// Class of the channel the message was posted in.
+    #[instrument(skip(self), err(Debug))]
+    async fn find_class_id(&self, id: Uuid) -> Result<Option<Uuid>, DbErr> {
+        let class_id = Message::find_by_id(id)
+            .find_also_related(Channel)
+            .one(self.loader())
+            .await?
+            .and_then(|(_, channel)| channel)
+            .map(|channel| channel.class_id);
+        Ok(class_id)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_message(&self, id: Uuid) -> Result<(), DbErr> {
+        Message::delete_by_id(id).exec(self.loader()).await?;
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use crate::api::class;
 use crate::api::message::MessageObject;
 use crate::core::AppError;
-use crate::core::{ClassMemberGuard, ClassOwnerGuard, LoggedInGuard};
+use crate::core::{ClassRole, ClassRoleGuard, LoggedInGuard};
 use async_graphql::futures_util::StreamExt;
 use async_graphql::ID;
 use async_graphql::{futures_util::Stream, Context, Subscription};
@@ -14,7 +14,9 @@ pub struct MessageSubscription;
 #[Subscription]
 impl MessageSubscription {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard.and(ClassMemberGuard::new(class_id.clone()))")]
+    #[graphql(
+        guard = "LoggedInGuard.and(ClassRoleGuard::new(class_id.clone(), ClassRole::Observer))"
+    )]
     async fn message_created(
         &self,
         ctx: &Context<'_>,
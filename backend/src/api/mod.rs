@@ -1,23 +1,35 @@
 mod assignment;
+mod calendar;
 mod channel;
 mod class;
 mod file;
 mod message;
+mod tag;
 mod user;
 
 use assignment::AssignmentMutation;
-use async_graphql::Schema;
+use async_graphql::extensions::Tracing;
+use async_graphql::{Schema, SchemaBuilder};
+use calendar::{CalendarMutation, CalendarQuery};
 use channel::ChannelMutation;
 use class::{ClassMutation, ClassQuery};
 use file::FileMutation;
 use message::MessageMutation;
+use tag::{TagMutation, TagQuery};
 use user::{UserMutation, UserQuery};
 
+pub use assignment::AssignmentRepo;
+pub use calendar::CalendarRest;
 pub use class::ClassRepo;
+pub use class::{ClassCloneProgress, CloneStage, CLASS_CLONE_PROGRESS, CLASS_CLONE_REQUESTER};
 pub use file::FileHandler;
+pub use message::MessageRepo;
 pub use user::UserRepo;
 pub use user::UserRest;
 
+use crate::core::impersonation::ImpersonationAudit;
+use crate::core::personal_access_token::ReadOnlyTokens;
+
 use self::assignment::AssignmentQuery;
 use self::{
     class::ClassSubscription,
@@ -27,7 +39,14 @@ use self::{
 pub const MAX_FILE_SIZE: u64 = 1 * 1024 * 1024;
 
 #[derive(async_graphql::MergedObject, Default)]
-pub struct Query(ClassQuery, UserQuery, MessageQuery, AssignmentQuery);
+pub struct Query(
+    ClassQuery,
+    UserQuery,
+    MessageQuery,
+    AssignmentQuery,
+    TagQuery,
+    CalendarQuery,
+);
 
 #[derive(async_graphql::MergedObject, Default)]
 pub struct Mutation(
@@ -37,9 +56,24 @@ pub struct Mutation(
     ChannelMutation,
     FileMutation,
     AssignmentMutation,
+    TagMutation,
+    CalendarMutation,
 );
 
 #[derive(async_graphql::MergedSubscription, Default)]
 pub struct Subscription(MessageSubscription, ClassSubscription);
 
 pub type AppSchema = Schema<Query, Mutation, Subscription>;
+
+/// The schema with the extensions every transport relies on, without the data shared by
+/// all requests.
+pub fn schema_builder() -> SchemaBuilder<Query, Mutation, Subscription> {
+    Schema::build(
+        Query::default(),
+        Mutation::default(),
+        Subscription::default(),
+    )
+    .extension(Tracing)
+    .extension(ReadOnlyTokens)
+    .extension(ImpersonationAudit)
+}
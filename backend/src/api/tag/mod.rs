@@ -0,0 +1,9 @@
+mod mutation;
+mod object;
+mod query;
+mod repo;
+
+pub use mutation::TagMutation;
+pub use object::TagObject;
+pub use query::TagQuery;
+pub use repo::{set_class_tags, TagRepo};
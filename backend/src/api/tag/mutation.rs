@@ -0,0 +1,106 @@
+use async_graphql::{dataloader::DataLoader, Context, Object, ID};
+use sea_orm::DatabaseConnection;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::core::{tags, AdminGuard, AppError, LoggedInGuard, UserError};
+
+use super::{TagObject, TagRepo};
+
+#[derive(Default)]
+pub struct TagMutation;
+
+#[Object]
+impl TagMutation {
+    /// Renames the tag on every class using it. Fails if the new name is taken,
+    /// `mergeTags` joins two tags into one.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    async fn rename_tag(
+        &self,
+        ctx: &Context<'_>,
+        tag_id: ID,
+        name: String,
+    ) -> Result<TagObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let tag_id = Uuid::parse_str(tag_id.as_str())?;
+        ensure_tag_exists(data_loader, tag_id).await?;
+        let normalized = tags::normalize_tags(&name)?;
+        let [name] = normalized.as_slice() else {
+            return Err(AppError::user(
+                "Tag must be a single word",
+                UserError::BadInput {
+                    parameter: "name",
+                    given_value: name,
+                },
+            ));
+        };
+        if let Some(existing) = TagRepo::find_tag_by_name(data_loader, name.clone()).await? {
+            if existing.id != tag_id {
+                return Err(AppError::user(
+                    "A tag with this name already exists",
+                    UserError::AlreadyTaken {
+                        parameter: "name",
+                        given_value: name.clone(),
+                    },
+                ));
+            }
+        }
+
+        TagRepo::rename_tag(data_loader, tag_id, name.clone()).await?;
+        find_tag(data_loader, tag_id).await
+    }
+
+    /// Moves the classes of the `sourceIds` tags over to `targetId` and deletes the sources.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    async fn merge_tags(
+        &self,
+        ctx: &Context<'_>,
+        source_ids: Vec<ID>,
+        target_id: ID,
+    ) -> Result<TagObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let target_id = Uuid::parse_str(target_id.as_str())?;
+        let source_ids = source_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+        ensure_tag_exists(data_loader, target_id).await?;
+
+        TagRepo::merge_tags(data_loader, source_ids, target_id).await?;
+        find_tag(data_loader, target_id).await
+    }
+}
+
+async fn find_tag(
+    data_loader: &DataLoader<DatabaseConnection>,
+    tag_id: Uuid,
+) -> Result<TagObject, AppError> {
+    match TagRepo::find_tag_by_id(data_loader, tag_id).await? {
+        Some(tag) => Ok(tag.into()),
+        None => Err(AppError::not_found(
+            "Tag not found".into(),
+            "tag",
+            "id",
+            tag_id.to_string(),
+        )),
+    }
+}
+
+async fn ensure_tag_exists(
+    data_loader: &DataLoader<DatabaseConnection>,
+    tag_id: Uuid,
+) -> Result<(), AppError> {
+    match TagRepo::find_tag_by_id(data_loader, tag_id).await? {
+        Some(_) => Ok(()),
+        None => Err(AppError::not_found(
+            "Tag not found".into(),
+            "tag",
+            "id",
+            tag_id.to_string(),
+        )),
+    }
+}
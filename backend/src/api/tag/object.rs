@@ -0,0 +1,25 @@
+use async_graphql::{SimpleObject, ID};
+use chrono::NaiveDateTime;
+
+use super::repo::TagWithCount;
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "Tag")]
+pub struct TagObject {
+    pub id: ID,
+    pub name: String,
+    /// Public classes using the tag, deleted and archived ones left out.
+    pub class_count: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<TagWithCount> for TagObject {
+    fn from(t: TagWithCount) -> Self {
+        Self {
+            id: ID::from(t.tag.id),
+            name: t.tag.name,
+            class_count: t.class_count,
+            created_at: t.tag.created_at,
+        }
+    }
+}
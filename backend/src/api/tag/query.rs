@@ -0,0 +1,43 @@
+use async_graphql::{dataloader::DataLoader, Context, Object};
+use sea_orm::sea_query::Expr;
+use sea_orm::{Condition, DatabaseConnection};
+use tracing::instrument;
+
+use crate::core::pagination::contains_pattern;
+use crate::core::{tags, AppError, LoggedInGuard, TokenScopeGuard};
+
+use super::{TagObject, TagRepo};
+
+/// Tags listed when `limit` is not given.
+const DEFAULT_TAG_LIMIT: i32 = 10;
+const MAX_TAG_LIMIT: i32 = 50;
+
+#[derive(Default)]
+pub struct TagQuery;
+
+#[Object]
+impl TagQuery {
+    /// The most used tags of public classes, or the ones containing `search` to
+    /// autocomplete what a teacher is typing.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn tags(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<Vec<TagObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let mut condition = Condition::all();
+        if let Some(search) = search.as_deref().and_then(tags::normalize_tag) {
+            condition = condition.add(
+                Expr::col(::entity::tag::Column::Name).like(contains_pattern(&search).as_str()),
+            );
+        }
+        let limit = limit.unwrap_or(DEFAULT_TAG_LIMIT).clamp(0, MAX_TAG_LIMIT);
+
+        let tags = TagRepo::find_tags(data_loader, condition, limit as u64).await?;
+        Ok(tags.into_iter().map(|t| t.into()).collect())
+    }
+}
@@ -0,0 +1,258 @@
+use ::entity::{class_tag, class_tag::Entity as ClassTag, tag, tag::Entity as Tag};
+use async_graphql::dataloader::DataLoader;
+use async_trait::async_trait;
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// A tag with the number of public classes using it.
+#[derive(Debug)]
+pub struct TagWithCount {
+    pub tag: tag::Model,
+    pub class_count: i64,
+}
+
+impl FromQueryResult for TagWithCount {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            tag: tag::Model::from_query_result(res, pre)?,
+            class_count: res.try_get(pre, "class_count")?,
+        })
+    }
+}
+
+/// Public classes that are neither deleted nor archived, tagged with the selected tag.
+const CLASS_COUNT: &str = r#"(
+    select count(*)
+    from class_tag ct
+    join "class" c on c.id = ct.class_id
+    where ct.tag_id = "tag"."id"
+    and c.public = true
+    and c.deleted_at is null
+    and c.archived_at is null
+)"#;
+
+/// Tags that no class links to.
+const ORPHANED: &str = r#"not exists (select 1 from class_tag ct where ct.tag_id = "tag"."id")"#;
+
+/// Replaces the tag named `$2` with `$3` in `class.tags` of the classes linked to tag `$1`.
+const REPLACE_IN_CLASS_TAGS: &str = r#"
+    update "class"
+    set tags = array_to_string(array_replace(string_to_array(tags, ' '), $2, $3), ' ')
+    where id in (select class_id from class_tag where tag_id = $1);
+"#;
+
+/// Rewrites `class.tags` of the classes linked to tag `$1` from their links. Tags keep the
+/// position they had, duplicates left behind by `REPLACE_IN_CLASS_TAGS` are dropped.
+const SYNC_CLASS_TAGS: &str = r#"
+    update "class" c
+    set tags = coalesce((
+        select string_agg(
+            t.name,
+            ' ' order by array_position(string_to_array(c.tags, ' '), t.name), t.name
+        )
+        from class_tag ct
+        join tag t on t.id = ct.tag_id
+        where ct.class_id = c.id
+    ), '')
+    where c.id in (select class_id from class_tag where tag_id = $1);
+"#;
+
+/// Deletes the tags among `ids` that are not used by any class anymore.
+async fn delete_orphaned_tags<C: ConnectionTrait>(db: &C, ids: Vec<Uuid>) -> Result<(), DbErr> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    Tag::delete_many()
+        .filter(tag::Column::Id.is_in(ids))
+        .filter(Expr::cust(ORPHANED))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Links the class to exactly the tags called `names`, creating the missing ones, and
+/// deletes the tags the class was the last one to use.
+/// The caller keeps `class.tags` in line with `names`.
+pub async fn set_class_tags<C: ConnectionTrait>(
+    db: &C,
+    class_id: Uuid,
+    names: &[String],
+) -> Result<(), DbErr> {
+    let previous = ClassTag::find()
+        .filter(class_tag::Column::ClassId.eq(class_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|ct| ct.tag_id)
+        .collect::<Vec<_>>();
+    ClassTag::delete_many()
+        .filter(class_tag::Column::ClassId.eq(class_id))
+        .exec(db)
+        .await?;
+    if names.is_empty() {
+        return delete_orphaned_tags(db, previous).await;
+    }
+
+    let now = Utc::now().naive_utc();
+    Tag::insert_many(names.iter().map(|name| tag::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        name: Set(name.clone()),
+        created_at: Set(now),
+    }))
+    .on_conflict(
+        OnConflict::column(tag::Column::Name)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    let tags = Tag::find()
+        .filter(tag::Column::Name.is_in(names.iter().cloned()))
+        .all(db)
+        .await?;
+    ClassTag::insert_many(tags.into_iter().map(|tag| class_tag::ActiveModel {
+        class_id: Set(class_id),
+        tag_id: Set(tag.id),
+    }))
+    .exec_without_returning(db)
+    .await?;
+
+    delete_orphaned_tags(db, previous).await
+}
+
+#[async_trait]
+pub trait TagRepo {
+    async fn find_tags(&self, filter: Condition, limit: u64) -> Result<Vec<TagWithCount>, DbErr>;
+    async fn find_tag_by_id(&self, id: Uuid) -> Result<Option<TagWithCount>, DbErr>;
+    async fn find_tag_by_name(&self, name: String) -> Result<Option<tag::Model>, DbErr>;
+    async fn rename_tag(&self, id: Uuid, name: String) -> Result<(), TransactionError<DbErr>>;
+    async fn merge_tags(
+        &self,
+        source_ids: Vec<Uuid>,
+        target_id: Uuid,
+    ) -> Result<(), TransactionError<DbErr>>;
+}
+
+#[async_trait]
+impl TagRepo for DataLoader<DatabaseConnection> {
+    #[instrument(skip(self), err(Debug))]
+    async fn find_tags(&self, filter: Condition, limit: u64) -> Result<Vec<TagWithCount>, DbErr> {
+        let tags = Tag::find()
+            .column_as(Expr::cust(CLASS_COUNT), "class_count")
+            .filter(filter)
+            // tags of private classes would give away what they are about
+            .filter(Expr::cust(&format!("{CLASS_COUNT} > 0")))
+            .order_by(Expr::cust("class_count"), Order::Desc)
+            .order_by_asc(tag::Column::Name)
+            .limit(limit)
+            .into_model::<TagWithCount>()
+            .all(self.loader())
+            .await?;
+        Ok(tags)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_tag_by_id(&self, id: Uuid) -> Result<Option<TagWithCount>, DbErr> {
+        let tag = Tag::find_by_id(id)
+            .column_as(Expr::cust(CLASS_COUNT), "class_count")
+            .into_model::<TagWithCount>()
+            .one(self.loader())
+            .await?;
+        Ok(tag)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_tag_by_name(&self, name: String) -> Result<Option<tag::Model>, DbErr> {
+        let tag = Tag::find()
+            .filter(tag::Column::Name.eq(name))
+            .one(self.loader())
+            .await?;
+        Ok(tag)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn rename_tag(&self, id: Uuid, name: String) -> Result<(), TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    let Some(tag) = Tag::find_by_id(id).one(txn).await? else {
+                        return Err(DbErr::RecordNotFound("tag not found".into()));
+                    };
+                    txn.execute(Statement::from_sql_and_values(
+                        DbBackend::Postgres,
+                        REPLACE_IN_CLASS_TAGS,
+                        [id.into(), tag.name.clone().into(), name.clone().into()],
+                    ))
+                    .await?;
+
+                    let mut active = tag.into_active_model();
+                    active.name = Set(name);
+                    active.update(txn).await?;
+                    Ok(())
+                })
+            })
+            .await
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn merge_tags(
+        &self,
+        source_ids: Vec<Uuid>,
+        target_id: Uuid,
+    ) -> Result<(), TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    let Some(target) = Tag::find_by_id(target_id).one(txn).await? else {
+                        return Err(DbErr::RecordNotFound("tag not found".into()));
+                    };
+                    let sources = Tag::find()
+                        .filter(tag::Column::Id.is_in(source_ids))
+                        .filter(tag::Column::Id.ne(target_id))
+                        .all(txn)
+                        .await?;
+
+                    for source in &sources {
+                        txn.execute(Statement::from_sql_and_values(
+                            DbBackend::Postgres,
+                            REPLACE_IN_CLASS_TAGS,
+                            [
+                                source.id.into(),
+                                source.name.clone().into(),
+                                target.name.clone().into(),
+                            ],
+                        ))
+                        .await?;
+                        txn.execute(Statement::from_sql_and_values(
+                            DbBackend::Postgres,
+                            r#"
+                            insert into class_tag (class_id, tag_id)
+                            select class_id, $2 from class_tag where tag_id = $1
+                            on conflict do nothing;
+                            "#,
+                            [source.id.into(), target_id.into()],
+                        ))
+                        .await?;
+                    }
+
+                    // Links of the merged tags go with them
+                    Tag::delete_many()
+                        .filter(tag::Column::Id.is_in(sources.iter().map(|s| s.id)))
+                        .exec(txn)
+                        .await?;
+                    txn.execute(Statement::from_sql_and_values(
+                        DbBackend::Postgres,
+                        SYNC_CLASS_TAGS,
+                        [target_id.into()],
+                    ))
+                    .await?;
+                    Ok(())
+                })
+            })
+            .await
+    }
+}
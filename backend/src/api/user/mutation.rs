@@ -1,25 +1,53 @@
 use crate::api::MAX_FILE_SIZE;
-use crate::core::auth::Claims;
+use crate::core::auth::{
+    create_access_token, create_two_factor_challenge, generate_opaque_token, hash_opaque_token,
+    revoke_sessions, take_two_factor_challenge, Claims, ACTIVATION_TOKEN_TTL_HOURS,
+    EMAIL_CHANGE_TOKEN_TTL_HOURS, PASSWORD_RESET_TOKEN_TTL_MINUTES, REFRESH_TOKEN_TTL_DAYS,
+};
+use crate::core::email_templates::{self, EmailTemplate};
+use crate::core::impersonation;
+use crate::core::localization;
+use crate::core::lockout;
+use crate::core::mailer::DynMailer;
+use crate::core::oidc::IdTokenClaims;
+use crate::core::password_policy;
+use crate::core::personal_access_token;
+use crate::core::rate_limit::{self, ClientIp};
+use crate::core::totp;
 use crate::core::AppError;
 use crate::core::UserError;
-use crate::{HOST_URL, MAIL_PASSWORD, MAIL_USERNAME, SECRET};
+use crate::core::{
+    class_role, maybe_undefined_to_active_value, option_to_active_value, AdminGuard, LoggedInGuard,
+    NotImpersonatingGuard, ResourceOwnerGuard, TokenScopeGuard, TwoFactorSetupGuard,
+};
+use crate::HOST_URL;
 use async_graphql::Upload;
 use async_graphql::ID;
 use async_graphql::{dataloader::DataLoader, Context, Object};
-use chrono::NaiveDateTime;
-use entity::user;
-use lettre::AsyncTransport;
+use chrono::{NaiveDateTime, Utc};
+use deadpool_redis::Pool;
+use entity::{refresh_token, sea_orm_active_enums, user};
 use tracing::instrument;
 
-use sea_orm::DatabaseConnection;
+use sea_orm::{DatabaseConnection, Set};
+use std::net::IpAddr;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use uuid::Uuid;
 
 use super::object::UserType;
-use super::object::{LoginInput, LoginResult, SignupInput};
+use super::object::{
+    CreatePersonalAccessTokenInput, CreatedPersonalAccessToken, ImpersonationObject, LoginInput,
+    LoginResult, SignupInput, TwoFactorSetup, UpdatePreferencesInput,
+};
 use super::UserObject;
 use super::UserRepo;
 
+const PASSWORD_RESET_EMAIL_LIMIT: u32 = 3;
+const PASSWORD_RESET_IP_LIMIT: u32 = 10;
+/// Activation emails per hour, like password resets.
+const ACTIVATION_EMAIL_LIMIT: u32 = 3;
+const ACTIVATION_IP_LIMIT: u32 = 10;
+
 #[derive(Default)]
 pub struct UserMutation;
 
@@ -33,9 +61,10 @@ impl UserMutation {
     ) -> Result<String, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let s3_bucket = ctx.data_unchecked::<s3::Bucket>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
 
         let avatar = input.avatar.take();
-        let id = register_user(input, data_loader, avatar.is_some()).await?;
+        let id = register_user(input, data_loader, mailer, avatar.is_some()).await?;
 
         if let Some(avatar) = avatar {
             let avatar = avatar.value(ctx)?;
@@ -73,11 +102,439 @@ impl UserMutation {
         input: LoginInput,
     ) -> Result<LoginResult, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
-        let res = login_user(input, data_loader).await?;
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+        let client_ip = ctx.data_opt::<ClientIp>().map(|ClientIp(ip)| *ip);
+        let res = login_user(input, data_loader, redis_pool, mailer, client_ip).await?;
         Ok(res)
     }
 
+    #[instrument(skip(self, ctx, two_factor_token, code), err(Debug))]
+    pub async fn login_two_factor(
+        &self,
+        ctx: &Context<'_>,
+        two_factor_token: String,
+        code: String,
+    ) -> Result<LoginResult, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        let user_id = take_two_factor_challenge(redis_pool, &two_factor_token).await?;
+        let user = find_user(data_loader, user_id).await?;
+        if !user.active || user.deleted_at.is_some() {
+            return Err(AppError::auth("User is not active"));
+        }
+
+        verify_two_factor_code(data_loader, &user, &code).await?;
+
+        start_session(data_loader, &user).await
+    }
+
+    /// Generates a new secret for the caller. It only takes effect after `confirmTwoFactor`.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "TwoFactorSetupGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())"
+    )]
+    pub async fn setup_two_factor(&self, ctx: &Context<'_>) -> Result<TwoFactorSetup, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+        if user.totp_enabled {
+            return Err(AppError::user(
+                "Two-factor authentication is already enabled",
+                UserError::BadInput {
+                    parameter: "user_id",
+                    given_value: user_id.to_string(),
+                },
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        UserRepo::set_totp_secret(data_loader, user_id, totp::encrypt_secret(&secret)).await?;
+
+        Ok(TwoFactorSetup {
+            otpauth_uri: totp::otpauth_uri(&secret, &user.username),
+            secret,
+        })
+    }
+
+    /// Enables two-factor authentication and returns the recovery codes.
+    /// They are shown only once. Tokens limited to the setup have to be refreshed afterwards.
+    #[instrument(skip(self, ctx, code), err(Debug))]
+    #[graphql(
+        guard = "TwoFactorSetupGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())"
+    )]
+    pub async fn confirm_two_factor(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> Result<Vec<String>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+        let secret = user.totp_secret.as_deref().and_then(totp::decrypt_secret);
+        let secret = match (secret, user.totp_enabled) {
+            (Some(secret), false) => secret,
+            _ => {
+                return Err(AppError::user(
+                    "Two-factor setup was not started",
+                    UserError::BadInput {
+                        parameter: "user_id",
+                        given_value: user_id.to_string(),
+                    },
+                ))
+            }
+        };
+
+        let step = match totp::verify(&secret, &code, Utc::now().timestamp(), None) {
+            Some(step) => step,
+            None => return Err(AppError::auth("Invalid two-factor code")),
+        };
+
+        let recovery_codes = totp::generate_recovery_codes();
+        let hashes = recovery_codes
+            .iter()
+            .map(|c| hash_opaque_token(c))
+            .collect();
+        UserRepo::enable_totp(data_loader, user_id, step, hashes).await?;
+
+        Ok(recovery_codes)
+    }
+
+    #[instrument(skip(self, ctx, password, code), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn disable_two_factor(
+        &self,
+        ctx: &Context<'_>,
+        password: String,
+        code: String,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+        if user.totp_required {
+            return Err(AppError::auth(
+                "Two-factor authentication is required for this account",
+            ));
+        }
+
+        let is_match = argon2_async::verify(password, user.password.clone()).await?;
+        if !is_match {
+            return Err(AppError::auth("Bad credentials"));
+        }
+
+        verify_two_factor_code(data_loader, &user, &code).await?;
+        UserRepo::disable_totp(data_loader, user_id).await?;
+
+        Ok(true)
+    }
+
+    /// Replaces all recovery codes of the caller, used or not.
+    #[instrument(skip(self, ctx, code), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn regenerate_recovery_codes(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> Result<Vec<String>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+        verify_two_factor_code(data_loader, &user, &code).await?;
+
+        let recovery_codes = totp::generate_recovery_codes();
+        let hashes = recovery_codes
+            .iter()
+            .map(|c| hash_opaque_token(c))
+            .collect();
+        UserRepo::replace_recovery_codes(data_loader, user_id, hashes).await?;
+
+        Ok(recovery_codes)
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    pub async fn admin_set_two_factor_required(
+        &self,
+        ctx: &Context<'_>,
+        user_id: ID,
+        required: bool,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let user_id = Uuid::parse_str(&user_id)?;
+        UserRepo::set_totp_required(data_loader, user_id, required).await?;
+
+        Ok(true)
+    }
+
+    /// Lifts a login lockout before it runs out and resets the failed attempt count.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    pub async fn admin_clear_lockout(
+        &self,
+        ctx: &Context<'_>,
+        user_id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&user_id)?;
+        let user = find_user(data_loader, user_id).await?;
+        lockout::unlock_account(redis_pool, user.id, &user.username).await?;
+        tracing::info!(
+            target: "backend::security",
+            event = "account_unlocked",
+            user_id = %user.id,
+            by = %claims.as_ref().expect("Guard ensures claims exist").sub,
+        );
+
+        Ok(true)
+    }
+
+    /// Lets an admin act as `user_id` for a short time, to see what they see.
+    /// Every request made with the token is written to the impersonation log.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    pub async fn impersonate_user(
+        &self,
+        ctx: &Context<'_>,
+        user_id: ID,
+    ) -> Result<ImpersonationObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        let admin_id = Uuid::parse_str(&claims.sub)?;
+        let user = find_user(data_loader, Uuid::parse_str(&user_id)?).await?;
+        if user.user_type == sea_orm_active_enums::UserType::Admin {
+            return Err(AppError::auth("Admins cannot be impersonated"));
+        }
+        // unactivated accounts are removed after a while, see `delete_unactivated_users`
+        if !user.active || user.deleted_at.is_some() {
+            return Err(AppError::auth("User is not active"));
+        }
+
+        let session_id = Uuid::new_v4();
+        let (access_token, impersonation_claims) =
+            impersonation::create_token(admin_id, user.id, session_id)?;
+        impersonation::record(data_loader, &impersonation_claims, "start").await?;
+        tracing::info!(
+            target: "backend::security",
+            event = "impersonation_started",
+            user_id = %user.id,
+            by = %admin_id,
+            session_id = %session_id,
+        );
+
+        Ok(ImpersonationObject {
+            access_token,
+            expires_at: NaiveDateTime::from_timestamp_opt(impersonation_claims.exp, 0)
+                .expect("Token expiry is a valid timestamp"),
+            user: user.into(),
+        })
+    }
+
+    #[instrument(skip(self, ctx, refresh_token), err(Debug))]
+    pub async fn refresh_token(
+        &self,
+        ctx: &Context<'_>,
+        refresh_token: String,
+    ) -> Result<LoginResult, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        let stored =
+            UserRepo::find_refresh_token(data_loader, hash_opaque_token(&refresh_token)).await?;
+        let stored = match stored {
+            Some(stored) => stored,
+            None => return Err(AppError::auth("Invalid refresh token")),
+        };
+
+        // A rotated token being presented again means it leaked, so the whole session is ended.
+        if stored.revoked_at.is_some() {
+            UserRepo::revoke_session(data_loader, stored.session_id).await?;
+            revoke_sessions(redis_pool, &[stored.session_id]).await?;
+            return Err(AppError::auth("Refresh token was revoked"));
+        }
+
+        if stored.expires_at < Utc::now().naive_utc() {
+            return Err(AppError::auth("Refresh token expired"));
+        }
+
+        let user = UserRepo::find_by_id(data_loader, stored.user_id)
+            .await?
+            .expect("RefreshToken to User is not optional");
+        if !user.active || user.deleted_at.is_some() {
+            end_user_sessions(data_loader, redis_pool, user.id).await?;
+            return Err(AppError::auth("User is not active"));
+        }
+
+        let new_token = generate_opaque_token();
+        let rotated = UserRepo::rotate_refresh_token(
+            data_loader,
+            stored.id,
+            new_refresh_token(&new_token, stored.session_id, user.id),
+        )
+        .await?;
+        // Someone else rotated the token in the meantime, which counts as presenting it again
+        if rotated.is_none() {
+            UserRepo::revoke_session(data_loader, stored.session_id).await?;
+            revoke_sessions(redis_pool, &[stored.session_id]).await?;
+            return Err(AppError::auth("Refresh token was revoked"));
+        }
+
+        let two_factor_setup_required = needs_two_factor_setup(&user);
+        Ok(LoginResult {
+            token: Some(create_access_token(
+                user.id,
+                stored.session_id,
+                two_factor_setup_required,
+            )?),
+            refresh_token: Some(new_token),
+            two_factor_setup_required,
+            ..Default::default()
+        })
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "TwoFactorSetupGuard")]
+    pub async fn logout(&self, ctx: &Context<'_>) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        let session_id = Uuid::parse_str(&claims.sid)?;
+        UserRepo::revoke_session(data_loader, session_id).await?;
+        revoke_sessions(redis_pool, &[session_id]).await?;
+        impersonation::record(data_loader, claims, "end").await?;
+
+        Ok(true)
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn logout_everywhere(&self, ctx: &Context<'_>) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        end_user_sessions(data_loader, redis_pool, user_id).await?;
+
+        Ok(true)
+    }
+
+    /// Creates a token for scripts and integrations. It is returned only this once.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn create_personal_access_token(
+        &self,
+        ctx: &Context<'_>,
+        input: CreatePersonalAccessTokenInput,
+    ) -> Result<CreatedPersonalAccessToken, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        // otherwise a scoped token could mint itself a token without the scope
+        if claims.is_personal_access_token() {
+            return Err(AppError::auth(
+                "Personal access tokens cannot create other tokens",
+            ));
+        }
+        let user_id = Uuid::parse_str(&claims.sub)?;
+
+        let now = Utc::now().naive_utc();
+        if matches!(input.expires_at, Some(expires_at) if expires_at <= now) {
+            return Err(AppError::user(
+                "Token would expire immediately",
+                UserError::BadInput {
+                    parameter: "expires_at",
+                    given_value: format!("{:?}", input.expires_at),
+                },
+            ));
+        }
+
+        let class_ids = match input.class_ids {
+            Some(class_ids) => {
+                let mut parsed = Vec::with_capacity(class_ids.len());
+                for class_id in class_ids {
+                    let id = Uuid::parse_str(&class_id)?;
+                    if class_role(data_loader, user_id, id).await?.is_none() {
+                        return Err(AppError::user(
+                            "Tokens can only be limited to classes you are a member of",
+                            UserError::BadInput {
+                                parameter: "class_ids",
+                                given_value: class_id.to_string(),
+                            },
+                        ));
+                    }
+                    parsed.push(id);
+                }
+                Some(parsed)
+            }
+            None => None,
+        };
+
+        let (token, token_hash) = personal_access_token::generate();
+        let model = ::entity::personal_access_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            name: Set(input.name),
+            token_hash: Set(token_hash),
+            user_id: Set(user_id),
+            read_only: Set(input.read_only),
+            class_ids: Set(class_ids),
+            created_at: Set(now),
+            expires_at: Set(input.expires_at),
+            last_used_at: Set(None),
+            revoked_at: Set(None),
+        };
+        let stored = UserRepo::create_personal_access_token(data_loader, model).await?;
+
+        Ok(CreatedPersonalAccessToken {
+            token,
+            personal_access_token: stored.into(),
+        })
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    pub async fn revoke_personal_access_token(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let token_id = Uuid::parse_str(&id)?;
+        if !UserRepo::revoke_personal_access_token(data_loader, user_id, token_id).await? {
+            return Err(AppError::not_found(
+                "Personal access token not found".into(),
+                "personal_access_token",
+                "id",
+                id.to_string(),
+            ));
+        }
+
+        Ok(true)
+    }
+
     #[instrument(skip(self, ctx, avatar), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(NotImpersonatingGuard).and(ResourceOwnerGuard::user(user_id.clone())).and(TokenScopeGuard::account())"
+    )]
     pub async fn update_user(
         &self,
         ctx: &Context<'_>,
@@ -122,7 +579,68 @@ impl UserMutation {
         Ok(user.into())
     }
 
+    /// Updates the profile and preferences of the caller.
     #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    async fn update_my_preferences(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdatePreferencesInput,
+    ) -> Result<UserObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+
+        if let Some(timezone) = &input.timezone {
+            if !localization::is_valid_timezone(timezone) {
+                return Err(AppError::user(
+                    "Unknown timezone",
+                    UserError::BadInput {
+                        parameter: "timezone",
+                        given_value: timezone.clone(),
+                    },
+                ));
+            }
+        }
+        if let Some(locale) = &input.locale {
+            if !email_templates::SUPPORTED_LOCALES.contains(&locale.as_str()) {
+                return Err(AppError::user(
+                    "Unsupported locale",
+                    UserError::BadInput {
+                        parameter: "locale",
+                        given_value: locale.clone(),
+                    },
+                ));
+            }
+        }
+
+        let mut preferences = user.preferences;
+        if let Some(notifications) = input.notifications {
+            notifications.apply(&mut preferences.notifications);
+        }
+        if let Some(display) = input.display {
+            display.apply(&mut preferences.display);
+        }
+
+        let model = user::ActiveModel {
+            bio: maybe_undefined_to_active_value(input.bio),
+            pronouns: maybe_undefined_to_active_value(input.pronouns),
+            timezone: option_to_active_value(input.timezone),
+            locale: option_to_active_value(input.locale),
+            preferences: Set(preferences),
+            ..Default::default()
+        };
+        let user = UserRepo::update_preferences(data_loader, user_id, model).await?;
+
+        Ok(user.into())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(
+        guard = "LoggedInGuard.and(NotImpersonatingGuard).and(ResourceOwnerGuard::user(user_id.clone())).and(TokenScopeGuard::account())"
+    )]
     async fn change_password(
         &self,
         ctx: &Context<'_>,
@@ -131,14 +649,94 @@ impl UserMutation {
         new_password: String,
     ) -> Result<UserObject, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
         let user_id = Uuid::parse_str(&user_id)?;
+        let user = find_user(data_loader, user_id).await?;
+        password_policy::validate("new_password", &new_password, &user.username, &user.email)
+            .await?;
         let user =
             UserRepo::change_password(data_loader, user_id, old_password, new_password).await?;
+        end_user_sessions(data_loader, redis_pool, user_id).await?;
 
         Ok(user.into())
     }
 
-    #[instrument(skip(self, ctx), err(Debug))]
+    /// Emails a confirmation link to `new_email` and a notice to the current address,
+    /// which stays in use until the link is followed.
+    #[instrument(skip(self, ctx, password), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    async fn request_email_change(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(email))] new_email: String,
+        password: String,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+
+        let client_ip = ctx.data_opt::<ClientIp>().map(|ClientIp(ip)| *ip);
+        verify_password(redis_pool, mailer, &user, password, client_ip).await?;
+
+        // emails are compared regardless of case, a change of case is the same address
+        if new_email.to_lowercase() == user.email.to_lowercase() {
+            return Err(AppError::user(
+                "New email is the same as the current one",
+                UserError::BadInput {
+                    parameter: "new_email",
+                    given_value: new_email,
+                },
+            ));
+        }
+        // the lookup ignores case like the unique index, `confirm_email_change` still catches
+        // a race through that index
+        if UserRepo::find_by_email(data_loader, new_email.clone())
+            .await?
+            .is_some()
+        {
+            return Err(AppError::user(
+                "Email is already taken",
+                UserError::AlreadyTaken {
+                    parameter: "new_email",
+                    given_value: new_email,
+                },
+            ));
+        }
+
+        send_email_change_emails(data_loader, mailer, &user, new_email).await?;
+
+        Ok(true)
+    }
+
+    #[instrument(skip(self, ctx, password), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(NotImpersonatingGuard).and(TokenScopeGuard::account())")]
+    async fn change_username(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 5, max_length = 20))] username: String,
+        password: String,
+    ) -> Result<UserObject, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let user_id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+        let user = find_user(data_loader, user_id).await?;
+
+        let client_ip = ctx.data_opt::<ClientIp>().map(|ClientIp(ip)| *ip);
+        verify_password(redis_pool, mailer, &user, password, client_ip).await?;
+
+        let user = UserRepo::change_username(data_loader, user_id, username).await?;
+
+        Ok(user.into())
+    }
+
+    #[instrument(skip(self, ctx, token, password), err(Debug))]
     async fn emergency_change_password(
         &self,
         ctx: &Context<'_>,
@@ -146,13 +744,63 @@ impl UserMutation {
         password: String,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
-        let token = Uuid::parse_str(&token)?;
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let token_hash = hash_opaque_token(&token);
+
+        if let Some(user) =
+            UserRepo::find_user_by_reset_token(data_loader, token_hash.clone()).await?
+        {
+            password_policy::validate("password", &password, &user.username, &user.email).await?;
+        }
+        let user = UserRepo::emergency_change_password(data_loader, token_hash, password).await?;
+        end_user_sessions(data_loader, redis_pool, user.id).await?;
+
+        Ok(true)
+    }
+
+    /// Always succeeds, so it can't be used to find out which emails are registered.
+    /// Requests are throttled per email and per client address.
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn resend_activation_email(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+    ) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
+
+        if let Some(ClientIp(ip)) = ctx.data_opt::<ClientIp>() {
+            let key = format!("activation_email:ip:{ip}");
+            if !rate_limit::hit(redis_pool, &key, ACTIVATION_IP_LIMIT, 60 * 60).await? {
+                return Err(AppError::user(
+                    "Too many activation email requests",
+                    UserError::TooManyRequests,
+                ));
+            }
+        }
+
+        // hitting the email limit is silent, otherwise it would reveal that the email exists
+        let key = format!("activation_email:email:{}", email.to_lowercase());
+        if !rate_limit::hit(redis_pool, &key, ACTIVATION_EMAIL_LIMIT, 60 * 60).await? {
+            return Ok(true);
+        }
 
-        UserRepo::emergency_change_password(data_loader, token, password).await?;
+        let user = UserRepo::find_by_email(data_loader, email).await?;
+        if let Some(user) = user {
+            // failing here would tell that the email is registered
+            if !user.active && user.deleted_at.is_none() {
+                if let Err(err) = send_activation_email(data_loader, mailer, &user).await {
+                    tracing::error!("Failed to send activation email to {}: {err:?}", user.id);
+                }
+            }
+        }
 
         Ok(true)
     }
 
+    /// Responds the same way whether the email is registered or not. Requests are throttled
+    /// per email and per client address.
     #[instrument(skip(self, ctx), err(Debug))]
     async fn create_password_change_token(
         &self,
@@ -160,43 +808,65 @@ impl UserMutation {
         email: String,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+        let mailer = ctx.data_unchecked::<DynMailer>();
 
-        let (token, user) = UserRepo::create_password_change_token(data_loader, email).await?;
-        let host = HOST_URL.to_string();
-        let username = user.username;
-        let addr = user.email;
-        let body = format!(
-            r#"Hello, {username}! Please, follow the link to change your password: <a href="{host}/password-reset/{token}<a>">{host}/password-reset/{token}<a>"#
-        );
-        let email = lettre::Message::builder()
-            .from(
-                MAIL_USERNAME
-                    .parse()
-                    .expect("Service username should be valid email"),
-            )
-            .to(addr.parse().expect("User email should be valid email"))
-            .subject("Account activation")
-            .header(lettre::message::header::ContentType::TEXT_HTML)
-            .body(body)
-            .expect("Email should be valid");
-
-        let creds = lettre::transport::smtp::authentication::Credentials::new(
-            MAIL_USERNAME.to_string(),
-            MAIL_PASSWORD.to_string(),
-        );
+        if let Some(ClientIp(ip)) = ctx.data_opt::<ClientIp>() {
+            let key = format!("password_reset:ip:{ip}");
+            if !rate_limit::hit(redis_pool, &key, PASSWORD_RESET_IP_LIMIT, 60 * 60).await? {
+                return Err(AppError::user(
+                    "Too many password reset requests",
+                    UserError::TooManyRequests,
+                ));
+            }
+        }
 
-        let mailer: lettre::AsyncSmtpTransport<lettre::Tokio1Executor> =
-            lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::relay("smtp.gmail.com")
-                .expect("Relay should be valid")
-                .credentials(creds)
-                .build();
+        // hitting the email limit is silent, otherwise it would reveal that the email exists
+        let key = format!("password_reset:email:{}", email.to_lowercase());
+        if !rate_limit::hit(redis_pool, &key, PASSWORD_RESET_EMAIL_LIMIT, 60 * 60).await? {
+            return Ok(true);
+        }
 
-        mailer.send(email).await?;
+        let token = generate_opaque_token();
+        let res =
+            UserRepo::create_password_change_token(data_loader, email, hash_opaque_token(&token))
+                .await?;
+        let user = match res {
+            Some(user) => user,
+            None => return Ok(true),
+        };
+        let mut context = tera::Context::new();
+        context.insert("username", &user.username);
+        context.insert(
+            "link",
+            &format!("{}/password-reset/{token}", HOST_URL.as_str()),
+        );
+        context.insert("expires_in_minutes", &PASSWORD_RESET_TOKEN_TTL_MINUTES);
+        let expires_at =
+            Utc::now().naive_utc() + chrono::Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES);
+        context.insert(
+            "expires_at",
+            &localization::format_datetime_for(expires_at, &user),
+        );
+        let email = email_templates::render(
+            EmailTemplate::PasswordReset,
+            &user.locale,
+            user.email,
+            &context,
+        )?;
+        // failing here would tell that the email is registered
+        if let Err(err) = mailer.send(email).await {
+            tracing::error!(
+                "Failed to send password reset email to {}: {err:?}",
+                user.id
+            );
+        }
 
         Ok(true)
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
     async fn admin_user_update(
         &self,
         ctx: &Context<'_>,
@@ -205,26 +875,44 @@ impl UserMutation {
         deleted_at: Option<NaiveDateTime>,
     ) -> Result<bool, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
         let user_id = Uuid::parse_str(&user_id)?;
         UserRepo::admin_user_update(data_loader, user_id, user_type.into(), deleted_at).await?;
 
+        if deleted_at.is_some() {
+            end_user_sessions(data_loader, redis_pool, user_id).await?;
+        }
+
         Ok(true)
     }
 }
 
+/// Failed attempts are throttled per username and per client address, and enough of them
+/// lock the account until it times out or the owner follows the link in the unlock email.
 pub async fn login_user(
     creadentials: LoginInput,
     data_loader: &DataLoader<DatabaseConnection>,
+    redis_pool: &Pool,
+    mailer: &DynMailer,
+    client_ip: Option<IpAddr>,
 ) -> Result<LoginResult, AppError> {
+    lockout::check_login_allowed(redis_pool, &creadentials.username, client_ip).await?;
+
     let user = UserRepo::find_by_username(data_loader, creadentials.username.clone()).await?;
 
     let user = match user {
         Some(user) => user,
         None => {
+            // unknown usernames are throttled the same way, so timing them doesn't reveal anything
+            lockout::record_login_failure(redis_pool, &creadentials.username, client_ip).await?;
             return Err(AppError::auth("Bad credentials"));
         }
     };
 
+    if lockout::is_locked(redis_pool, user.id).await? {
+        return Err(AppError::auth("Account is temporarily locked"));
+    }
+
     if !user.active {
         return Err(AppError::auth("User is not active"));
     }
@@ -233,69 +921,354 @@ pub async fn login_user(
         return Err(AppError::auth("User is deleted"));
     }
 
-    let is_match = argon2_async::verify(creadentials.password, user.password).await?;
+    let is_match = argon2_async::verify(creadentials.password, user.password.clone()).await?;
+    if !is_match {
+        let failures =
+            lockout::record_login_failure(redis_pool, &creadentials.username, client_ip).await?;
+        if failures >= lockout::LOCKOUT_THRESHOLD {
+            lock_account(redis_pool, mailer, &user, client_ip).await?;
+            return Err(AppError::auth("Account is temporarily locked"));
+        }
+        return Err(AppError::auth("Bad credentials"));
+    }
+    lockout::clear_login_failures(redis_pool, &creadentials.username).await?;
+
+    if user.totp_enabled {
+        let two_factor_token = create_two_factor_challenge(redis_pool, user.id).await?;
+        return Ok(LoginResult {
+            two_factor_token: Some(two_factor_token),
+            ..Default::default()
+        });
+    }
+
+    start_session(data_loader, &user).await
+}
+
+/// Checks the password of a signed in user before a sensitive change. Failures count
+/// towards the same lockout as failed logins.
+pub async fn verify_password(
+    redis_pool: &Pool,
+    mailer: &DynMailer,
+    user: &user::Model,
+    password: String,
+    client_ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    lockout::check_login_allowed(redis_pool, &user.username, client_ip).await?;
+    if lockout::is_locked(redis_pool, user.id).await? {
+        return Err(AppError::auth("Account is temporarily locked"));
+    }
+
+    let is_match = argon2_async::verify(password, user.password.clone()).await?;
     if !is_match {
+        let failures = lockout::record_login_failure(redis_pool, &user.username, client_ip).await?;
+        if failures >= lockout::LOCKOUT_THRESHOLD {
+            lock_account(redis_pool, mailer, user, client_ip).await?;
+            return Err(AppError::auth("Account is temporarily locked"));
+        }
         return Err(AppError::auth("Bad credentials"));
     }
+    lockout::clear_login_failures(redis_pool, &user.username).await
+}
+
+async fn lock_account(
+    redis_pool: &Pool,
+    mailer: &DynMailer,
+    user: &user::Model,
+    client_ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    let token = lockout::lock_account(redis_pool, user.id).await?;
+    tracing::warn!(
+        target: "backend::security",
+        event = "account_locked",
+        user_id = %user.id,
+        ip = ?client_ip,
+        minutes = lockout::LOCKOUT_MINUTES,
+    );
+
+    let mut context = tera::Context::new();
+    context.insert("username", &user.username);
+    context.insert(
+        "link",
+        &format!("{}/api/v1/user/unlock/{token}", HOST_URL.as_str()),
+    );
+    context.insert("locked_for_minutes", &lockout::LOCKOUT_MINUTES);
+    let locked_until = Utc::now().naive_utc() + chrono::Duration::minutes(lockout::LOCKOUT_MINUTES);
+    context.insert(
+        "locked_until",
+        &localization::format_datetime_for(locked_until, user),
+    );
+    let email = email_templates::render(
+        EmailTemplate::AccountLocked,
+        &user.locale,
+        user.email.clone(),
+        &context,
+    )?;
+    mailer.send(email).await
+}
+
+/// Starts a new session for the user, returning a short lived access token
+/// and the first refresh token of the session.
+pub async fn start_session(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user: &user::Model,
+) -> Result<LoginResult, AppError> {
+    let session_id = Uuid::new_v4();
+    let refresh_token = generate_opaque_token();
+    UserRepo::create_refresh_token(
+        data_loader,
+        new_refresh_token(&refresh_token, session_id, user.id),
+    )
+    .await?;
+
+    let two_factor_setup_required = needs_two_factor_setup(user);
+    let token = create_access_token(user.id, session_id, two_factor_setup_required)?;
+    Ok(LoginResult {
+        token: Some(token),
+        refresh_token: Some(refresh_token),
+        two_factor_setup_required,
+        ..Default::default()
+    })
+}
+
+/// Users required to use two-factor authentication only get to set it up until they do.
+fn needs_two_factor_setup(user: &user::Model) -> bool {
+    user.totp_required && !user.totp_enabled
+}
+
+async fn find_user(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user_id: Uuid,
+) -> Result<user::Model, AppError> {
+    match UserRepo::find_by_id(data_loader, user_id).await? {
+        Some(user) => Ok(user),
+        None => Err(AppError::not_found(
+            "user not found",
+            "user",
+            "id",
+            &user_id.to_string(),
+        )),
+    }
+}
+
+/// Accepts either a TOTP code or one of the unused recovery codes of the user.
+async fn verify_two_factor_code(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user: &user::Model,
+    code: &str,
+) -> Result<(), AppError> {
+    if !user.totp_enabled {
+        return Err(AppError::auth("Two-factor authentication is not enabled"));
+    }
+
+    let secret = user.totp_secret.as_deref().and_then(totp::decrypt_secret);
+    if secret.is_none() {
+        tracing::error!("Two-factor secret of user {} can't be decrypted", user.id);
+    }
+    let step = secret.and_then(|secret| {
+        totp::verify(&secret, code, Utc::now().timestamp(), user.totp_last_step)
+    });
+    if let Some(step) = step {
+        // the step is claimed in the database, so of two requests racing with the same code
+        // only one gets in
+        if UserRepo::claim_totp_step(data_loader, user.id, step).await? {
+            return Ok(());
+        }
+        return Err(AppError::auth("Invalid two-factor code"));
+    }
+
+    let code_hash = hash_opaque_token(&totp::normalize_recovery_code(code));
+    if UserRepo::use_recovery_code(data_loader, user.id, code_hash).await? {
+        return Ok(());
+    }
+
+    Err(AppError::auth("Invalid two-factor code"))
+}
+
+/// Revokes every session of the user, including access tokens that did not expire yet.
+pub async fn end_user_sessions(
+    data_loader: &DataLoader<DatabaseConnection>,
+    redis_pool: &Pool,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let session_ids = UserRepo::revoke_user_sessions(data_loader, user_id).await?;
+    revoke_sessions(redis_pool, &session_ids).await?;
+    Ok(())
+}
+
+fn new_refresh_token(token: &str, session_id: Uuid, user_id: Uuid) -> refresh_token::ActiveModel {
+    let now = Utc::now().naive_utc();
+    refresh_token::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        token_hash: Set(hash_opaque_token(token)),
+        session_id: Set(session_id),
+        user_id: Set(user_id),
+        created_at: Set(now),
+        expires_at: Set(now + chrono::Duration::days(REFRESH_TOKEN_TTL_DAYS)),
+        revoked_at: Set(None),
+    }
+}
+
+/// Emails a single use activation link. Sending a new one invalidates the previous link.
+#[instrument(skip(data_loader, mailer, user), err(Debug))]
+pub async fn send_activation_email(
+    data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
+    user: &user::Model,
+) -> Result<(), AppError> {
+    let token = generate_opaque_token();
+    UserRepo::create_activation_token(data_loader, user.id, hash_opaque_token(&token)).await?;
+
+    let mut context = tera::Context::new();
+    context.insert("username", &user.username);
+    context.insert(
+        "link",
+        &format!("{}/api/v1/user/activate/{token}", HOST_URL.as_str()),
+    );
+    context.insert("expires_in_hours", &ACTIVATION_TOKEN_TTL_HOURS);
+    let email = email_templates::render(
+        EmailTemplate::Activation,
+        &user.locale,
+        user.email.clone(),
+        &context,
+    )?;
+    mailer.send(email).await?;
+
+    Ok(())
+}
 
-    let token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &Claims {
-            sub: user.id.to_string(),
-            exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp(),
-        },
-        &jsonwebtoken::EncodingKey::from_secret(SECRET.as_ref()),
+/// Emails a single use link that moves the account to `new_email`, and lets the current
+/// address know about it. Requesting another change invalidates the previous link.
+#[instrument(skip(data_loader, mailer, user), err(Debug))]
+async fn send_email_change_emails(
+    data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
+    user: &user::Model,
+    new_email: String,
+) -> Result<(), AppError> {
+    let token = generate_opaque_token();
+    UserRepo::create_email_change_token(
+        data_loader,
+        user.id,
+        new_email.clone(),
+        hash_opaque_token(&token),
+    )
+    .await?;
+
+    let mut context = tera::Context::new();
+    context.insert("username", &user.username);
+    context.insert("new_email", &new_email);
+    let notice = email_templates::render(
+        EmailTemplate::EmailChangeNotice,
+        &user.locale,
+        user.email.clone(),
+        &context,
+    )?;
+
+    context.insert(
+        "link",
+        &format!("{}/api/v1/user/confirm-email/{token}", HOST_URL.as_str()),
+    );
+    context.insert("expires_in_hours", &EMAIL_CHANGE_TOKEN_TTL_HOURS);
+    let confirmation = email_templates::render(
+        EmailTemplate::EmailChange,
+        &user.locale,
+        new_email,
+        &context,
     )?;
 
-    Ok(LoginResult { token })
+    mailer.send(confirmation).await?;
+    mailer.send(notice).await?;
+
+    Ok(())
 }
 
-#[instrument(skip(creadentials, data_loader, has_avatar), err(Debug))]
+#[instrument(skip(creadentials, data_loader, mailer, has_avatar), err(Debug))]
 pub async fn register_user(
     mut creadentials: SignupInput,
     data_loader: &DataLoader<DatabaseConnection>,
+    mailer: &DynMailer,
     has_avatar: bool,
 ) -> Result<Uuid, AppError> {
+    password_policy::validate(
+        "password",
+        &creadentials.password,
+        &creadentials.username,
+        &creadentials.email,
+    )
+    .await?;
     let hash = argon2_async::hash(creadentials.password).await?;
     creadentials.password = hash;
-    let addr = creadentials.email.clone();
-    let username = creadentials.username.clone();
     let id = UserRepo::create_user(data_loader, creadentials.into_active_model(has_avatar)).await?;
 
-    // skip email sending in debug mode
-    if cfg!(debug_assertions) {
-        return Ok(id);
-    }
+    let user = UserRepo::find_by_id(data_loader, id)
+        .await?
+        .expect("User was just created");
+    send_activation_email(data_loader, mailer, &user).await?;
 
-    let host = HOST_URL.to_string();
-    let body = format!(
-        r#"Hello, {username}! Please, follow the link to activate your account: <a href="{host}/api/v1/user/activate/{id}<a>">{host}/api/v1/user/activate/{id}<a>"#
-    );
-
-    let email = lettre::Message::builder()
-        .from(
-            MAIL_USERNAME
-                .parse()
-                .expect("Service username should be valid email"),
-        )
-        .to(addr.parse().expect("User email should be valid email"))
-        .subject("Account activation")
-        .header(lettre::message::header::ContentType::TEXT_HTML)
-        .body(body)
-        .expect("Email should be valid");
-
-    let creds = lettre::transport::smtp::authentication::Credentials::new(
-        MAIL_USERNAME.to_string(),
-        MAIL_PASSWORD.to_string(),
-    );
+    Ok(id)
+}
 
-    let mailer: lettre::AsyncSmtpTransport<lettre::Tokio1Executor> =
-        lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::relay("smtp.gmail.com")
-            .expect("Relay should be valid")
-            .credentials(creds)
-            .build();
+/// Creates an already active account for someone whose email was verified by the identity provider.
+#[instrument(skip(data_loader, claims), err(Debug))]
+pub async fn register_oidc_user(
+    data_loader: &DataLoader<DatabaseConnection>,
+    claims: &IdTokenClaims,
+    email: String,
+) -> Result<Uuid, AppError> {
+    let preferred_username = match &claims.preferred_username {
+        Some(username) => username.as_str(),
+        None => email.split('@').next().unwrap_or_default(),
+    };
+    let username = available_username(data_loader, preferred_username).await?;
+    // the account has no usable password until the user sets one with a password reset
+    let password = argon2_async::hash(generate_opaque_token()).await?;
 
-    mailer.send(email).await?;
+    let user = user::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        username: Set(username),
+        first_name: Set(claims.given_name.clone().unwrap_or_default()),
+        last_name: Set(claims.family_name.clone().unwrap_or_default()),
+        has_avatar: Set(false),
+        email: Set(email),
+        password: Set(password),
+        created_at: Set(Utc::now().naive_utc()),
+        deleted_at: Set(None),
+        active: Set(true),
+        user_type: Set(sea_orm_active_enums::UserType::Regular),
+        locale: Set(
+            email_templates::resolve_locale(claims.locale.as_deref().unwrap_or_default()).into(),
+        ),
+        ..Default::default()
+    };
+    let id = UserRepo::create_user(data_loader, user).await?;
 
     Ok(id)
 }
+
+async fn available_username(
+    data_loader: &DataLoader<DatabaseConnection>,
+    preferred: &str,
+) -> Result<String, AppError> {
+    let base = preferred
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(14)
+        .collect::<String>();
+    if base.len() >= 5
+        && UserRepo::find_by_username(data_loader, base.clone())
+            .await?
+            .is_none()
+    {
+        return Ok(base);
+    }
+
+    loop {
+        let candidate = format!("{base}-{:05}", rand::random::<u32>() % 100_000);
+        if UserRepo::find_by_username(data_loader, candidate.clone())
+            .await?
+            .is_none()
+        {
+            return Ok(candidate);
+        }
+    }
+}
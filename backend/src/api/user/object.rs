@@ -5,18 +5,24 @@ use async_graphql::{
     ComplexObject, Context, InputObject, Object, Result, SimpleObject, Upload, ID,
 };
 
-use async_graphql::Enum;
+use async_graphql::{Enum, MaybeUndefined};
 use deadpool_redis::redis::{self, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs};
-use entity::{sea_orm_active_enums, user};
+use entity::{
+    impersonation_log, personal_access_token, sea_orm_active_enums, user, user_preferences,
+};
 use partialdebug::placeholder::PartialDebug;
-use sea_orm::{DatabaseConnection, Set};
+use sea_orm::sea_query::{Expr, Func};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, Set};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::api::assignment::{AssignmentObject, AssignmentRepo};
 use crate::api::class::{ClassObject, ClassRepo};
-use crate::core::{AppError, LoggedInGuard};
+use crate::api::user::UserRepo;
+use crate::core::email_templates::resolve_locale;
+use crate::core::pagination::{contains_pattern, OrderDirection};
+use crate::core::{AppError, Claims, LoggedInGuard};
 
 #[derive(Clone, Debug, SimpleObject, Serialize, Deserialize)]
 #[graphql(complex)]
@@ -30,6 +36,18 @@ pub struct UserObject {
     pub last_name: String,
     pub user_type: UserType,
     pub deleted_at: Option<chrono::NaiveDateTime>,
+    #[graphql(skip)]
+    pub two_factor_enabled: bool,
+    #[graphql(skip)]
+    pub two_factor_required: bool,
+    #[graphql(skip)]
+    pub locale: String,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    /// IANA name like `Europe/Ljubljana`.
+    pub timezone: String,
+    #[graphql(skip)]
+    pub preferences: PreferencesObject,
 }
 
 impl ToRedisArgs for UserObject {
@@ -48,6 +66,13 @@ impl ToRedisArgs for UserObject {
             self.deleted_at
                 .map(|d| d.timestamp().to_string())
                 .unwrap_or("".into()),
+            self.two_factor_enabled.to_string(),
+            self.two_factor_required.to_string(),
+            self.locale.clone(),
+            self.bio.clone().unwrap_or_default(),
+            self.pronouns.clone().unwrap_or_default(),
+            self.timezone.clone(),
+            serde_json::to_string(&self.preferences).expect("Preferences serialize to json"),
         ];
         vec.write_redis_args(out)
     }
@@ -72,6 +97,13 @@ impl FromRedisValue for UserObject {
                     0,
                 ))
             },
+            two_factor_enabled: vec[8].parse::<bool>().unwrap(),
+            two_factor_required: vec[9].parse::<bool>().unwrap(),
+            locale: vec[10].clone(),
+            bio: Some(vec[11].clone()).filter(|b| !b.is_empty()),
+            pronouns: Some(vec[12].clone()).filter(|p| !p.is_empty()),
+            timezone: vec[13].clone(),
+            preferences: serde_json::from_str(&vec[14]).unwrap_or_default(),
         })
     }
 }
@@ -132,12 +164,19 @@ impl UserObject {
     async fn clesses(&self, ctx: &Context<'_>) -> Result<Vec<ClassObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
 
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+
         let user_id = Uuid::parse_str(&self.id)?;
         let classes = ClassRepo::find_by_user_id(data_loader, user_id)
             .await?
             .expect("user id is valid");
 
-        Ok(classes.into_iter().map(|c| c.into()).collect())
+        Ok(classes
+            .into_iter()
+            .filter(|c| claims.allows_class(c.id))
+            .map(|c| c.into())
+            .collect())
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
@@ -145,12 +184,19 @@ impl UserObject {
     async fn owned_classes(&self, ctx: &Context<'_>) -> Result<Vec<ClassObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
 
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+
         let owner_id = Uuid::parse_str(&self.id)?;
         let classes = ClassRepo::find_by_owner_id(data_loader, owner_id)
             .await?
             .expect("owner id is valid");
 
-        Ok(classes.into_iter().map(|c| c.into()).collect())
+        Ok(classes
+            .into_iter()
+            .filter(|c| claims.allows_class(c.id))
+            .map(|c| c.into())
+            .collect())
     }
 
     #[instrument(skip(self, ctx), err(Debug))]
@@ -158,10 +204,94 @@ impl UserObject {
     async fn assignments(&self, ctx: &Context<'_>) -> Result<Vec<AssignmentObject>, AppError> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
 
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+
         let user_id = Uuid::parse_str(&self.id)?;
         let assignments = AssignmentRepo::find_by_user_id(data_loader, user_id).await?;
 
-        Ok(assignments.into_iter().map(|c| c.into()).collect())
+        Ok(assignments
+            .into_iter()
+            .filter(|a| claims.allows_class(a.class_id))
+            .map(|c| c.into())
+            .collect())
+    }
+
+    /// Only visible to the user themselves.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn preferences(&self, ctx: &Context<'_>) -> Result<Option<PreferencesObject>, AppError> {
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        match claims.sub == self.id.as_str() {
+            true => Ok(Some(self.preferences.clone())),
+            false => Ok(None),
+        }
+    }
+
+    /// Only visible to the user themselves and admins.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn two_factor_enabled(&self, ctx: &Context<'_>) -> Result<Option<bool>, AppError> {
+        match self.is_self_or_admin(ctx).await? {
+            true => Ok(Some(self.two_factor_enabled)),
+            false => Ok(None),
+        }
+    }
+
+    /// Only visible to the user themselves and admins.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn two_factor_required(&self, ctx: &Context<'_>) -> Result<Option<bool>, AppError> {
+        match self.is_self_or_admin(ctx).await? {
+            true => Ok(Some(self.two_factor_required)),
+            false => Ok(None),
+        }
+    }
+
+    /// Only visible to the user themselves and admins.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn locale(&self, ctx: &Context<'_>) -> Result<Option<String>, AppError> {
+        match self.is_self_or_admin(ctx).await? {
+            true => Ok(Some(self.locale.clone())),
+            false => Ok(None),
+        }
+    }
+
+    /// The admin behind the current request, only set on the impersonated user.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard")]
+    async fn impersonated_by(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        let admin_id = match claims.impersonator_id() {
+            Some(admin_id) if claims.sub == self.id.as_str() => admin_id,
+            _ => return Ok(None),
+        };
+
+        let admin = UserRepo::find_by_id(data_loader, admin_id).await?;
+        Ok(admin.map(|a| a.into()))
+    }
+}
+
+impl UserObject {
+    async fn is_self_or_admin(&self, ctx: &Context<'_>) -> Result<bool, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("Guard ensures claims exist");
+        if claims.sub == self.id.as_str() {
+            return Ok(true);
+        }
+        if claims.is_class_scoped() {
+            return Ok(false);
+        }
+        let user = UserRepo::find_by_id(data_loader, Uuid::parse_str(&claims.sub)?).await?;
+        Ok(matches!(user, Some(user) if user.user_type == sea_orm_active_enums::UserType::Admin))
     }
 }
 
@@ -176,6 +306,13 @@ impl From<::entity::user::Model> for UserObject {
             last_name: u.last_name,
             user_type: u.user_type.into(),
             deleted_at: u.deleted_at,
+            two_factor_enabled: u.totp_enabled,
+            two_factor_required: u.totp_required,
+            locale: u.locale,
+            bio: u.bio,
+            pronouns: u.pronouns,
+            timezone: u.timezone,
+            preferences: u.preferences.into(),
         }
     }
 }
@@ -190,9 +327,10 @@ pub struct SignupInput {
     pub last_name: String,
     #[graphql(validator(email))]
     pub email: String,
-    #[graphql(validator(min_length = 8, max_length = 100))]
+    /// Checked against the password policy when the account is created.
     pub password: String,
     pub avatar: Option<Upload>,
+    pub locale: Option<String>,
 }
 
 impl SignupInput {
@@ -209,6 +347,7 @@ impl SignupInput {
             deleted_at: Set(None),
             active: Set(false),
             user_type: Set(sea_orm_active_enums::UserType::Regular),
+            locale: Set(resolve_locale(self.locale.as_deref().unwrap_or_default()).into()),
             ..Default::default()
         }
     }
@@ -220,13 +359,407 @@ pub struct LoginInput {
     pub password: String,
 }
 
+/// Either a finished login with both tokens, or a two-factor challenge
+/// that has to be completed with `loginTwoFactor`.
+#[derive(Default)]
 pub struct LoginResult {
-    pub token: String,
+    pub token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub two_factor_token: Option<String>,
+    pub two_factor_setup_required: bool,
 }
 
 #[Object]
 impl LoginResult {
-    pub async fn token(&self) -> &str {
-        &self.token
+    pub async fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub async fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
+    pub async fn two_factor_token(&self) -> Option<&str> {
+        self.two_factor_token.as_deref()
+    }
+
+    /// The token then only allows setting up two-factor authentication, and has to be
+    /// refreshed once `confirmTwoFactor` succeeds.
+    pub async fn two_factor_setup_required(&self) -> bool {
+        self.two_factor_setup_required
+    }
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct LockedAccount {
+    pub user: UserObject,
+    pub locked_until: chrono::NaiveDateTime,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(name = "PersonalAccessToken")]
+pub struct PersonalAccessTokenObject {
+    pub id: ID,
+    pub name: String,
+    pub read_only: bool,
+    /// Classes the token is limited to, `null` when it can access every class.
+    pub class_ids: Option<Vec<ID>>,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<personal_access_token::Model> for PersonalAccessTokenObject {
+    fn from(t: personal_access_token::Model) -> Self {
+        Self {
+            id: ID::from(t.id),
+            name: t.name,
+            read_only: t.read_only,
+            class_ids: t
+                .class_ids
+                .map(|ids| ids.into_iter().map(ID::from).collect()),
+            created_at: t.created_at,
+            expires_at: t.expires_at,
+            last_used_at: t.last_used_at,
+        }
+    }
+}
+
+#[derive(InputObject, Clone, Debug)]
+pub struct CreatePersonalAccessTokenInput {
+    #[graphql(validator(min_length = 1, max_length = 50))]
+    pub name: String,
+    pub read_only: bool,
+    /// Limits the token to these classes, every class of the user when left out.
+    pub class_ids: Option<Vec<ID>>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct CreatedPersonalAccessToken {
+    /// Shown only once, only its hash is stored.
+    pub token: String,
+    pub personal_access_token: PersonalAccessTokenObject,
+}
+
+#[derive(InputObject, Clone, Debug, Default)]
+pub struct UserFilter {
+    pub user_type: Option<UserType>,
+    pub active: Option<bool>,
+    pub deleted: Option<bool>,
+    pub created_after: Option<chrono::NaiveDateTime>,
+    pub created_before: Option<chrono::NaiveDateTime>,
+    /// Matched against the username, first and last name and email.
+    pub search: Option<String>,
+}
+
+impl From<UserFilter> for Condition {
+    fn from(filter: UserFilter) -> Self {
+        let mut condition = Condition::all();
+        if let Some(user_type) = filter.user_type {
+            condition = condition
+                .add(user::Column::UserType.eq(sea_orm_active_enums::UserType::from(user_type)));
+        }
+        if let Some(active) = filter.active {
+            condition = condition.add(user::Column::Active.eq(active));
+        }
+        if let Some(deleted) = filter.deleted {
+            condition = condition.add(match deleted {
+                true => user::Column::DeletedAt.is_not_null(),
+                false => user::Column::DeletedAt.is_null(),
+            });
+        }
+        if let Some(created_after) = filter.created_after {
+            condition = condition.add(user::Column::CreatedAt.gte(created_after));
+        }
+        if let Some(created_before) = filter.created_before {
+            condition = condition.add(user::Column::CreatedAt.lt(created_before));
+        }
+        if let Some(search) = filter.search.filter(|s| !s.trim().is_empty()) {
+            let pattern = contains_pattern(&search);
+            let mut any = Condition::any();
+            for column in [
+                user::Column::Username,
+                user::Column::FirstName,
+                user::Column::LastName,
+                user::Column::Email,
+            ] {
+                any = any.add(Expr::expr(Func::lower(Expr::col(column))).like(pattern.as_str()));
+            }
+            condition = condition.add(any);
+        }
+        condition
+    }
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UserOrderField {
+    CreatedAt,
+    Username,
+    FirstName,
+    LastName,
+    Email,
+}
+
+impl From<UserOrderField> for user::Column {
+    fn from(field: UserOrderField) -> Self {
+        match field {
+            UserOrderField::CreatedAt => Self::CreatedAt,
+            UserOrderField::Username => Self::Username,
+            UserOrderField::FirstName => Self::FirstName,
+            UserOrderField::LastName => Self::LastName,
+            UserOrderField::Email => Self::Email,
+        }
+    }
+}
+
+/// Users are ordered by newest first when no order is given.
+#[derive(InputObject, Copy, Clone, Debug)]
+pub struct UserOrder {
+    pub field: UserOrderField,
+    pub direction: OrderDirection,
+}
+
+impl Default for UserOrder {
+    fn default() -> Self {
+        Self {
+            field: UserOrderField::CreatedAt,
+            direction: OrderDirection::Desc,
+        }
+    }
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(name = "Impersonation")]
+pub struct ImpersonationObject {
+    /// Used instead of the admin's own access token, there is no refresh token.
+    pub access_token: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub user: UserObject,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(complex)]
+#[graphql(name = "ImpersonationLogEntry")]
+pub struct ImpersonationLogObject {
+    pub id: ID,
+    /// Shared by every entry of one impersonation.
+    pub session_id: ID,
+    pub admin_id: ID,
+    pub user_id: ID,
+    /// `start`, `end`, `rtc` or what the token was used for: the GraphQL operation like
+    /// `query Me`, or the REST request like `GET /api/v1/meeting/{class_id}`.
+    pub action: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<impersonation_log::Model> for ImpersonationLogObject {
+    fn from(l: impersonation_log::Model) -> Self {
+        Self {
+            id: ID::from(l.id),
+            session_id: ID::from(l.session_id),
+            admin_id: ID::from(l.admin_id),
+            user_id: ID::from(l.user_id),
+            action: l.action,
+            created_at: l.created_at,
+        }
+    }
+}
+
+#[ComplexObject]
+impl ImpersonationLogObject {
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn admin(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let admin = UserRepo::find_by_id(data_loader, Uuid::parse_str(&self.admin_id)?).await?;
+        Ok(admin.map(|a| a.into()))
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, AppError> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let user = UserRepo::find_by_id(data_loader, Uuid::parse_str(&self.user_id)?).await?;
+        Ok(user.map(|u| u.into()))
+    }
+}
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl From<user_preferences::Theme> for Theme {
+    fn from(t: user_preferences::Theme) -> Self {
+        match t {
+            user_preferences::Theme::System => Self::System,
+            user_preferences::Theme::Light => Self::Light,
+            user_preferences::Theme::Dark => Self::Dark,
+        }
+    }
+}
+
+impl From<Theme> for user_preferences::Theme {
+    fn from(t: Theme) -> Self {
+        match t {
+            Theme::System => Self::System,
+            Theme::Light => Self::Light,
+            Theme::Dark => Self::Dark,
+        }
+    }
+}
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum HourCycle {
+    H12,
+    H24,
+}
+
+impl From<user_preferences::HourCycle> for HourCycle {
+    fn from(h: user_preferences::HourCycle) -> Self {
+        match h {
+            user_preferences::HourCycle::H12 => Self::H12,
+            user_preferences::HourCycle::H24 => Self::H24,
+        }
+    }
+}
+
+impl From<HourCycle> for user_preferences::HourCycle {
+    fn from(h: HourCycle) -> Self {
+        match h {
+            HourCycle::H12 => Self::H12,
+            HourCycle::H24 => Self::H24,
+        }
+    }
+}
+
+#[derive(SimpleObject, Clone, Debug, Default, Serialize, Deserialize)]
+#[graphql(name = "Preferences")]
+pub struct PreferencesObject {
+    pub notifications: NotificationPreferencesObject,
+    pub display: DisplayPreferencesObject,
+}
+
+#[derive(SimpleObject, Clone, Debug, Default, Serialize, Deserialize)]
+#[graphql(name = "NotificationPreferences")]
+pub struct NotificationPreferencesObject {
+    pub email_digest: bool,
+    pub mentions: bool,
+    pub assignment_reminders: bool,
+    pub submission_feedback: bool,
+}
+
+#[derive(SimpleObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(name = "DisplayPreferences")]
+pub struct DisplayPreferencesObject {
+    pub theme: Theme,
+    /// `null` uses the convention of the user's locale.
+    pub hour_cycle: Option<HourCycle>,
+    pub compact: bool,
+}
+
+impl Default for DisplayPreferencesObject {
+    fn default() -> Self {
+        user_preferences::DisplayPreferences::default().into()
+    }
+}
+
+impl From<user_preferences::UserPreferences> for PreferencesObject {
+    fn from(p: user_preferences::UserPreferences) -> Self {
+        Self {
+            notifications: p.notifications.into(),
+            display: p.display.into(),
+        }
+    }
+}
+
+impl From<user_preferences::NotificationPreferences> for NotificationPreferencesObject {
+    fn from(n: user_preferences::NotificationPreferences) -> Self {
+        Self {
+            email_digest: n.email_digest,
+            mentions: n.mentions,
+            assignment_reminders: n.assignment_reminders,
+            submission_feedback: n.submission_feedback,
+        }
+    }
+}
+
+impl From<user_preferences::DisplayPreferences> for DisplayPreferencesObject {
+    fn from(d: user_preferences::DisplayPreferences) -> Self {
+        Self {
+            theme: d.theme.into(),
+            hour_cycle: d.hour_cycle.map(|h| h.into()),
+            compact: d.compact,
+        }
+    }
+}
+
+/// Fields that are left out keep their current value, `null` clears the optional ones.
+#[derive(InputObject, Debug)]
+pub struct UpdatePreferencesInput {
+    #[graphql(validator(max_length = 500))]
+    pub bio: MaybeUndefined<String>,
+    #[graphql(validator(max_length = 30))]
+    pub pronouns: MaybeUndefined<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub notifications: Option<NotificationPreferencesInput>,
+    pub display: Option<DisplayPreferencesInput>,
+}
+
+#[derive(InputObject, Debug)]
+pub struct NotificationPreferencesInput {
+    pub email_digest: Option<bool>,
+    pub mentions: Option<bool>,
+    pub assignment_reminders: Option<bool>,
+    pub submission_feedback: Option<bool>,
+}
+
+#[derive(InputObject, Debug)]
+pub struct DisplayPreferencesInput {
+    pub theme: Option<Theme>,
+    pub hour_cycle: MaybeUndefined<HourCycle>,
+    pub compact: Option<bool>,
+}
+
+impl NotificationPreferencesInput {
+    pub fn apply(self, n: &mut user_preferences::NotificationPreferences) {
+        if let Some(email_digest) = self.email_digest {
+            n.email_digest = email_digest;
+        }
+        if let Some(mentions) = self.mentions {
+            n.mentions = mentions;
+        }
+        if let Some(assignment_reminders) = self.assignment_reminders {
+            n.assignment_reminders = assignment_reminders;
+        }
+        if let Some(submission_feedback) = self.submission_feedback {
+            n.submission_feedback = submission_feedback;
+        }
+    }
+}
+
+impl DisplayPreferencesInput {
+    pub fn apply(self, d: &mut user_preferences::DisplayPreferences) {
+        if let Some(theme) = self.theme {
+            d.theme = theme.into();
+        }
+        match self.hour_cycle {
+            MaybeUndefined::Value(hour_cycle) => d.hour_cycle = Some(hour_cycle.into()),
+            MaybeUndefined::Null => d.hour_cycle = None,
+            MaybeUndefined::Undefined => {}
+        }
+        if let Some(compact) = self.compact {
+            d.compact = compact;
+        }
     }
 }
@@ -1,10 +1,21 @@
-use crate::core::{AdminGuard, AppError, Claims, LoggedInGuard};
+use crate::core::lockout;
+use crate::core::pagination::{self, TotalCount};
+use crate::core::{
+    AdminGuard, AppError, Claims, LoggedInGuard, TokenScopeGuard, TwoFactorSetupGuard,
+};
 
+use async_graphql::connection::{self, Connection, Edge};
 use async_graphql::{dataloader::DataLoader, Context, Object, ID};
-use sea_orm::DatabaseConnection;
+use chrono::NaiveDateTime;
+use deadpool_redis::Pool;
+use entity::impersonation_log;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection};
 use tracing::instrument;
 use uuid::Uuid;
 
+use super::object::{
+    ImpersonationLogObject, LockedAccount, PersonalAccessTokenObject, UserFilter, UserOrder,
+};
 use super::{UserObject, UserRepo};
 
 #[derive(Default)]
@@ -13,7 +24,7 @@ pub struct UserQuery;
 #[Object]
 impl UserQuery {
     #[instrument(skip(self, ctx), err(Debug))]
-    #[graphql(guard = "LoggedInGuard")]
+    #[graphql(guard = "TwoFactorSetupGuard")]
     async fn me(&self, ctx: &Context<'_>) -> Result<UserObject, async_graphql::Error> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
@@ -24,11 +35,155 @@ impl UserQuery {
         Ok(u.into())
     }
 
+    /// Directory of all users except admins, 25 per page unless `first` or `last` is given.
     #[instrument(skip(self, ctx), err(Debug))]
     #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
-    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<UserObject>, async_graphql::Error> {
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<UserFilter>,
+        order: Option<UserOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, UserObject, TotalCount>, async_graphql::Error> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
-        let users = UserRepo::find_all(data_loader).await?;
-        Ok(users.into_iter().map(|u| u.into()).collect())
+        let condition = Condition::from(filter.unwrap_or_default());
+        let order = order.unwrap_or_default();
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total = UserRepo::count_users(data_loader, condition.clone()).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let users = UserRepo::find_users(
+                    data_loader,
+                    condition,
+                    order.field.into(),
+                    order.direction.into(),
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = users
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, u)| Edge::new(page.offset + i, UserObject::from(u)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
+    }
+
+    /// Impersonations and every request made with them, newest first.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    async fn impersonation_log(
+        &self,
+        ctx: &Context<'_>,
+        admin_id: Option<ID>,
+        user_id: Option<ID>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, ImpersonationLogObject, TotalCount>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let mut condition = Condition::all();
+        if let Some(admin_id) = admin_id {
+            condition =
+                condition.add(impersonation_log::Column::AdminId.eq(Uuid::parse_str(&admin_id)?));
+        }
+        if let Some(user_id) = user_id {
+            condition =
+                condition.add(impersonation_log::Column::UserId.eq(Uuid::parse_str(&user_id)?));
+        }
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let total =
+                    UserRepo::count_impersonation_logs(data_loader, condition.clone()).await?;
+                let page = pagination::page(total as usize, after, before, first, last);
+                let logs = UserRepo::find_impersonation_logs(
+                    data_loader,
+                    condition,
+                    page.offset as u64,
+                    page.limit as u64,
+                )
+                .await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    TotalCount { total_count: total },
+                );
+                connection.edges = logs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, l)| Edge::new(page.offset + i, ImpersonationLogObject::from(l)))
+                    .collect();
+
+                Ok::<_, AppError>(connection)
+            },
+        )
+        .await
+    }
+
+    /// Personal access tokens of the current user that were not revoked.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(TokenScopeGuard::account())")]
+    async fn personal_access_tokens(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<PersonalAccessTokenObject>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+        let id = Uuid::parse_str(&claims.as_ref().expect("Guard ensures claims exist").sub)?;
+
+        let tokens = UserRepo::find_personal_access_tokens(data_loader, id).await?;
+        Ok(tokens.into_iter().map(|t| t.into()).collect())
+    }
+
+    /// Accounts that are currently locked after too many failed logins.
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(guard = "LoggedInGuard.and(AdminGuard)")]
+    async fn locked_accounts(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<LockedAccount>, async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+        let redis_pool = ctx.data_unchecked::<Pool>();
+
+        let mut accounts = Vec::new();
+        for (user_id, locked_until) in lockout::locked_accounts(redis_pool).await? {
+            let user = match UserRepo::find_by_id(data_loader, user_id).await? {
+                Some(user) => user,
+                None => continue,
+            };
+            accounts.push(LockedAccount {
+                user: user.into(),
+                locked_until: NaiveDateTime::from_timestamp_opt(locked_until, 0)
+                    .expect("Lock expiry is a valid timestamp"),
+            });
+        }
+
+        Ok(accounts)
     }
 }
@@ -3,19 +3,27 @@ use std::collections::HashMap;
 use ::entity::membership;
 use ::entity::sea_orm_active_enums::UserType;
 use ::entity::{
-    membership::Entity as Membership, password_reset_token,
-    password_reset_token::Entity as PasswordResetToken, user, user::Entity as User,
+    activation_token, activation_token::Entity as ActivationToken, email_change_token,
+    email_change_token::Entity as EmailChangeToken, impersonation_log,
+    impersonation_log::Entity as ImpersonationLog, membership::Entity as Membership,
+    password_reset_token, password_reset_token::Entity as PasswordResetToken,
+    personal_access_token, personal_access_token::Entity as PersonalAccessToken, recovery_code,
+    recovery_code::Entity as RecoveryCode, refresh_token, refresh_token::Entity as RefreshToken,
+    user, user::Entity as User,
 };
 use async_graphql::dataloader::{DataLoader, Loader};
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use sea_orm::DatabaseConnection;
 use sea_orm::*;
 use std::sync::Arc;
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::core::{option_to_active_value, AppError};
+use crate::core::{
+    option_to_active_value, totp, unique_violation, AppError, UserError,
+    ACTIVATION_TOKEN_TTL_HOURS, EMAIL_CHANGE_TOKEN_TTL_HOURS, PASSWORD_RESET_TOKEN_TTL_MINUTES,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
 struct UsersByClassId(Uuid);
@@ -84,8 +92,13 @@ impl Loader<UserByAuthorId> for DatabaseConnection {
 pub trait UserRepo {
     async fn find_by_username(&self, username: String) -> Result<Option<user::Model>, DbErr>;
     async fn find_by_id(&self, id: Uuid) -> Result<Option<user::Model>, DbErr>;
-    async fn create_user(&self, si: user::ActiveModel) -> Result<Uuid, DbErr>;
+    async fn find_by_email(&self, email: String) -> Result<Option<user::Model>, DbErr>;
+    async fn create_user(&self, si: user::ActiveModel) -> Result<Uuid, AppError>;
     async fn activate_user(&self, id: Uuid) -> Result<(), DbErr>;
+    async fn create_activation_token(&self, user_id: Uuid, token_hash: String)
+        -> Result<(), DbErr>;
+    async fn activate_by_token(&self, token_hash: String) -> Result<user::Model, AppError>;
+    async fn delete_unactivated_users(&self, created_before: NaiveDateTime) -> Result<u64, DbErr>;
 
     async fn find_by_class_id(
         &self,
@@ -106,8 +119,33 @@ pub trait UserRepo {
         old_password: String,
         new_password: String,
     ) -> Result<user::Model, AppError>;
+    async fn update_preferences(
+        &self,
+        user_id: Uuid,
+        model: user::ActiveModel,
+    ) -> Result<user::Model, DbErr>;
+    async fn change_username(
+        &self,
+        user_id: Uuid,
+        username: String,
+    ) -> Result<user::Model, AppError>;
+    async fn create_email_change_token(
+        &self,
+        user_id: Uuid,
+        new_email: String,
+        token_hash: String,
+    ) -> Result<(), DbErr>;
+    async fn confirm_email_change(&self, token_hash: String) -> Result<user::Model, AppError>;
 
-    async fn find_all(&self) -> Result<Vec<user::Model>, DbErr>;
+    async fn count_users(&self, filter: Condition) -> Result<u64, DbErr>;
+    async fn find_users(
+        &self,
+        filter: Condition,
+        order_by: user::Column,
+        order: Order,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<user::Model>, DbErr>;
     async fn admin_user_update(
         &self,
         user_id: Uuid,
@@ -117,15 +155,92 @@ pub trait UserRepo {
 
     async fn emergency_change_password(
         &self,
-        token: Uuid,
+        token_hash: String,
         password: String,
     ) -> Result<user::Model, AppError>;
 
-    async fn find_user_by_reset_token(&self, token: Uuid) -> Result<Option<user::Model>, DbErr>;
+    async fn find_user_by_reset_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr>;
     async fn create_password_change_token(
         &self,
         user_email: String,
-    ) -> Result<(Uuid, user::Model), AppError>;
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr>;
+
+    async fn create_refresh_token(
+        &self,
+        model: refresh_token::ActiveModel,
+    ) -> Result<refresh_token::Model, DbErr>;
+    async fn find_refresh_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<refresh_token::Model>, DbErr>;
+    async fn rotate_refresh_token(
+        &self,
+        old_token_id: Uuid,
+        model: refresh_token::ActiveModel,
+    ) -> Result<Option<refresh_token::Model>, TransactionError<DbErr>>;
+    async fn revoke_session(&self, session_id: Uuid) -> Result<(), DbErr>;
+    async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<Vec<Uuid>, DbErr>;
+    async fn create_personal_access_token(
+        &self,
+        model: personal_access_token::ActiveModel,
+    ) -> Result<personal_access_token::Model, DbErr>;
+    async fn find_personal_access_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<personal_access_token::Model>, DbErr>;
+    async fn find_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<personal_access_token::Model>, DbErr>;
+    async fn touch_personal_access_token(
+        &self,
+        token_id: Uuid,
+        used_at: NaiveDateTime,
+    ) -> Result<(), DbErr>;
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, DbErr>;
+    async fn revoke_personal_access_tokens(&self, user_id: Uuid) -> Result<u64, DbErr>;
+    async fn create_impersonation_log(
+        &self,
+        model: impersonation_log::ActiveModel,
+    ) -> Result<impersonation_log::Model, DbErr>;
+    async fn count_impersonation_logs(&self, filter: Condition) -> Result<u64, DbErr>;
+    async fn find_impersonation_logs(
+        &self,
+        filter: Condition,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<impersonation_log::Model>, DbErr>;
+    async fn set_totp_secret(&self, user_id: Uuid, secret: String) -> Result<(), DbErr>;
+    async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), TransactionError<DbErr>>;
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), TransactionError<DbErr>>;
+    async fn claim_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, DbErr>;
+    async fn find_plaintext_totp_secrets(&self) -> Result<Vec<(Uuid, String)>, DbErr>;
+    async fn replace_totp_secret(
+        &self,
+        user_id: Uuid,
+        old_secret: String,
+        new_secret: String,
+    ) -> Result<bool, DbErr>;
+    async fn set_totp_required(&self, user_id: Uuid, required: bool) -> Result<(), AppError>;
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), TransactionError<DbErr>>;
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: String) -> Result<bool, DbErr>;
 }
 
 #[async_trait]
@@ -149,12 +264,25 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         Ok(user)
     }
 
+    /// Emails are matched case-insensitively, like the providers handing them out do.
+    #[instrument(skip(self), err(Debug))]
+    async fn find_by_email(&self, email: String) -> Result<Option<user::Model>, DbErr> {
+        let user = User::find()
+            .filter(email_eq(&email))
+            .one(self.loader())
+            .await?;
+        Ok(user)
+    }
+
     #[instrument(skip(self), err(Debug))]
-    async fn create_user(&self, mut si: user::ActiveModel) -> Result<Uuid, DbErr> {
+    async fn create_user(&self, mut si: user::ActiveModel) -> Result<Uuid, AppError> {
         if cfg!(debug_assertions) {
             si.active = Set(true);
         }
-        let u = User::insert(si).exec(self.loader()).await?;
+        let u = User::insert(si.clone())
+            .exec(self.loader())
+            .await
+            .map_err(|err| already_taken(err, &si))?;
         Ok(u.last_insert_id)
     }
 
@@ -171,6 +299,130 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         Ok(())
     }
 
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn create_activation_token(
+        &self,
+        user_id: Uuid,
+        token_hash: String,
+    ) -> Result<(), DbErr> {
+        // only the most recently sent link stays valid
+        ActivationToken::delete_many()
+            .filter(activation_token::Column::UserId.eq(user_id))
+            .exec(self.loader())
+            .await?;
+
+        activation_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            token_hash: Set(token_hash),
+            user_id: Set(user_id),
+            expires_at: Set(
+                Utc::now().naive_utc() + chrono::Duration::hours(ACTIVATION_TOKEN_TTL_HOURS)
+            ),
+        }
+        .insert(self.loader())
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn activate_by_token(&self, token_hash: String) -> Result<user::Model, AppError> {
+        let token = ActivationToken::find()
+            .filter(activation_token::Column::TokenHash.eq(token_hash))
+            .one(self.loader())
+            .await?;
+
+        let token = match token {
+            Some(token) => token,
+            None => return Err(AppError::auth("Invalid activation link")),
+        };
+
+        if token.expires_at < Utc::now().naive_utc() {
+            return Err(AppError::auth("Activation link expired"));
+        }
+
+        // deleting first makes sure a link can't be used twice, even by concurrent requests
+        let res = ActivationToken::delete_by_id(token.id)
+            .exec(self.loader())
+            .await?;
+        if res.rows_affected != 1 {
+            return Err(AppError::auth("Invalid activation link"));
+        }
+
+        // accounts deleted in the meantime stay inactive
+        let res = User::update_many()
+            .col_expr(user::Column::Active, sea_query::Expr::value(true))
+            .filter(user::Column::Id.eq(token.user_id))
+            .filter(user::Column::DeletedAt.is_null())
+            .exec(self.loader())
+            .await?;
+        if res.rows_affected != 1 {
+            return Err(AppError::auth("Invalid activation link"));
+        }
+
+        let user = User::find_by_id(token.user_id)
+            .one(self.loader())
+            .await?
+            .ok_or_else(|| AppError::auth("Invalid activation link"))?;
+
+        Ok(user)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_unactivated_users(&self, created_before: NaiveDateTime) -> Result<u64, DbErr> {
+        ActivationToken::delete_many()
+            .filter(activation_token::Column::ExpiresAt.lt(Utc::now().naive_utc()))
+            .exec(self.loader())
+            .await?;
+
+        let condition = Condition::all()
+            .add(user::Column::Active.eq(false))
+            .add(user::Column::DeletedAt.is_null())
+            .add(user::Column::CreatedAt.lt(created_before));
+        let user_ids = User::find()
+            .select_only()
+            .column(user::Column::Id)
+            .filter(condition)
+            .into_tuple::<Uuid>()
+            .all(self.loader())
+            .await?;
+
+        // every account on its own, one that is still referenced must not keep the rest around
+        let mut deleted = 0;
+        for user_id in user_ids {
+            let res = self
+                .loader()
+                .transaction::<_, u64, DbErr>(|txn| {
+                    Box::pin(async move {
+                        ActivationToken::delete_many()
+                            .filter(activation_token::Column::UserId.eq(user_id))
+                            .exec(txn)
+                            .await?;
+                        PasswordResetToken::delete_many()
+                            .filter(password_reset_token::Column::UserId.eq(user_id))
+                            .exec(txn)
+                            .await?;
+                        let res = User::delete_many()
+                            .filter(user::Column::Id.eq(user_id))
+                            .filter(user::Column::Active.eq(false))
+                            .exec(txn)
+                            .await?;
+
+                        Ok(res.rows_affected)
+                    })
+                })
+                .await;
+            match res {
+                Ok(rows) => deleted += rows,
+                Err(err) => {
+                    tracing::warn!(%user_id, "Failed to remove unactivated account: {err}")
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
     #[instrument(skip(self), err(Debug))]
     async fn find_by_class_id(
         &self,
@@ -222,6 +474,102 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         Ok(user)
     }
 
+    #[instrument(skip(self), err(Debug))]
+    async fn update_preferences(
+        &self,
+        user_id: Uuid,
+        mut model: user::ActiveModel,
+    ) -> Result<user::Model, DbErr> {
+        model.id = Set(user_id);
+        let user = User::update(model).exec(self.loader()).await?;
+
+        Ok(user)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn change_username(
+        &self,
+        user_id: Uuid,
+        username: String,
+    ) -> Result<user::Model, AppError> {
+        let model = user::ActiveModel {
+            id: Set(user_id),
+            username: Set(username),
+            ..Default::default()
+        };
+        let user = User::update(model.clone())
+            .exec(self.loader())
+            .await
+            .map_err(|err| already_taken(err, &model))?;
+
+        Ok(user)
+    }
+
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn create_email_change_token(
+        &self,
+        user_id: Uuid,
+        new_email: String,
+        token_hash: String,
+    ) -> Result<(), DbErr> {
+        // only the most recently requested change can be confirmed
+        EmailChangeToken::delete_many()
+            .filter(email_change_token::Column::UserId.eq(user_id))
+            .exec(self.loader())
+            .await?;
+
+        email_change_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            token_hash: Set(token_hash),
+            user_id: Set(user_id),
+            new_email: Set(new_email),
+            expires_at: Set(
+                Utc::now().naive_utc() + chrono::Duration::hours(EMAIL_CHANGE_TOKEN_TTL_HOURS)
+            ),
+        }
+        .insert(self.loader())
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn confirm_email_change(&self, token_hash: String) -> Result<user::Model, AppError> {
+        let token = EmailChangeToken::find()
+            .filter(email_change_token::Column::TokenHash.eq(token_hash))
+            .one(self.loader())
+            .await?;
+
+        let token = match token {
+            Some(token) => token,
+            None => return Err(AppError::auth("Invalid confirmation link")),
+        };
+
+        if token.expires_at < Utc::now().naive_utc() {
+            return Err(AppError::auth("Confirmation link expired"));
+        }
+
+        let res = EmailChangeToken::delete_by_id(token.id)
+            .exec(self.loader())
+            .await?;
+        if res.rows_affected != 1 {
+            return Err(AppError::auth("Invalid confirmation link"));
+        }
+
+        // the address may have been claimed by someone else since the change was requested
+        let model = user::ActiveModel {
+            id: Set(token.user_id),
+            email: Set(token.new_email),
+            ..Default::default()
+        };
+        let user = User::update(model.clone())
+            .exec(self.loader())
+            .await
+            .map_err(|err| already_taken(err, &model))?;
+
+        Ok(user)
+    }
+
     #[instrument(skip(self), err(Debug))]
     async fn change_password(
         &self,
@@ -264,9 +612,32 @@ impl UserRepo for DataLoader<DatabaseConnection> {
     }
 
     #[instrument(skip(self), err(Debug))]
-    async fn find_all(&self) -> Result<Vec<user::Model>, DbErr> {
+    async fn count_users(&self, filter: Condition) -> Result<u64, DbErr> {
+        let count = User::find()
+            .filter(user::Column::UserType.ne(UserType::Admin))
+            .filter(filter)
+            .count(self.loader())
+            .await?;
+        Ok(count)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_users(
+        &self,
+        filter: Condition,
+        order_by: user::Column,
+        order: Order,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<user::Model>, DbErr> {
+        // Ties are broken by id so that offsets stay stable between pages
         let users = User::find()
             .filter(user::Column::UserType.ne(UserType::Admin))
+            .filter(filter)
+            .order_by(order_by, order)
+            .order_by_asc(user::Column::Id)
+            .offset(offset)
+            .limit(limit)
             .all(self.loader())
             .await?;
         Ok(users)
@@ -308,31 +679,36 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         Ok(user)
     }
 
-    #[instrument(skip(self), err(Debug))]
+    #[instrument(skip(self, token_hash, password), err(Debug))]
     async fn emergency_change_password(
         &self,
-        token: Uuid,
+        token_hash: String,
         password: String,
     ) -> Result<user::Model, AppError> {
-        let user = PasswordResetToken::find()
-            .filter(password_reset_token::Column::Id.eq(token))
+        let res = PasswordResetToken::find()
+            .filter(password_reset_token::Column::TokenHash.eq(token_hash))
             .find_also_related(User)
             .one(self.loader())
             .await?
-            .map(|(_, u)| u.expect("PasswordResetToken to User is not optional"));
+            .map(|(t, u)| (t, u.expect("PasswordResetToken to User is not optional")));
 
-        let user = match user {
-            Some(user) => user,
-            None => {
-                return Err(AppError::not_found(
-                    "user not found",
-                    "user",
-                    "id",
-                    &token.to_string(),
-                ));
-            }
+        let (token, user) = match res {
+            Some(res) => res,
+            None => return Err(AppError::auth("Invalid password reset link")),
         };
 
+        // removing the token before changing the password keeps it single use
+        let deleted = PasswordResetToken::delete_by_id(token.id)
+            .exec(self.loader())
+            .await?;
+        if deleted.rows_affected != 1 {
+            return Err(AppError::auth("Password reset link was already used"));
+        }
+
+        if token.expires_at < Utc::now().naive_utc() {
+            return Err(AppError::auth("Password reset link expired"));
+        }
+
         let hash = argon2_async::hash(password).await?;
 
         let user_update = user::ActiveModel {
@@ -342,17 +718,21 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         };
 
         let user = User::update(user_update).exec(self.loader()).await?;
-        PasswordResetToken::delete_by_id(token)
-            .exec(self.loader())
-            .await?;
 
         Ok(user)
     }
 
-    #[instrument(skip(self), err(Debug))]
-    async fn find_user_by_reset_token(&self, token: Uuid) -> Result<Option<user::Model>, DbErr> {
+    #[instrument(skip(self, token_hash), err(Debug))]
+    async fn find_user_by_reset_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr> {
+        let condition = Condition::all()
+            .add(password_reset_token::Column::TokenHash.eq(token_hash))
+            .add(password_reset_token::Column::ExpiresAt.gt(Utc::now().naive_utc()));
+
         let user = PasswordResetToken::find()
-            .filter(password_reset_token::Column::Id.eq(token))
+            .filter(condition)
             .find_also_related(User)
             .one(self.loader())
             .await?
@@ -361,38 +741,606 @@ impl UserRepo for DataLoader<DatabaseConnection> {
         Ok(user)
     }
 
-    #[instrument(skip(self), err(Debug))]
+    /// Only the hash of the token is stored, like for activation links.
+    #[instrument(skip(self, token_hash), err(Debug))]
     async fn create_password_change_token(
         &self,
         user_email: String,
-    ) -> Result<(Uuid, user::Model), AppError> {
-        let user = User::find()
-            .filter(user::Column::Email.eq(user_email.clone()))
-            .one(self.loader())
-            .await?;
+        token_hash: String,
+    ) -> Result<Option<user::Model>, DbErr> {
+        let condition = Condition::all()
+            .add(email_eq(&user_email))
+            .add(user::Column::DeletedAt.is_null());
+
+        let user = User::find().filter(condition).one(self.loader()).await?;
 
         let user = match user {
             Some(user) => user,
-            None => {
-                return Err(AppError::not_found(
-                    "user not found".to_string(),
-                    "user",
-                    "email",
-                    user_email,
-                ));
-            }
+            None => return Ok(None),
         };
 
+        // a newer link replaces all previous ones
+        PasswordResetToken::delete_many()
+            .filter(password_reset_token::Column::UserId.eq(user.id))
+            .exec(self.loader())
+            .await?;
+
+        let now = Utc::now().naive_utc();
         let active_token = password_reset_token::ActiveModel {
             id: Set(Uuid::new_v4()),
             user_id: Set(user.id),
-            ..Default::default()
+            created_at: Set(now),
+            expires_at: Set(now + chrono::Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES)),
+            token_hash: Set(token_hash),
         };
-        let token_id = PasswordResetToken::insert(active_token)
+        PasswordResetToken::insert(active_token)
             .exec(self.loader())
-            .await?
-            .last_insert_id;
+            .await?;
+
+        Ok(Some(user))
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn create_refresh_token(
+        &self,
+        model: refresh_token::ActiveModel,
+    ) -> Result<refresh_token::Model, DbErr> {
+        let token = model.insert(self.loader()).await?;
+        Ok(token)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_refresh_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<refresh_token::Model>, DbErr> {
+        let token = RefreshToken::find()
+            .filter(refresh_token::Column::TokenHash.eq(token_hash))
+            .one(self.loader())
+            .await?;
+        Ok(token)
+    }
+
+    /// Returns `None` without creating the new token if the old one was already revoked,
+    /// for example by a concurrent refresh with the same token.
+    #[instrument(skip(self), err(Debug))]
+    async fn rotate_refresh_token(
+        &self,
+        old_token_id: Uuid,
+        model: refresh_token::ActiveModel,
+    ) -> Result<Option<refresh_token::Model>, TransactionError<DbErr>> {
+        let token = self
+            .loader()
+            .transaction::<_, Option<refresh_token::Model>, DbErr>(|txn| {
+                Box::pin(async move {
+                    let res = RefreshToken::update_many()
+                        .col_expr(
+                            refresh_token::Column::RevokedAt,
+                            sea_query::Expr::value(Utc::now().naive_utc()),
+                        )
+                        .filter(refresh_token::Column::Id.eq(old_token_id))
+                        .filter(refresh_token::Column::RevokedAt.is_null())
+                        .exec(txn)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Ok(None);
+                    }
+
+                    let token = model.insert(txn).await?;
+                    Ok(Some(token))
+                })
+            })
+            .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn revoke_session(&self, session_id: Uuid) -> Result<(), DbErr> {
+        let condition = Condition::all()
+            .add(refresh_token::Column::SessionId.eq(session_id))
+            .add(refresh_token::Column::RevokedAt.is_null());
+
+        RefreshToken::update_many()
+            .col_expr(
+                refresh_token::Column::RevokedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(condition)
+            .exec(self.loader())
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<Vec<Uuid>, DbErr> {
+        let condition = Condition::all()
+            .add(refresh_token::Column::UserId.eq(user_id))
+            .add(refresh_token::Column::RevokedAt.is_null());
+
+        let tokens = RefreshToken::find()
+            .filter(condition.clone())
+            .all(self.loader())
+            .await?;
+
+        RefreshToken::update_many()
+            .col_expr(
+                refresh_token::Column::RevokedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(condition)
+            .exec(self.loader())
+            .await?;
+
+        let mut session_ids = tokens.into_iter().map(|t| t.session_id).collect::<Vec<_>>();
+        session_ids.sort();
+        session_ids.dedup();
+
+        Ok(session_ids)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn create_personal_access_token(
+        &self,
+        model: personal_access_token::ActiveModel,
+    ) -> Result<personal_access_token::Model, DbErr> {
+        let token = model.insert(self.loader()).await?;
+        Ok(token)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_personal_access_token(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<personal_access_token::Model>, DbErr> {
+        let token = PersonalAccessToken::find()
+            .filter(personal_access_token::Column::TokenHash.eq(token_hash))
+            .one(self.loader())
+            .await?;
+        Ok(token)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<personal_access_token::Model>, DbErr> {
+        let condition = Condition::all()
+            .add(personal_access_token::Column::UserId.eq(user_id))
+            .add(personal_access_token::Column::RevokedAt.is_null());
+
+        let tokens = PersonalAccessToken::find()
+            .filter(condition)
+            .order_by_desc(personal_access_token::Column::CreatedAt)
+            .all(self.loader())
+            .await?;
+        Ok(tokens)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn touch_personal_access_token(
+        &self,
+        token_id: Uuid,
+        used_at: NaiveDateTime,
+    ) -> Result<(), DbErr> {
+        PersonalAccessToken::update(personal_access_token::ActiveModel {
+            id: Set(token_id),
+            last_used_at: Set(Some(used_at)),
+            ..Default::default()
+        })
+        .exec(self.loader())
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, DbErr> {
+        let condition = Condition::all()
+            .add(personal_access_token::Column::Id.eq(token_id))
+            .add(personal_access_token::Column::UserId.eq(user_id))
+            .add(personal_access_token::Column::RevokedAt.is_null());
+
+        let res = PersonalAccessToken::update_many()
+            .col_expr(
+                personal_access_token::Column::RevokedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(condition)
+            .exec(self.loader())
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Revokes every token of the user, returning how many were still active.
+    #[instrument(skip(self), err(Debug))]
+    async fn revoke_personal_access_tokens(&self, user_id: Uuid) -> Result<u64, DbErr> {
+        let condition = Condition::all()
+            .add(personal_access_token::Column::UserId.eq(user_id))
+            .add(personal_access_token::Column::RevokedAt.is_null());
+
+        let res = PersonalAccessToken::update_many()
+            .col_expr(
+                personal_access_token::Column::RevokedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(condition)
+            .exec(self.loader())
+            .await?;
+
+        Ok(res.rows_affected)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn create_impersonation_log(
+        &self,
+        model: impersonation_log::ActiveModel,
+    ) -> Result<impersonation_log::Model, DbErr> {
+        let log = model.insert(self.loader()).await?;
+        Ok(log)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn count_impersonation_logs(&self, filter: Condition) -> Result<u64, DbErr> {
+        let count = ImpersonationLog::find()
+            .filter(filter)
+            .count(self.loader())
+            .await?;
+        Ok(count)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn find_impersonation_logs(
+        &self,
+        filter: Condition,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<impersonation_log::Model>, DbErr> {
+        let logs = ImpersonationLog::find()
+            .filter(filter)
+            .order_by_desc(impersonation_log::Column::CreatedAt)
+            .order_by_asc(impersonation_log::Column::Id)
+            .offset(offset)
+            .limit(limit)
+            .all(self.loader())
+            .await?;
+        Ok(logs)
+    }
+
+    #[instrument(skip(self, secret), err(Debug))]
+    async fn set_totp_secret(&self, user_id: Uuid, secret: String) -> Result<(), DbErr> {
+        User::update(user::ActiveModel {
+            id: Set(user_id),
+            totp_secret: Set(Some(secret)),
+            totp_enabled: Set(false),
+            totp_last_step: Set(None),
+            ..Default::default()
+        })
+        .exec(self.loader())
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, recovery_code_hashes), err(Debug))]
+    async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    User::update(user::ActiveModel {
+                        id: Set(user_id),
+                        totp_enabled: Set(true),
+                        totp_last_step: Set(Some(step)),
+                        ..Default::default()
+                    })
+                    .exec(txn)
+                    .await?;
+
+                    insert_recovery_codes(txn, user_id, recovery_code_hashes).await
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    User::update(user::ActiveModel {
+                        id: Set(user_id),
+                        totp_secret: Set(None),
+                        totp_enabled: Set(false),
+                        totp_last_step: Set(None),
+                        ..Default::default()
+                    })
+                    .exec(txn)
+                    .await?;
+
+                    RecoveryCode::delete_many()
+                        .filter(recovery_code::Column::UserId.eq(user_id))
+                        .exec(txn)
+                        .await?;
+
+                    Ok(())
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records `step` as used, unless it or a later step already was.
+    #[instrument(skip(self), err(Debug))]
+    async fn claim_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, DbErr> {
+        let res = User::update_many()
+            .col_expr(user::Column::TotpLastStep, sea_query::Expr::value(step))
+            .filter(user::Column::Id.eq(user_id))
+            .filter(
+                Condition::any()
+                    .add(user::Column::TotpLastStep.is_null())
+                    .add(user::Column::TotpLastStep.lt(step)),
+            )
+            .exec(self.loader())
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Secrets stored before they were encrypted.
+    #[instrument(skip(self), err(Debug))]
+    async fn find_plaintext_totp_secrets(&self) -> Result<Vec<(Uuid, String)>, DbErr> {
+        let users = User::find()
+            .filter(user::Column::TotpSecret.is_not_null())
+            .all(self.loader())
+            .await?;
+
+        Ok(users
+            .into_iter()
+            .filter_map(|u| Some((u.id, u.totp_secret?)))
+            .filter(|(_, secret)| !totp::is_encrypted(secret))
+            .collect())
+    }
+
+    /// Only replaces the secret if it is still `old_secret`, so a new setup isn't overwritten.
+    #[instrument(skip(self, old_secret, new_secret), err(Debug))]
+    async fn replace_totp_secret(
+        &self,
+        user_id: Uuid,
+        old_secret: String,
+        new_secret: String,
+    ) -> Result<bool, DbErr> {
+        let res = User::update_many()
+            .col_expr(user::Column::TotpSecret, sea_query::Expr::value(new_secret))
+            .filter(user::Column::Id.eq(user_id))
+            .filter(user::Column::TotpSecret.eq(old_secret))
+            .exec(self.loader())
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn set_totp_required(&self, user_id: Uuid, required: bool) -> Result<(), AppError> {
+        let user = User::find_by_id(user_id).one(self.loader()).await?;
+        if user.is_none() {
+            return Err(AppError::not_found(
+                "user not found",
+                "user",
+                "id",
+                &user_id.to_string(),
+            ));
+        }
+
+        User::update(user::ActiveModel {
+            id: Set(user_id),
+            totp_required: Set(required),
+            ..Default::default()
+        })
+        .exec(self.loader())
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, recovery_code_hashes), err(Debug))]
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), TransactionError<DbErr>> {
+        self.loader()
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(
+                    async move { insert_recovery_codes(txn, user_id, recovery_code_hashes).await },
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, code_hash), err(Debug))]
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: String) -> Result<bool, DbErr> {
+        let condition = Condition::all()
+            .add(recovery_code::Column::UserId.eq(user_id))
+            .add(recovery_code::Column::CodeHash.eq(code_hash))
+            .add(recovery_code::Column::UsedAt.is_null());
+
+        let res = RecoveryCode::update_many()
+            .col_expr(
+                recovery_code::Column::UsedAt,
+                sea_query::Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(condition)
+            .exec(self.loader())
+            .await?;
+
+        Ok(res.rows_affected == 1)
+    }
+}
+
+/// Matches `email` regardless of case, like the unique index on `lower(email)`.
+fn email_eq(email: &str) -> sea_query::SimpleExpr {
+    sea_query::Expr::expr(sea_query::Func::lower(sea_query::Expr::col(
+        user::Column::Email,
+    )))
+    .eq(email.to_lowercase())
+}
+
+/// Reports a violated unique constraint on `user` as a taken username or email.
+fn already_taken(err: DbErr, model: &user::ActiveModel) -> AppError {
+    let (message, parameter, value) = match unique_violation(&err).as_deref() {
+        Some("user_username_key") => ("Username is already taken", "username", &model.username),
+        Some("user_email_key" | "user_email_lower_key") => {
+            ("Email is already taken", "email", &model.email)
+        }
+        _ => return err.into(),
+    };
+
+    AppError::user(
+        message,
+        UserError::AlreadyTaken {
+            parameter,
+            given_value: value.as_ref().clone(),
+        },
+    )
+}
+
+/// Drops the previous recovery codes of the user, used or not, and stores the new ones.
+async fn insert_recovery_codes(
+    txn: &DatabaseTransaction,
+    user_id: Uuid,
+    recovery_code_hashes: Vec<String>,
+) -> Result<(), DbErr> {
+    RecoveryCode::delete_many()
+        .filter(recovery_code::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+
+    let codes = recovery_code_hashes
+        .into_iter()
+        .map(|code_hash| recovery_code::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            code_hash: Set(code_hash),
+            user_id: Set(user_id),
+            used_at: Set(None),
+        });
+    RecoveryCode::insert_many(codes).exec(txn).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{connect, user_model};
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn emails_differing_in_case_are_taken() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+
+        let first = user_model();
+        let email = first.email.to_uppercase();
+        UserRepo::create_user(
+            &data_loader,
+            user::Model {
+                email: email.clone(),
+                ..first
+            }
+            .into_active_model(),
+        )
+        .await
+        .unwrap();
+
+        let res = UserRepo::create_user(
+            &data_loader,
+            user::Model {
+                email: email.to_lowercase(),
+                ..user_model()
+            }
+            .into_active_model(),
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(err) if err.to_string().ends_with("of parameter `email` is already taken")
+        ));
+        let found = UserRepo::find_by_email(&data_loader, email.to_lowercase())
+            .await
+            .unwrap();
+        assert_eq!(found.map(|u| u.email), Some(email));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn referenced_unactivated_users_are_skipped() {
+        let conn = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        let created_at = Utc::now().naive_utc() - chrono::Duration::days(30);
+        let mut users = Vec::new();
+        for _ in 0..2 {
+            let user = user::Model {
+                active: false,
+                created_at,
+                ..user_model()
+            }
+            .into_active_model()
+            .insert(&conn)
+            .await
+            .unwrap();
+            users.push(user);
+        }
+        let admin = user::Model {
+            user_type: UserType::Admin,
+            ..user_model()
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        // audit records are kept, so the account they point to can't go
+        impersonation_log::Model {
+            id: Uuid::new_v4(),
+            session_id: Uuid::new_v4(),
+            admin_id: admin.id,
+            user_id: users[0].id,
+            action: "start".into(),
+            created_at,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
 
-        Ok((token_id, user))
+        UserRepo::delete_unactivated_users(&data_loader, created_at + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert!(User::find_by_id(users[0].id)
+            .one(&conn)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(User::find_by_id(users[1].id)
+            .one(&conn)
+            .await
+            .unwrap()
+            .is_none());
     }
 }
@@ -1,23 +1,485 @@
 use async_graphql::dataloader::DataLoader;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::headers::Cookie;
+use axum::http::header::SET_COOKIE;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::TypedHeader;
+use deadpool_redis::Pool;
+use entity::sea_orm_active_enums::UserType;
 use sea_orm::DatabaseConnection;
+use serde::Deserialize;
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::core::AppError;
+use crate::core::email_templates::{self, EmailTemplate, DEFAULT_LOCALE};
+use crate::core::lockout;
+use crate::core::oidc::{
+    clear_state_cookie, state_cookie, store_authorization_request, take_authorization_request,
+    AuthorizationRequest, OidcClient, STATE_COOKIE,
+};
+use crate::core::{
+    create_two_factor_challenge, generate_opaque_token, hash_opaque_token, AppError, Claims,
+    UserError,
+};
+use crate::HOST_URL;
 
+use super::mutation::{end_user_sessions, register_oidc_user, start_session};
 use super::UserRepo;
 
 pub struct UserRest;
 
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackParams {
+    code: Option<String>,
+    state: String,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailPreviewParams {
+    locale: Option<String>,
+    #[serde(default)]
+    text: bool,
+}
+
 impl UserRest {
-    #[instrument(skip(conn), err(Debug))]
+    #[instrument(skip(token, conn), err(Debug))]
     pub async fn activate(
-        Path(user_id): Path<Uuid>,
+        Path(token): Path<String>,
         State(conn): State<DatabaseConnection>,
     ) -> Result<(), AppError> {
         let data_loader = DataLoader::new(conn, tokio::spawn);
-        UserRepo::activate_user(&data_loader, user_id).await?;
+        UserRepo::activate_by_token(&data_loader, hash_opaque_token(&token)).await?;
         Ok(())
     }
+
+    /// Switches the account to the address the confirmation link was sent to. Everything
+    /// signed in with the account has to sign in again, as the old address could be used
+    /// to take it over.
+    #[instrument(skip(token, conn, redis_pool), err(Debug))]
+    pub async fn confirm_email(
+        Path(token): Path<String>,
+        State(conn): State<DatabaseConnection>,
+        State(redis_pool): State<Pool>,
+    ) -> Result<(), AppError> {
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+        let user = UserRepo::confirm_email_change(&data_loader, hash_opaque_token(&token)).await?;
+        end_user_sessions(&data_loader, &redis_pool, user.id).await?;
+        UserRepo::revoke_personal_access_tokens(&data_loader, user.id).await?;
+        Ok(())
+    }
+
+    /// Lifts a login lockout with the token from the unlock email.
+    #[instrument(skip(token, conn, redis_pool), err(Debug))]
+    pub async fn unlock(
+        Path(token): Path<String>,
+        State(conn): State<DatabaseConnection>,
+        State(redis_pool): State<Pool>,
+    ) -> Result<(), AppError> {
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+        let user_id = lockout::take_unlock_token(&redis_pool, &token).await?;
+        let user = match UserRepo::find_by_id(&data_loader, user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::auth("Unknown or expired unlock token")),
+        };
+        lockout::unlock_account(&redis_pool, user.id, &user.username).await?;
+        tracing::info!(
+            target: "backend::security",
+            event = "account_unlocked",
+            user_id = %user.id,
+            by = "unlock_email",
+        );
+
+        Ok(())
+    }
+
+    /// Starts the authorization code flow with PKCE by redirecting to the identity provider.
+    #[instrument(skip(redis_pool), err(Debug))]
+    pub async fn oidc_login(State(redis_pool): State<Pool>) -> Result<impl IntoResponse, AppError> {
+        let client = OidcClient::discover().await?;
+        let state = generate_opaque_token();
+        let request = AuthorizationRequest::generate();
+        store_authorization_request(&redis_pool, &state, &request).await?;
+
+        Ok((
+            [(SET_COOKIE, state_cookie(&state))],
+            Redirect::to(&client.authorization_url(&state, &request)),
+        ))
+    }
+
+    /// Finishes the login and hands the tokens to the frontend in the url fragment,
+    /// so they never reach server logs.
+    #[instrument(skip(params, cookies, conn, redis_pool), err(Debug))]
+    pub async fn oidc_callback(
+        Query(params): Query<OidcCallbackParams>,
+        cookies: Option<TypedHeader<Cookie>>,
+        State(conn): State<DatabaseConnection>,
+        State(redis_pool): State<Pool>,
+    ) -> Result<impl IntoResponse, AppError> {
+        // a state that didn't start in this browser means someone else's login is being
+        // finished here
+        let cookie_state = cookies.as_ref().and_then(|c| c.get(STATE_COOKIE));
+        if cookie_state != Some(params.state.as_str()) {
+            return Err(AppError::auth(
+                "Login state does not belong to this browser",
+            ));
+        }
+        let request = take_authorization_request(&redis_pool, &params.state).await?;
+        let code = match (params.code, params.error) {
+            (Some(code), None) => code,
+            (_, error) => {
+                return Err(AppError::auth(format!(
+                    "Identity provider refused the login: {}",
+                    error.unwrap_or_default()
+                )))
+            }
+        };
+
+        let client = OidcClient::discover().await?;
+        let claims = client.exchange_code(&code, &request).await?;
+        let email = match &claims.email {
+            Some(email) if claims.email_verified => email.clone(),
+            _ => {
+                return Err(AppError::auth(
+                    "Identity provider did not return a verified email",
+                ))
+            }
+        };
+
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+        let user = match UserRepo::find_by_email(&data_loader, email.clone()).await? {
+            Some(user) => user,
+            None => {
+                let id = register_oidc_user(&data_loader, &claims, email).await?;
+                UserRepo::find_by_id(&data_loader, id)
+                    .await?
+                    .expect("User was just created")
+            }
+        };
+
+        if user.deleted_at.is_some() {
+            return Err(AppError::auth("User is deleted"));
+        }
+        // a verified email is exactly what the activation link proves
+        if !user.active {
+            UserRepo::activate_user(&data_loader, user.id).await?;
+        }
+
+        let fragment = if user.totp_enabled {
+            let two_factor_token = create_two_factor_challenge(&redis_pool, user.id).await?;
+            format!("two_factor_token={two_factor_token}")
+        } else {
+            let res = start_session(&data_loader, &user).await?;
+            format!(
+                "token={}&refresh_token={}&two_factor_setup_required={}",
+                res.token.expect("Session has an access token"),
+                res.refresh_token.expect("Session has a refresh token"),
+                res.two_factor_setup_required,
+            )
+        };
+
+        Ok((
+            [(SET_COOKIE, clear_state_cookie())],
+            Redirect::to(&format!("{}/login/oidc#{fragment}", HOST_URL.as_str())),
+        ))
+    }
+
+    /// Renders an email template with sample data, as HTML or with `?text=true` as plain text.
+    #[instrument(skip(conn), err(Debug))]
+    pub async fn email_preview(
+        Path(template): Path<String>,
+        Query(params): Query<EmailPreviewParams>,
+        State(conn): State<DatabaseConnection>,
+        claims: Claims,
+    ) -> Result<Response, AppError> {
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+        let user_id = Uuid::parse_str(&claims.sub)?;
+        let user = UserRepo::find_by_id(&data_loader, user_id).await?;
+        if !matches!(user, Some(user) if user.user_type == UserType::Admin) {
+            return Err(AppError::auth("Only admins can preview emails"));
+        }
+
+        let template = match template.parse::<EmailTemplate>() {
+            Ok(template) => template,
+            Err(_) => {
+                return Err(AppError::user(
+                    "Unknown email template",
+                    UserError::BadInput {
+                        parameter: "template",
+                        given_value: template,
+                    },
+                ))
+            }
+        };
+
+        let locale = params.locale.as_deref().unwrap_or(DEFAULT_LOCALE);
+        let email = email_templates::render(
+            template,
+            locale,
+            "preview@example.com".into(),
+            &template.sample_context(),
+        )?;
+
+        if params.text {
+            Ok(format!("Subject: {}\n\n{}", email.subject, email.text_body).into_response())
+        } else {
+            Ok(Html(email.html_body).into_response())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::headers::Header;
+    use axum::http::header::LOCATION;
+    use axum::http::HeaderValue;
+    use deadpool_redis::{Config, Runtime};
+
+    use super::*;
+    use crate::api::user::mutation::{send_activation_email, verify_password};
+    use crate::core::mailer::{DynMailer, InMemoryMailer};
+    use crate::core::oidc::mock_idp::{MockIdp, CLIENT_ID};
+    use crate::core::personal_access_token;
+    use crate::test_utils::{self, user_model};
+    use ::entity::{personal_access_token as pat, user};
+    use chrono::Utc;
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, Set};
+    use std::sync::Arc;
+
+    fn cookies(value: &str) -> Option<TypedHeader<Cookie>> {
+        let value = HeaderValue::from_str(value).unwrap();
+        Some(TypedHeader(
+            Cookie::decode(&mut std::iter::once(&value)).unwrap(),
+        ))
+    }
+
+    fn params(code: &str, state: &str) -> Query<OidcCallbackParams> {
+        Query(OidcCallbackParams {
+            code: Some(code.into()),
+            state: state.into(),
+            error: None,
+        })
+    }
+
+    async fn connect() -> (DatabaseConnection, Pool) {
+        if std::env::var("SECRET").is_err() {
+            std::env::set_var("SECRET", "secret");
+        }
+        let conn = test_utils::connect().await;
+        let redis_pool = Config::from_url(std::env::var("REDIS_URL").unwrap())
+            .create_pool(Some(Runtime::Tokio1))
+            .unwrap();
+        (conn, redis_pool)
+    }
+
+    async fn insert_user(conn: &DatabaseConnection, password: &str) -> user::Model {
+        argon2_async::set_config(argon2_async::Config::default()).await;
+        user::Model {
+            password: argon2_async::hash(password).await.unwrap(),
+            ..user_model()
+        }
+        .into_active_model()
+        .insert(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn oidc_callback_needs_the_state_cookie() {
+        // neither is reached, the state is checked first
+        let conn = DatabaseConnection::Disconnected;
+        let redis_pool = Config::from_url("redis://127.0.0.1:1/")
+            .create_pool(Some(Runtime::Tokio1))
+            .unwrap();
+
+        for cookie in [None, cookies("oidc_state=other")] {
+            let res = UserRest::oidc_callback(
+                params("code", "state"),
+                cookie,
+                State(conn.clone()),
+                State(redis_pool.clone()),
+            )
+            .await;
+            assert!(matches!(res, Err(err) if err.to_string() == "Authentication error"));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL and redis at REDIS_URL"]
+    async fn oidc_login_finishes_through_the_identity_provider() {
+        let idp = MockIdp::start().await;
+        std::env::set_var("OIDC_ISSUER_URL", &idp.issuer);
+        std::env::set_var("OIDC_CLIENT_ID", CLIENT_ID);
+        let (conn, redis_pool) = connect().await;
+
+        let login = UserRest::oidc_login(State(redis_pool.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let set_cookie = login.headers()[SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.contains("HttpOnly"));
+        let cookie = set_cookie.split(';').next().unwrap().to_owned();
+        let email = format!("{}@Example.com", Uuid::new_v4());
+        let (code, state) = idp.authorize(login.headers()[LOCATION].to_str().unwrap(), &email);
+
+        let callback = UserRest::oidc_callback(
+            params(&code, &state),
+            cookies(&cookie),
+            State(conn.clone()),
+            State(redis_pool.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let location = callback.headers()[LOCATION].to_str().unwrap();
+        assert!(location.contains("/login/oidc#token="));
+        assert!(callback.headers()[SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .contains("Max-Age=0"));
+
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+        let user = UserRepo::find_by_email(&data_loader, email.to_lowercase())
+            .await
+            .unwrap();
+        assert!(matches!(user, Some(user) if user.active));
+
+        // the state was used up with the first callback
+        let replayed = UserRest::oidc_callback(
+            params(&code, &state),
+            cookies(&cookie),
+            State(conn),
+            State(redis_pool),
+        )
+        .await;
+        assert!(replayed.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL and redis at REDIS_URL"]
+    async fn confirming_an_email_change_signs_out_everywhere() {
+        let (conn, redis_pool) = connect().await;
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+        let user = insert_user(&conn, "correct horse").await;
+
+        start_session(&data_loader, &user).await.unwrap();
+        let (_, token_hash) = personal_access_token::generate();
+        UserRepo::create_personal_access_token(
+            &data_loader,
+            pat::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                name: Set("ci".into()),
+                token_hash: Set(token_hash),
+                user_id: Set(user.id),
+                read_only: Set(false),
+                class_ids: Set(None),
+                created_at: Set(Utc::now().naive_utc()),
+                expires_at: Set(None),
+                last_used_at: Set(None),
+                revoked_at: Set(None),
+            },
+        )
+        .await
+        .unwrap();
+
+        let new_email = format!("{}@example.com", Uuid::new_v4());
+        let token = generate_opaque_token();
+        UserRepo::create_email_change_token(
+            &data_loader,
+            user.id,
+            new_email.clone(),
+            hash_opaque_token(&token),
+        )
+        .await
+        .unwrap();
+        UserRest::confirm_email(
+            Path(token.clone()),
+            State(conn.clone()),
+            State(redis_pool.clone()),
+        )
+        .await
+        .unwrap();
+
+        let changed = UserRepo::find_by_email(&data_loader, new_email.to_uppercase())
+            .await
+            .unwrap();
+        assert!(matches!(changed, Some(changed) if changed.id == user.id));
+        // nothing was left to revoke
+        let sessions = UserRepo::revoke_user_sessions(&data_loader, user.id)
+            .await
+            .unwrap();
+        assert!(sessions.is_empty());
+        let tokens = UserRepo::find_personal_access_tokens(&data_loader, user.id)
+            .await
+            .unwrap();
+        assert!(tokens.is_empty());
+
+        let replayed = UserRest::confirm_email(Path(token), State(conn), State(redis_pool)).await;
+        assert!(replayed.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL and redis at REDIS_URL"]
+    async fn emailed_activation_link_activates_the_account() {
+        let (conn, _) = connect().await;
+        let in_memory = Arc::new(InMemoryMailer::default());
+        let mailer: DynMailer = in_memory.clone();
+        let mut user = insert_user(&conn, "correct horse")
+            .await
+            .into_active_model();
+        user.active = Set(false);
+        let user = user.update(&conn).await.unwrap();
+        let data_loader = DataLoader::new(conn.clone(), tokio::spawn);
+
+        send_activation_email(&data_loader, &mailer, &user)
+            .await
+            .unwrap();
+
+        let sent = in_memory.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, user.email);
+        let token = sent[0]
+            .text_body
+            .split("/api/v1/user/activate/")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .expect("Activation email should contain the link")
+            .to_owned();
+
+        UserRest::activate(Path(token.clone()), State(conn.clone()))
+            .await
+            .unwrap();
+        let activated = user::Entity::find_by_id(user.id)
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(activated.active);
+
+        let replayed = UserRest::activate(Path(token), State(conn)).await;
+        assert!(replayed.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL and redis at REDIS_URL"]
+    async fn password_checks_respect_the_lockout() {
+        let (conn, redis_pool) = connect().await;
+        let mailer: DynMailer = Arc::new(InMemoryMailer::default());
+        let user = insert_user(&conn, "correct horse").await;
+
+        let wrong = verify_password(&redis_pool, &mailer, &user, "wrong".into(), None).await;
+        assert!(matches!(wrong, Err(err) if err.to_string() == "Authentication error"));
+        verify_password(&redis_pool, &mailer, &user, "correct horse".into(), None)
+            .await
+            .unwrap();
+
+        // a locked account can't be changed even with the right password
+        lockout::lock_account(&redis_pool, user.id).await.unwrap();
+        let locked =
+            verify_password(&redis_pool, &mailer, &user, "correct horse".into(), None).await;
+        assert!(locked.is_err());
+        lockout::unlock_account(&redis_pool, user.id, &user.username)
+            .await
+            .unwrap();
+    }
 }
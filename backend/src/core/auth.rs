@@ -1,44 +1,166 @@
 use crate::{
-    api::{ClassRepo, UserRepo},
+    api::{AssignmentRepo, ClassRepo, MessageRepo, UserRepo},
     SECRET,
 };
-use async_graphql::{dataloader::DataLoader, Context, Guard, ID};
+use std::convert::Infallible;
+
+use async_graphql::{dataloader::DataLoader, Context, Enum, Guard, ID};
 use async_trait::async_trait;
 use axum::{
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts},
     headers::{authorization::Bearer, Authorization},
     http::request::Parts,
     TypedHeader,
 };
 
-use entity::sea_orm_active_enums::UserType;
+use base64::Engine;
+use deadpool_redis::{redis::AsyncCommands, Pool};
+use entity::sea_orm_active_enums::{MemberRole, UserType};
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use super::AppError;
+use super::impersonation::{self, Actor};
+use super::personal_access_token::{self, TokenScope};
+use super::{AppError, UserError};
+
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+pub const TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = 5;
+pub const ACTIVATION_TOKEN_TTL_HOURS: i64 = 48;
+pub const UNACTIVATED_ACCOUNT_TTL_DAYS: i64 = 7;
+pub const PASSWORD_RESET_TOKEN_TTL_MINUTES: i64 = 60;
+pub const EMAIL_CHANGE_TOKEN_TTL_HOURS: i64 = 24;
+const REVOKED_SESSION: &str = "revoked_session";
+const TWO_FACTOR_CHALLENGE: &str = "two_factor_challenge";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    pub sid: String,
     pub exp: i64,
+    /// Only set for personal access tokens, where `sid` is the id of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<TokenScope>,
+    /// Only set for impersonation tokens, `sub` is then the impersonated user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// Set until a user who is required to use two-factor authentication sets it up.
+    /// Such tokens can't do anything else.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub two_factor_setup: bool,
+}
+
+impl Claims {
+    pub fn is_personal_access_token(&self) -> bool {
+        self.scope.is_some()
+    }
+
+    pub fn allows_class(&self, class_id: Uuid) -> bool {
+        match &self.scope {
+            Some(scope) => scope.allows_class(class_id),
+            None => true,
+        }
+    }
+
+    /// Personal access tokens limited to some classes, which can't act on the account or
+    /// anything outside of those classes.
+    pub fn is_class_scoped(&self) -> bool {
+        matches!(&self.scope, Some(scope) if scope.class_ids.is_some())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        matches!(&self.scope, Some(scope) if scope.read_only)
+    }
+
+    /// The admin behind an impersonation token.
+    pub fn impersonator_id(&self) -> Option<Uuid> {
+        self.act
+            .as_ref()
+            .and_then(|actor| Uuid::parse_str(&actor.sub).ok())
+    }
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for Claims
 where
+    Pool: FromRef<S>,
+    DatabaseConnection: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
 
     async fn from_request_parts(req: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let TypedHeader(Authorization(bearer)) =
-            TypedHeader::<Authorization<Bearer>>::from_request_parts(req, state)
-                .await
-                .map_err(|_| AppError::auth("Malformed JWT"))?;
-        validate_token(bearer.token())
+        let action = format!("{} {}", req.method, req.uri.path());
+        let claims = claims_from_parts(req, state, Some(&action)).await?;
+        if claims.two_factor_setup {
+            return Err(AppError::auth(
+                "Two-factor authentication has to be set up first",
+            ));
+        }
+        Ok(claims)
+    }
+}
+
+/// Claims of the GraphQL endpoint. Unlike [`Claims`] it also accepts tokens of users who
+/// still have to set up two-factor authentication, the guards keep those in check.
+pub struct GraphQLClaims(pub Option<Claims>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for GraphQLClaims
+where
+    Pool: FromRef<S>,
+    DatabaseConnection: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(req: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // the operations are recorded by `ImpersonationAudit`, with their names
+        Ok(Self(claims_from_parts(req, state, None).await.ok()))
+    }
+}
+
+async fn claims_from_parts<S>(
+    req: &mut Parts,
+    state: &S,
+    action: Option<&str>,
+) -> Result<Claims, AppError>
+where
+    Pool: FromRef<S>,
+    DatabaseConnection: FromRef<S>,
+    S: Send + Sync,
+{
+    let TypedHeader(Authorization(bearer)) =
+        TypedHeader::<Authorization<Bearer>>::from_request_parts(req, state)
+            .await
+            .map_err(|_| AppError::auth("Malformed JWT"))?;
+    let redis_pool = Pool::from_ref(state);
+    let conn = DatabaseConnection::from_ref(state);
+    authenticate(&redis_pool, conn, bearer.token(), action).await
+}
+
+/// Accepts both session JWTs and personal access tokens. Uses of impersonation tokens
+/// are written to the audit log as `action`, if there is one.
+pub async fn authenticate(
+    redis_pool: &Pool,
+    conn: DatabaseConnection,
+    token: &str,
+    action: Option<&str>,
+) -> Result<Claims, AppError> {
+    let data_loader = DataLoader::new(conn, tokio::spawn);
+    if personal_access_token::is_personal_access_token(token) {
+        return personal_access_token::validate(&data_loader, token).await;
+    }
+
+    let claims = validate_session(redis_pool, token).await?;
+    if let Some(action) = action {
+        impersonation::record(&data_loader, &claims, action).await?;
     }
+
+    Ok(claims)
 }
 
 pub fn validate_token(token: &str) -> Result<Claims, AppError> {
@@ -53,6 +175,100 @@ pub fn validate_token(token: &str) -> Result<Claims, AppError> {
     Ok(token_data.claims)
 }
 
+/// Validates the JWT and makes sure its session was not revoked before the token expired.
+pub async fn validate_session(redis_pool: &Pool, token: &str) -> Result<Claims, AppError> {
+    let claims = validate_token(token)?;
+
+    let mut conn = redis_pool.get().await?;
+    let is_revoked: bool = conn
+        .exists(format!("{}:{}", REVOKED_SESSION, claims.sid))
+        .await?;
+    if is_revoked {
+        return Err(AppError::auth("Session was revoked"));
+    }
+
+    Ok(claims)
+}
+
+/// Marks sessions as revoked for as long as access tokens issued for them stay valid.
+pub async fn revoke_sessions(redis_pool: &Pool, session_ids: &[Uuid]) -> Result<(), AppError> {
+    let mut conn = redis_pool.get().await?;
+    for session_id in session_ids {
+        let _: () = conn
+            .set_ex(
+                format!("{}:{}", REVOKED_SESSION, session_id),
+                true,
+                (ACCESS_TOKEN_TTL_MINUTES * 60) as usize,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Stores a short lived challenge proving that the user already passed the password check.
+pub async fn create_two_factor_challenge(
+    redis_pool: &Pool,
+    user_id: Uuid,
+) -> Result<String, AppError> {
+    let token = generate_opaque_token();
+    let mut conn = redis_pool.get().await?;
+    let _: () = conn
+        .set_ex(
+            format!("{}:{}", TWO_FACTOR_CHALLENGE, hash_opaque_token(&token)),
+            user_id.to_string(),
+            (TWO_FACTOR_CHALLENGE_TTL_MINUTES * 60) as usize,
+        )
+        .await?;
+
+    Ok(token)
+}
+
+/// Consumes the challenge, so every wrong code sends the user back to the password step.
+pub async fn take_two_factor_challenge(redis_pool: &Pool, token: &str) -> Result<Uuid, AppError> {
+    let key = format!("{}:{}", TWO_FACTOR_CHALLENGE, hash_opaque_token(token));
+    let mut conn = redis_pool.get().await?;
+    let user_id: Option<String> = conn.get(&key).await?;
+    let _: () = conn.del(&key).await?;
+
+    match user_id {
+        Some(user_id) => Ok(Uuid::parse_str(&user_id)?),
+        None => Err(AppError::auth("Two-factor challenge expired")),
+    }
+}
+
+pub fn create_access_token(
+    user_id: Uuid,
+    session_id: Uuid,
+    two_factor_setup: bool,
+) -> Result<String, AppError> {
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &Claims {
+            sub: user_id.to_string(),
+            sid: session_id.to_string(),
+            exp: (chrono::Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_TTL_MINUTES))
+                .timestamp(),
+            scope: None,
+            act: None,
+            two_factor_setup,
+        },
+        &jsonwebtoken::EncodingKey::from_secret(SECRET.as_ref()),
+    )?;
+
+    Ok(token)
+}
+
+/// Creates a random, url safe token. Only its hash should ever be stored.
+pub fn generate_opaque_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_opaque_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 pub struct LoggedInGuard;
 
 #[async_trait]
@@ -61,94 +277,389 @@ impl Guard for LoggedInGuard {
         let claims = ctx.data_unchecked::<Option<Claims>>();
 
         match claims {
+            Some(claims) if claims.two_factor_setup => {
+                Err(AppError::auth("Two-factor authentication has to be set up first").into())
+            }
             Some(_) => Ok(()),
             None => Err(AppError::auth("Missing JWT claims").into()),
         }
     }
 }
 
-pub struct ClassMemberGuard {
+/// Like [`LoggedInGuard`], but also lets in users who still have to set up two-factor
+/// authentication.
+pub struct TwoFactorSetupGuard;
+
+#[async_trait]
+impl Guard for TwoFactorSetupGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        match claims {
+            Some(_) => Ok(()),
+            None => Err(AppError::auth("Missing JWT claims").into()),
+        }
+    }
+}
+
+/// Keeps impersonating admins away from the credentials and sessions of the user.
+pub struct NotImpersonatingGuard;
+
+#[async_trait]
+impl Guard for NotImpersonatingGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        match claims {
+            Some(claims) if claims.act.is_some() => {
+                Err(AppError::auth("Not allowed while impersonating").into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps personal access tokens limited to some classes inside of them. Fields that don't
+/// belong to a single class take [`TokenScopeGuard::account`].
+pub struct TokenScopeGuard {
+    class_id: Option<ID>,
+}
+
+impl TokenScopeGuard {
+    pub fn account() -> Self {
+        Self { class_id: None }
+    }
+
+    pub fn class(class_id: ID) -> Self {
+        Self {
+            class_id: Some(class_id),
+        }
+    }
+}
+
+#[async_trait]
+impl Guard for TokenScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
+        let claims = ctx.data_unchecked::<Option<Claims>>();
+
+        let claims = claims.as_ref().expect("claims exist");
+        let allowed = match &self.class_id {
+            Some(class_id) => claims.allows_class(class_id.parse::<Uuid>()?),
+            None => !claims.is_class_scoped(),
+        };
+        match allowed {
+            true => Ok(()),
+            false => Err(AppError::auth("Token is not allowed to access this resource").into()),
+        }
+    }
+}
+
+/// What a user may do in a class, from the least to the most privileged role.
+/// Every role can do everything the roles before it can.
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[graphql(name = "MemberRole")]
+pub enum ClassRole {
+    /// Reads the class without taking part in it.
+    Observer,
+    /// Posts in open channels and hands in submissions.
+    Student,
+    /// Grades submissions, manages files and posts in read-only channels.
+    TeachingAssistant,
+    /// Manages the class content, its members and their roles.
+    CoTeacher,
+    /// Not stored on the membership, the owner is `class.owner_id`.
+    Owner,
+}
+
+impl From<MemberRole> for ClassRole {
+    fn from(role: MemberRole) -> Self {
+        match role {
+            MemberRole::Observer => Self::Observer,
+            MemberRole::Student => Self::Student,
+            MemberRole::TeachingAssistant => Self::TeachingAssistant,
+            MemberRole::CoTeacher => Self::CoTeacher,
+        }
+    }
+}
+
+impl ClassRole {
+    /// The stored membership role, `None` for the owner who is not stored on the membership.
+    pub fn member_role(&self) -> Option<MemberRole> {
+        match self {
+            ClassRole::Observer => Some(MemberRole::Observer),
+            ClassRole::Student => Some(MemberRole::Student),
+            ClassRole::TeachingAssistant => Some(MemberRole::TeachingAssistant),
+            ClassRole::CoTeacher => Some(MemberRole::CoTeacher),
+            ClassRole::Owner => None,
+        }
+    }
+}
+
+/// Lets through members of the class whose role is at least `role`.
+pub struct ClassRoleGuard {
     class_id: ID,
+    role: ClassRole,
 }
 
-impl ClassMemberGuard {
-    pub fn new(class_id: ID) -> Self {
-        Self { class_id }
+impl ClassRoleGuard {
+    pub fn new(class_id: ID, role: ClassRole) -> Self {
+        Self { class_id, role }
     }
 }
 
 #[async_trait]
-impl Guard for ClassMemberGuard {
+impl Guard for ClassRoleGuard {
     async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
 
-        let user_id = Uuid::parse_str(claims.as_ref().expect("claims exist").sub.as_str())?;
+        let claims = claims.as_ref().expect("claims exist");
+        let user_id = Uuid::parse_str(claims.sub.as_str())?;
         let class_id = self.class_id.parse::<Uuid>()?;
+        if !claims.allows_class(class_id) {
+            return Err(AppError::auth("Token is not allowed to access this class").into());
+        }
+        authorize_class_role(data_loader, user_id, class_id, self.role).await?;
+
+        Ok(())
+    }
+}
 
-        if is_class_member(data_loader, user_id, class_id).await {
-            return Ok(());
+/// Keeps archived classes read-only. Goes next to a [`ClassRoleGuard`] on mutations that
+/// add to or change the content of a class.
+pub struct NotArchivedGuard {
+    target: ArchiveTarget,
+}
+
+enum ArchiveTarget {
+    Class(ID),
+    /// The class of the channel the message was posted in.
+    Message(ID),
+}
+
+impl NotArchivedGuard {
+    pub fn new(class_id: ID) -> Self {
+        Self {
+            target: ArchiveTarget::Class(class_id),
         }
+    }
+
+    pub fn message(id: ID) -> Self {
+        Self {
+            target: ArchiveTarget::Message(id),
+        }
+    }
+}
+
+#[async_trait]
+impl Guard for NotArchivedGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
+        let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
+
+        let class_id = match &self.target {
+            ArchiveTarget::Class(id) => id.parse::<Uuid>()?,
+            ArchiveTarget::Message(id) => {
+                match MessageRepo::find_class_id(data_loader, id.parse()?).await? {
+                    Some(class_id) => class_id,
+                    // the resolver reports the missing message
+                    None => return Ok(()),
+                }
+            }
+        };
+        ensure_not_archived(data_loader, class_id).await?;
+
+        Ok(())
+    }
+}
+
+pub async fn ensure_not_archived(
+    data_loader: &DataLoader<DatabaseConnection>,
+    class_id: Uuid,
+) -> Result<(), AppError> {
+    match ClassRepo::find_by_id(data_loader, class_id).await? {
+        Some(class) if class.archived_at.is_some() => Err(AppError::user(
+            "Class is archived",
+            UserError::BadInput {
+                parameter: "class_id",
+                given_value: class_id.to_string(),
+            },
+        )),
+        _ => Ok(()),
+    }
+}
 
-        return Err(AppError::auth("User is not a member of this class").into());
+pub async fn authorize_class_role(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user_id: Uuid,
+    class_id: Uuid,
+    required: ClassRole,
+) -> Result<(), AppError> {
+    match class_role(data_loader, user_id, class_id).await? {
+        Some(role) if role >= required => Ok(()),
+        Some(_) => Err(AppError::auth(format!(
+            "User needs to be at least {required:?} in this class"
+        ))),
+        None => Err(AppError::auth("User is not a member of this class")),
     }
 }
 
+/// Role of the user in the class, `None` if they are not a member.
+pub async fn class_role(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user_id: Uuid,
+    class_id: Uuid,
+) -> Result<Option<ClassRole>, AppError> {
+    let class = match ClassRepo::find_by_id(data_loader, class_id).await? {
+        Some(class) => class,
+        None => return Ok(None),
+    };
+    if class.owner_id == user_id {
+        return Ok(Some(ClassRole::Owner));
+    }
+
+    let membership = ClassRepo::find_membership(data_loader, class_id, user_id).await?;
+    Ok(membership.map(|membership| membership.role.into()))
+}
+
 pub async fn is_class_member(
     data_loader: &DataLoader<DatabaseConnection>,
     user_id: Uuid,
     class_id: Uuid,
 ) -> bool {
-    let members = match UserRepo::find_by_class_id(data_loader, class_id).await {
-        Ok(members) => members,
-        Err(_) => return false,
-    };
+    matches!(
+        class_role(data_loader, user_id, class_id).await,
+        Ok(Some(_))
+    )
+}
 
-    if let Some(members) = members {
-        if members.iter().any(|m| m.id == user_id) {
-            return true;
-        }
-    }
-    return false;
+/// Something that belongs to a single user, who is the only one allowed to change it.
+#[derive(Debug, Clone)]
+pub enum Resource {
+    User(ID),
+    Message(ID),
+    Submission(ID),
+    /// Feedback belongs to everyone grading in the class the submission was handed in to.
+    Feedback(ID),
 }
 
-pub struct ClassOwnerGuard {
-    class_id: ID,
+pub struct ResourceOwnerGuard {
+    resource: Resource,
 }
 
-impl ClassOwnerGuard {
-    pub fn new(class_id: ID) -> Self {
-        Self { class_id }
+impl ResourceOwnerGuard {
+    pub fn user(id: ID) -> Self {
+        Self {
+            resource: Resource::User(id),
+        }
+    }
+
+    pub fn message(id: ID) -> Self {
+        Self {
+            resource: Resource::Message(id),
+        }
+    }
+
+    pub fn submission(id: ID) -> Self {
+        Self {
+            resource: Resource::Submission(id),
+        }
+    }
+
+    pub fn feedback(id: ID) -> Self {
+        Self {
+            resource: Resource::Feedback(id),
+        }
     }
 }
 
 #[async_trait]
-impl Guard for ClassOwnerGuard {
+impl Guard for ResourceOwnerGuard {
     async fn check(&self, ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
 
         let user_id = Uuid::parse_str(claims.as_ref().expect("claims exist").sub.as_str())?;
-        let class_id = self.class_id.parse::<Uuid>()?;
+        authorize_owner(data_loader, user_id, &self.resource).await?;
 
-        let class = ClassRepo::find_by_id(data_loader, class_id).await?;
-        if let Some(class) = class {
-            if class.owner_id == user_id {
-                return Ok(());
+        Ok(())
+    }
+}
+
+pub async fn authorize_owner(
+    data_loader: &DataLoader<DatabaseConnection>,
+    user_id: Uuid,
+    resource: &Resource,
+) -> Result<(), AppError> {
+    let allowed = match resource_owner(data_loader, resource).await? {
+        ResourceOwner::User(owner_id) => owner_id == user_id,
+        ResourceOwner::Graders(class_id) => matches!(
+            class_role(data_loader, user_id, class_id).await?,
+            Some(role) if role >= ClassRole::TeachingAssistant
+        ),
+    };
+    if allowed {
+        return Ok(());
+    }
+
+    Err(AppError::auth("User is not the owner of this resource"))
+}
+
+/// Who a resource belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceOwner {
+    User(Uuid),
+    /// Teaching assistants and everyone above them in the class.
+    Graders(Uuid),
+}
+
+pub async fn resource_owner(
+    data_loader: &DataLoader<DatabaseConnection>,
+    resource: &Resource,
+) -> Result<ResourceOwner, AppError> {
+    let owner = match resource {
+        Resource::User(id) => Some(ResourceOwner::User(id.parse::<Uuid>()?)),
+        Resource::Message(id) => MessageRepo::find_by_id(data_loader, id.parse()?)
+            .await?
+            .map(|message| ResourceOwner::User(message.author_id)),
+        Resource::Submission(id) => AssignmentRepo::find_submission_by_id(data_loader, id.parse()?)
+            .await?
+            .map(|submission| ResourceOwner::User(submission.user_id)),
+        Resource::Feedback(id) => {
+            match AssignmentRepo::find_feedback_by_id(data_loader, id.parse()?).await? {
+                Some(feedback) => {
+                    submission_class_id(data_loader, feedback.assignment_submission_id)
+                        .await?
+                        .map(ResourceOwner::Graders)
+                }
+                None => None,
             }
         }
+    };
 
-        return Err(AppError::auth("User is not the owner of this class").into());
-    }
+    owner.ok_or_else(|| {
+        let (name, id) = match resource {
+            Resource::User(id) => ("user", id),
+            Resource::Message(id) => ("message", id),
+            Resource::Submission(id) => ("assignment_submission", id),
+            Resource::Feedback(id) => ("assignment_submission_feedback", id),
+        };
+        AppError::not_found(format!("{name} not found"), name, "id", id.to_string())
+    })
 }
 
-pub struct ResourceOwnerGuard;
+/// Class the submission was handed in to.
+pub async fn submission_class_id(
+    data_loader: &DataLoader<DatabaseConnection>,
+    submission_id: Uuid,
+) -> Result<Option<Uuid>, AppError> {
+    let submission = AssignmentRepo::find_submission_by_id(data_loader, submission_id).await?;
+    let submission = match submission {
+        Some(submission) => submission,
+        None => return Ok(None),
+    };
+    let assignment = AssignmentRepo::find_by_id(data_loader, submission.assignment_id).await?;
 
-#[async_trait]
-impl Guard for ResourceOwnerGuard {
-    async fn check(&self, _ctx: &Context<'_>) -> Result<(), async_graphql::Error> {
-        todo!()
-    }
+    Ok(assignment.map(|assignment| assignment.class_id))
 }
 
 pub struct AdminGuard;
@@ -159,7 +670,12 @@ impl Guard for AdminGuard {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
 
-        let user_id = Uuid::parse_str(claims.as_ref().expect("claims exist").sub.as_str())?;
+        let claims = claims.as_ref().expect("claims exist");
+        // a token limited to some classes must not carry the powers of the account
+        if claims.is_class_scoped() {
+            return Err(AppError::auth("Token is not allowed to access this resource").into());
+        }
+        let user_id = Uuid::parse_str(claims.sub.as_str())?;
         let user = UserRepo::find_by_id(data_loader, user_id)
             .await?
             .expect("user must exist");
@@ -179,7 +695,11 @@ impl Guard for ModGuard {
         let data_loader = ctx.data_unchecked::<DataLoader<DatabaseConnection>>();
         let claims = ctx.data_unchecked::<Option<Claims>>();
 
-        let user_id = Uuid::parse_str(claims.as_ref().expect("claims exist").sub.as_str())?;
+        let claims = claims.as_ref().expect("claims exist");
+        if claims.is_class_scoped() {
+            return Err(AppError::auth("Token is not allowed to access this resource").into());
+        }
+        let user_id = Uuid::parse_str(claims.sub.as_str())?;
         let user = UserRepo::find_by_id(data_loader, user_id)
             .await?
             .expect("user must exist");
@@ -190,3 +710,144 @@ impl Guard for ModGuard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{connect, insert_class, insert_member, insert_user};
+    use chrono::Utc;
+    use entity::{
+        assignment, assignment_submission, assignment_submission_feedback, channel, message,
+    };
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+
+    fn is_auth_error(res: Result<(), AppError>) -> bool {
+        matches!(res, Err(err) if err.to_string() == "Authentication error")
+    }
+
+    #[tokio::test]
+    async fn users_can_only_change_themselves() {
+        let data_loader = DataLoader::new(DatabaseConnection::Disconnected, tokio::spawn);
+        let user_id = Uuid::new_v4();
+
+        let own = Resource::User(user_id.into());
+        assert!(authorize_owner(&data_loader, user_id, &own).await.is_ok());
+
+        let other = Resource::User(Uuid::new_v4().into());
+        assert!(is_auth_error(
+            authorize_owner(&data_loader, user_id, &other).await
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database at DATABASE_URL"]
+    async fn cross_user_edits_are_rejected() {
+        let conn = connect().await;
+
+        let teacher = insert_user(&conn).await.id;
+        let student = insert_user(&conn).await.id;
+        let stranger = insert_user(&conn).await.id;
+        let assistant = insert_user(&conn).await.id;
+        let now = Utc::now().naive_utc();
+
+        let class = insert_class(&conn, teacher).await;
+        for (user_id, role) in [
+            (teacher, MemberRole::CoTeacher),
+            (student, MemberRole::Student),
+            (assistant, MemberRole::TeachingAssistant),
+        ] {
+            insert_member(&conn, class.id, user_id, role).await;
+        }
+        let channel = channel::Model {
+            id: Uuid::new_v4(),
+            name: "general".into(),
+            description: None,
+            allow_members_to_post: true,
+            class_id: class.id,
+            deleted_at: None,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let message = message::Model {
+            id: Uuid::new_v4(),
+            content: "Hello".into(),
+            channel_id: channel.id,
+            author_id: student,
+            created_at: now,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let assignment = assignment::Model {
+            id: Uuid::new_v4(),
+            name: "Fractions".into(),
+            content: String::new(),
+            created_at: now,
+            due_at: None,
+            class_id: class.id,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let submission = assignment_submission::Model {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: None,
+            assignment_id: assignment.id,
+            user_id: student,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+        let feedback = assignment_submission_feedback::Model {
+            id: Uuid::new_v4(),
+            assignment_submission_id: submission.id,
+            feedback: "Well done".into(),
+            created_at: now,
+            updated_at: None,
+        }
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+
+        let data_loader = DataLoader::new(conn, tokio::spawn);
+        let message = Resource::Message(message.id.into());
+        let submission = Resource::Submission(submission.id.into());
+        let feedback = Resource::Feedback(feedback.id.into());
+
+        for (owner, resource) in [
+            (student, &message),
+            (student, &submission),
+            (teacher, &feedback),
+        ] {
+            assert!(authorize_owner(&data_loader, owner, resource).await.is_ok());
+            for other in [teacher, student, stranger]
+                .into_iter()
+                .filter(|u| *u != owner)
+            {
+                assert!(
+                    is_auth_error(authorize_owner(&data_loader, other, resource).await),
+                    "{other} should not be able to change {resource:?}"
+                );
+            }
+        }
+
+        // teaching assistants grade without owning the class
+        assert!(authorize_owner(&data_loader, assistant, &feedback)
+            .await
+            .is_ok());
+        assert!(is_auth_error(
+            authorize_owner(&data_loader, assistant, &submission).await
+        ));
+
+        let missing = Resource::Message(Uuid::new_v4().into());
+        let res = authorize_owner(&data_loader, student, &missing).await;
+        assert!(matches!(res, Err(err) if err.to_string().ends_with("was not found")));
+    }
+}
@@ -0,0 +1,558 @@
+//! Recurring class events and the iCalendar (RFC 5545) feed they are published in.
+//!
+//! Only the part of `RRULE` that timetables need is supported: daily, weekly and monthly
+//! repetition with an interval, weekdays for weekly rules and an end given by `COUNT` or
+//! `UNTIL`. Rules repeat in the timezone of their event, so a 10:00 lecture stays at 10:00
+//! when daylight saving time starts.
+
+use std::fmt;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+use super::{AppError, UserError};
+
+/// Upper bound for `COUNT`, which also keeps expanding a rule cheap.
+pub const MAX_COUNT: u32 = 1000;
+/// Upper bound for `INTERVAL`.
+pub const MAX_INTERVAL: u32 = 99;
+/// Candidate dates looked at while expanding a single rule, in case `UNTIL` is far away.
+const MAX_STEPS: usize = 20_000;
+/// How far ahead of now rules without an end are expanded in the feed. Calendar apps poll
+/// the feed, so the window moves along.
+const FEED_DAYS_AHEAD: i64 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Days a weekly rule repeats on, the day of the first occurrence when empty.
+    pub by_day: Vec<Weekday>,
+    pub count: Option<u32>,
+    /// Last moment an occurrence may start at, in UTC.
+    pub until: Option<NaiveDateTime>,
+}
+
+fn bad_rule(given_value: &str) -> AppError {
+    AppError::user(
+        "Unsupported recurrence rule",
+        UserError::BadInput {
+            parameter: "recurrence",
+            given_value: given_value.to_owned(),
+        },
+    )
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn format_weekday(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// Local time in `timezone` as UTC. Times skipped by a DST change are moved past the gap.
+fn to_utc(local: NaiveDateTime, timezone: Tz) -> NaiveDateTime {
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(local + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|t| t.naive_utc())
+        .unwrap_or(local)
+}
+
+impl Recurrence {
+    /// Parses an `RRULE` value, with or without the `RRULE:` prefix. An `UNTIL` date without
+    /// a time includes the whole day in `timezone`.
+    pub fn parse(rule: &str, timezone: Tz) -> Result<Self, AppError> {
+        let value = rule.trim();
+        let value = value.strip_prefix("RRULE:").unwrap_or(value);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut count = None;
+        let mut until = None;
+        for part in value.split(';').filter(|p| !p.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or_else(|| bad_rule(rule))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(bad_rule(rule)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| (1..=MAX_INTERVAL).contains(i))
+                        .ok_or_else(|| bad_rule(rule))?
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let day = parse_weekday(&day.to_ascii_uppercase())
+                            .ok_or_else(|| bad_rule(rule))?;
+                        if !by_day.contains(&day) {
+                            by_day.push(day);
+                        }
+                    }
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|c| (1..=MAX_COUNT).contains(c))
+                            .ok_or_else(|| bad_rule(rule))?,
+                    )
+                }
+                "UNTIL" => {
+                    until = Some(if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+                        NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                            .map_err(|_| bad_rule(rule))?
+                    } else {
+                        let day = NaiveDate::parse_from_str(value, "%Y%m%d")
+                            .map_err(|_| bad_rule(rule))?;
+                        to_utc(
+                            day.and_hms_opt(23, 59, 59).expect("Time is valid"),
+                            timezone,
+                        )
+                    })
+                }
+                // The week always starts on Monday, which is what every client assumes
+                "WKST" if value.eq_ignore_ascii_case("MO") => {}
+                _ => return Err(bad_rule(rule)),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| bad_rule(rule))?;
+        // RFC 5545 forbids a rule with both ends
+        if (count.is_some() && until.is_some())
+            || (!by_day.is_empty() && frequency != Frequency::Weekly)
+        {
+            return Err(bad_rule(rule));
+        }
+        by_day.sort_by_key(|d| d.num_days_from_monday());
+
+        Ok(Self {
+            frequency,
+            interval,
+            by_day,
+            count,
+            until,
+        })
+    }
+
+    /// Starts of the occurrences of an event first starting at `starts_at` (UTC) that begin
+    /// before `to`, in order. The first occurrence is always `starts_at` itself.
+    fn starts(
+        &self,
+        starts_at: NaiveDateTime,
+        timezone: Tz,
+        to: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        let first = timezone.from_utc_datetime(&starts_at).naive_local();
+        let time = first.time();
+        let monday = first.date() - Duration::days(first.weekday().num_days_from_monday() as i64);
+
+        let mut starts = vec![starts_at];
+        for step in 0..MAX_STEPS {
+            let dates = match self.frequency {
+                Frequency::Daily => {
+                    vec![first.date() + Duration::days((step as i64 + 1) * self.interval as i64)]
+                }
+                Frequency::Weekly => {
+                    let week = monday + Duration::weeks(step as i64 * self.interval as i64);
+                    let days = if self.by_day.is_empty() {
+                        vec![first.weekday()]
+                    } else {
+                        self.by_day.clone()
+                    };
+                    days.into_iter()
+                        .map(|d| week + Duration::days(d.num_days_from_monday() as i64))
+                        .filter(|d| *d > first.date())
+                        .collect()
+                }
+                // Months without the day of the first occurrence are skipped
+                Frequency::Monthly => first
+                    .date()
+                    .checked_add_months(Months::new((step as u32 + 1) * self.interval))
+                    .filter(|d| d.day() == first.day())
+                    .into_iter()
+                    .collect(),
+            };
+
+            for date in dates {
+                let start = to_utc(date.and_time(time), timezone);
+                let ended = self.count.is_some_and(|c| starts.len() >= c as usize)
+                    || self.until.is_some_and(|u| start > u);
+                if ended || start >= to {
+                    return starts;
+                }
+                starts.push(start);
+            }
+        }
+        starts
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days = self
+                .by_day
+                .iter()
+                .map(|d| format_weekday(*d))
+                .collect::<Vec<_>>();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", format_utc(until))?;
+        }
+        Ok(())
+    }
+}
+
+/// Occurrences of an event that overlap `[from, to)`, as `(starts_at, ends_at)` in UTC.
+pub fn occurrences(
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    timezone: Tz,
+    recurrence: Option<&Recurrence>,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let duration = ends_at - starts_at;
+    let starts = match recurrence {
+        Some(recurrence) => recurrence.starts(starts_at, timezone, to),
+        None => vec![starts_at],
+    };
+    starts
+        .into_iter()
+        .map(|s| (s, s + duration))
+        .filter(|(s, e)| *s < to && *e > from)
+        .collect()
+}
+
+/// `YYYYMMDDTHHMMSSZ`, the UTC form of an iCalendar date-time.
+pub fn format_utc(at: NaiveDateTime) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a TEXT value.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+/// Breaks a content line into lines of at most 75 octets, continued by a leading space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// A `VEVENT` of the feed. Events without an end are points in time, like due dates.
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub categories: Option<String>,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>,
+    pub timezone: Tz,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// A `VCALENDAR` built up event by event. Recurring events are expanded into one `VEVENT`
+/// per occurrence with UTC times, as a `TZID` would need a `VTIMEZONE` for every zone used.
+pub struct Calendar {
+    body: String,
+    stamp: String,
+    expand_until: NaiveDateTime,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        let now = Utc::now().naive_utc();
+        let mut calendar = Self {
+            body: String::new(),
+            stamp: format_utc(now),
+            expand_until: now + Duration::days(FEED_DAYS_AHEAD),
+        };
+        calendar.line("BEGIN:VCALENDAR");
+        calendar.line("VERSION:2.0");
+        calendar.line("PRODID:-//Go e-Learn//Class calendar//EN");
+        calendar.line("CALSCALE:GREGORIAN");
+        calendar.line("METHOD:PUBLISH");
+        calendar.line(&format!("X-WR-CALNAME:{}", escape_text(name)));
+        calendar
+    }
+
+    fn line(&mut self, line: &str) {
+        self.body.push_str(&fold_line(line));
+    }
+
+    pub fn add_event(&mut self, event: &CalendarEvent) {
+        let starts = match &event.recurrence {
+            Some(recurrence) => {
+                recurrence.starts(event.starts_at, event.timezone, self.expand_until)
+            }
+            None => vec![event.starts_at],
+        };
+        let duration = event.ends_at.map(|e| e - event.starts_at);
+
+        for starts_at in starts {
+            // the first occurrence keeps the id it had before the event got a rule
+            let uid = if starts_at == event.starts_at {
+                event.uid.clone()
+            } else {
+                format!("{}-{}", format_utc(starts_at), event.uid)
+            };
+            self.add_occurrence(event, &uid, starts_at, duration.map(|d| starts_at + d));
+        }
+    }
+
+    fn add_occurrence(
+        &mut self,
+        event: &CalendarEvent,
+        uid: &str,
+        starts_at: NaiveDateTime,
+        ends_at: Option<NaiveDateTime>,
+    ) {
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:{}", uid));
+        let stamp = format!("DTSTAMP:{}", self.stamp);
+        self.line(&stamp);
+        self.line(&format!("DTSTART:{}", format_utc(starts_at)));
+        if let Some(ends_at) = ends_at {
+            self.line(&format!("DTEND:{}", format_utc(ends_at)));
+        }
+        self.line(&format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(description) = &event.description {
+            self.line(&format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        if let Some(location) = &event.location {
+            self.line(&format!("LOCATION:{}", escape_text(location)));
+        }
+        if let Some(url) = &event.url {
+            self.line(&format!("URL:{}", url));
+        }
+        if let Some(categories) = &event.categories {
+            self.line(&format!("CATEGORIES:{}", escape_text(categories)));
+        }
+        self.line("END:VEVENT");
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let rule =
+            Recurrence::parse("RRULE:freq=weekly;byday=WE,MO;UNTIL=20240301", Tz::UTC).unwrap();
+        assert_eq!(rule.by_day, vec![Weekday::Mon, Weekday::Wed]);
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20240301T235959Z"
+        );
+        assert_eq!(
+            Recurrence::parse("FREQ=DAILY;INTERVAL=2;COUNT=3", Tz::UTC)
+                .unwrap()
+                .to_string(),
+            "FREQ=DAILY;INTERVAL=2;COUNT=3"
+        );
+        for rule in [
+            "",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;COUNT=2;UNTIL=20240101",
+            "FREQ=MONTHLY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=WEEKLY;INTERVAL=0",
+        ] {
+            assert!(Recurrence::parse(rule, Tz::UTC).is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn weekly_rules_keep_local_time_over_dst() {
+        let timezone: Tz = "Europe/Ljubljana".parse().unwrap();
+        let rule = Recurrence::parse("FREQ=WEEKLY;BYDAY=MO,TH;COUNT=4", timezone).unwrap();
+        // Thursday 21 March 2024, 10:00 CET, ten days before summer time
+        let starts = occurrences(
+            at(2024, 3, 21, 9, 0),
+            at(2024, 3, 21, 10, 30),
+            timezone,
+            Some(&rule),
+            at(2024, 1, 1, 0, 0),
+            at(2025, 1, 1, 0, 0),
+        )
+        .into_iter()
+        .map(|(s, _)| s)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                at(2024, 3, 21, 9, 0),
+                at(2024, 3, 25, 9, 0),
+                at(2024, 3, 28, 9, 0),
+                at(2024, 4, 1, 8, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn occurrences_are_limited_to_the_range() {
+        let rule = Recurrence::parse("FREQ=MONTHLY;UNTIL=20240601T000000Z", Tz::UTC).unwrap();
+        let found = occurrences(
+            at(2024, 1, 31, 12, 0),
+            at(2024, 1, 31, 13, 0),
+            Tz::UTC,
+            Some(&rule),
+            at(2024, 2, 1, 0, 0),
+            at(2024, 12, 1, 0, 0),
+        );
+        // February, April and June have no 31st, June is past UNTIL anyway
+        assert_eq!(
+            found,
+            vec![
+                (at(2024, 3, 31, 12, 0), at(2024, 3, 31, 13, 0)),
+                (at(2024, 5, 31, 12, 0), at(2024, 5, 31, 13, 0)),
+            ]
+        );
+        assert!(occurrences(
+            at(2024, 1, 1, 12, 0),
+            at(2024, 1, 1, 13, 0),
+            Tz::UTC,
+            None,
+            at(2024, 1, 2, 0, 0),
+            at(2024, 1, 3, 0, 0),
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn calendars_are_escaped_and_folded() {
+        let mut calendar = Calendar::new("Classes");
+        calendar.add_event(&CalendarEvent {
+            uid: "1@example.com".into(),
+            summary: "Exam; part 1, \\ 2".into(),
+            description: Some(format!("Bring\n{}", "pencils ".repeat(12))),
+            location: None,
+            url: None,
+            categories: None,
+            starts_at: at(2024, 1, 5, 9, 0),
+            ends_at: None,
+            timezone: Tz::UTC,
+            recurrence: None,
+        });
+        let ics = calendar.finish();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:Exam\\; part 1\\, \\\\ 2\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20240105T090000Z\r\n"));
+        assert!(ics.contains("DESCRIPTION:Bring\\npencils"));
+        assert!(ics.split("\r\n").all(|l| l.len() <= 75));
+        assert!(ics.split("\r\n").any(|l| l.starts_with(' ')));
+    }
+
+    #[test]
+    fn recurring_events_are_expanded_in_utc() {
+        let timezone: Tz = "Europe/Ljubljana".parse().unwrap();
+        let mut calendar = Calendar::new("Classes");
+        calendar.add_event(&CalendarEvent {
+            uid: "1@example.com".into(),
+            summary: "Lecture".into(),
+            description: None,
+            location: None,
+            url: None,
+            categories: None,
+            starts_at: at(2024, 3, 28, 9, 0),
+            ends_at: Some(at(2024, 3, 28, 10, 30)),
+            timezone,
+            recurrence: Some(Recurrence::parse("FREQ=WEEKLY;COUNT=2", timezone).unwrap()),
+        });
+        let ics = calendar.finish();
+
+        assert!(!ics.contains("TZID") && !ics.contains("RRULE"));
+        assert!(ics.contains("\r\nUID:1@example.com\r\nDTSTAMP:"));
+        assert!(ics.contains("\r\nDTSTART:20240328T090000Z\r\nDTEND:20240328T103000Z\r\n"));
+        // 10:00 in Ljubljana after summer time started
+        assert!(ics.contains("\r\nUID:20240404T080000Z-1@example.com\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20240404T080000Z\r\nDTEND:20240404T093000Z\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+    }
+}
@@ -0,0 +1,162 @@
+use lazy_static::lazy_static;
+use serde_json::json;
+use tera::{Context, Tera};
+
+use super::mailer::Email;
+use super::AppError;
+use crate::EMAIL_TEMPLATE_DIR;
+
+pub const DEFAULT_LOCALE: &str = "en";
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "sl"];
+
+lazy_static! {
+    static ref TEMPLATES: Tera = Tera::new(&format!("{}/**/*", EMAIL_TEMPLATE_DIR.as_str()))
+        .expect("Email templates should be valid");
+}
+
+/// Loads and checks all templates up front, so a broken template fails at startup.
+pub fn init() {
+    lazy_static::initialize(&TEMPLATES);
+}
+
+/// Every email exists as `{locale}/{name}.subject`, `{locale}/{name}.html` and
+/// `{locale}/{name}.txt` in the template directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTemplate {
+    Activation,
+    PasswordReset,
+    AccountLocked,
+    EmailChange,
+    EmailChangeNotice,
+    ClassInvite,
+    JoinRequest,
+    JoinRequestDecision,
+    NotificationDigest,
+}
+
+impl EmailTemplate {
+    fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Activation => "activation",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::AccountLocked => "account_locked",
+            EmailTemplate::EmailChange => "email_change",
+            EmailTemplate::EmailChangeNotice => "email_change_notice",
+            EmailTemplate::ClassInvite => "class_invite",
+            EmailTemplate::JoinRequest => "join_request",
+            EmailTemplate::JoinRequestDecision => "join_request_decision",
+            EmailTemplate::NotificationDigest => "notification_digest",
+        }
+    }
+
+    /// Placeholder values used by the admin preview.
+    pub fn sample_context(&self) -> Context {
+        let value = match self {
+            EmailTemplate::Activation => json!({
+                "username": "jane.doe",
+                "link": "https://example.com/api/v1/user/activate/token",
+                "expires_in_hours": 48,
+            }),
+            EmailTemplate::PasswordReset => json!({
+                "username": "jane.doe",
+                "link": "https://example.com/password-reset/token",
+                "expires_in_minutes": 60,
+                "expires_at": "January 5, 2024, 2:05 PM CET",
+            }),
+            EmailTemplate::AccountLocked => json!({
+                "username": "jane.doe",
+                "link": "https://example.com/api/v1/user/unlock/token",
+                "locked_for_minutes": 30,
+                "locked_until": "January 5, 2024, 2:05 PM CET",
+            }),
+            EmailTemplate::EmailChange => json!({
+                "username": "jane.doe",
+                "new_email": "jane.doe@example.org",
+                "link": "https://example.com/api/v1/user/confirm-email/token",
+                "expires_in_hours": 24,
+            }),
+            EmailTemplate::EmailChangeNotice => json!({
+                "username": "jane.doe",
+                "new_email": "jane.doe@example.org",
+            }),
+            EmailTemplate::ClassInvite => json!({
+                "inviter": "John Smith",
+                "class_name": "Mathematics 101",
+                "link": "https://example.com/invite/token",
+                "code": "K7QX-M2TB",
+            }),
+            EmailTemplate::JoinRequest => json!({
+                "username": "john.smith",
+                "requester": "Jane Doe",
+                "class_name": "Mathematics 101",
+                "note": "I am in your Tuesday group.",
+                "link": "https://example.com/class/id",
+            }),
+            EmailTemplate::JoinRequestDecision => json!({
+                "username": "jane.doe",
+                "class_name": "Mathematics 101",
+                "approved": true,
+                "link": "https://example.com/class/id",
+            }),
+            EmailTemplate::NotificationDigest => json!({
+                "username": "jane.doe",
+                "notifications": [
+                    { "title": "Mathematics 101", "body": "New assignment: Fractions" },
+                    { "title": "Physics", "body": "3 new messages in #general" },
+                ],
+                "link": "https://example.com",
+            }),
+        };
+
+        Context::from_value(value).expect("Sample context is an object")
+    }
+}
+
+impl std::str::FromStr for EmailTemplate {
+    type Err = ();
+    fn from_str(input: &str) -> Result<EmailTemplate, Self::Err> {
+        match input {
+            "activation" => Ok(Self::Activation),
+            "password_reset" => Ok(Self::PasswordReset),
+            "account_locked" => Ok(Self::AccountLocked),
+            "email_change" => Ok(Self::EmailChange),
+            "email_change_notice" => Ok(Self::EmailChangeNotice),
+            "class_invite" => Ok(Self::ClassInvite),
+            "join_request" => Ok(Self::JoinRequest),
+            "join_request_decision" => Ok(Self::JoinRequestDecision),
+            "notification_digest" => Ok(Self::NotificationDigest),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Maps tags like `sl-SI` to their language and falls back to the default locale
+/// for anything that has no templates.
+pub fn resolve_locale(locale: &str) -> &'static str {
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    SUPPORTED_LOCALES
+        .iter()
+        .find(|l| l.eq_ignore_ascii_case(language))
+        .unwrap_or(&DEFAULT_LOCALE)
+}
+
+pub fn render(
+    template: EmailTemplate,
+    locale: &str,
+    to: String,
+    context: &Context,
+) -> Result<Email, AppError> {
+    let locale = resolve_locale(locale);
+    let mut context = context.clone();
+    context.insert("locale", locale);
+
+    let path = format!("{}/{}", locale, template.name());
+    let subject = TEMPLATES.render(&format!("{path}.subject"), &context)?;
+
+    Ok(Email {
+        to,
+        subject: subject.trim().to_owned(),
+        html_body: TEMPLATES.render(&format!("{path}.html"), &context)?,
+        text_body: TEMPLATES.render(&format!("{path}.txt"), &context)?,
+    })
+}
@@ -5,7 +5,10 @@ use axum::{
     Json,
 };
 use deadpool_redis::{redis, PoolError};
-use sea_orm::{error::DbErr, TransactionError};
+use sea_orm::{
+    error::{DbErr, RuntimeErr, SqlxError},
+    TransactionError,
+};
 use serde_json::json;
 use std::{
     fmt::{Display, Formatter},
@@ -118,7 +121,22 @@ impl ErrorExtensions for AppError {
                 e.set("parameter", *parameter);
                 e.set("given_value", given_value.clone());
             }
+            ErrorKind::User(UserError::PolicyViolation {
+                parameter,
+                violations,
+            }) => {
+                e.set("parameter", *parameter);
+                e.set("violations", violations.clone());
+            }
+            ErrorKind::User(UserError::AlreadyTaken {
+                parameter,
+                given_value,
+            }) => {
+                e.set("parameter", *parameter);
+                e.set("given_value", given_value.clone());
+            }
             ErrorKind::User(UserError::FileTooLarge) => {}
+            ErrorKind::User(UserError::TooManyRequests) => {}
             ErrorKind::Internal(_) => {}
         })
     }
@@ -140,8 +158,13 @@ pub enum InternalError {
     Redis(redis::RedisError),
     RedisPool(PoolError),
     Email(lettre::transport::smtp::Error),
+    EmailFile(lettre::transport::file::Error),
+    EmailAddress(lettre::address::AddressError),
+    EmailMessage(lettre::error::Error),
     Io(std::io::Error),
     S3(s3::error::S3Error),
+    Http(reqwest::Error),
+    Template(tera::Error),
     Other,
 }
 
@@ -156,8 +179,13 @@ impl std::fmt::Display for InternalError {
             InternalError::Redis(err) => write!(f, "Redis error: {err}"),
             InternalError::RedisPool(err) => write!(f, "Redis pool error: {err}"),
             InternalError::Email(err) => write!(f, "Email error: {err}"),
+            InternalError::EmailFile(err) => write!(f, "Email error: {err}"),
+            InternalError::EmailAddress(err) => write!(f, "Email address error: {err}"),
+            InternalError::EmailMessage(err) => write!(f, "Email error: {err}"),
             InternalError::Io(err) => write!(f, "IO error: {err}"),
             InternalError::S3(err) => write!(f, "S3 error: {err}"),
+            InternalError::Http(err) => write!(f, "HTTP error: {err}"),
+            InternalError::Template(err) => write!(f, "Template error: {err}"),
             InternalError::Other => write!(f, "Other error"),
         }
     }
@@ -169,7 +197,18 @@ pub enum UserError {
         parameter: &'static str,
         given_value: String,
     },
+    /// Like `BadInput`, but names the rules the value broke instead of echoing it back.
+    PolicyViolation {
+        parameter: &'static str,
+        violations: Vec<&'static str>,
+    },
+    /// The value is unique and already belongs to someone else.
+    AlreadyTaken {
+        parameter: &'static str,
+        given_value: String,
+    },
     FileTooLarge,
+    TooManyRequests,
 }
 
 impl std::fmt::Display for UserError {
@@ -183,11 +222,44 @@ impl std::fmt::Display for UserError {
                 "Bad input for parameter `{}` with value `{}`",
                 parameter, given_value
             ),
+            UserError::PolicyViolation {
+                parameter,
+                violations,
+            } => write!(
+                f,
+                "Parameter `{}` violates: {}",
+                parameter,
+                violations.join(", ")
+            ),
+            UserError::AlreadyTaken {
+                parameter,
+                given_value,
+            } => write!(
+                f,
+                "Value `{}` of parameter `{}` is already taken",
+                given_value, parameter
+            ),
             UserError::FileTooLarge => write!(f, "File too large"),
+            UserError::TooManyRequests => write!(f, "Too many requests"),
         }
     }
 }
 
+/// Name of the violated constraint when `err` comes from a unique constraint.
+pub fn unique_violation(err: &DbErr) -> Option<String> {
+    let (DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(err)))
+    | DbErr::Query(RuntimeErr::SqlxError(SqlxError::Database(err)))) = err
+    else {
+        return None;
+    };
+
+    // 23505 is `unique_violation` in Postgres
+    if err.code().as_deref() != Some("23505") {
+        return None;
+    }
+    err.constraint().map(str::to_owned)
+}
+
 impl From<DbErr> for AppError {
     fn from(inner: DbErr) -> Self {
         AppError {
@@ -281,6 +353,33 @@ impl From<lettre::transport::smtp::Error> for AppError {
     }
 }
 
+impl From<lettre::transport::file::Error> for AppError {
+    fn from(inner: lettre::transport::file::Error) -> Self {
+        AppError {
+            message: INTERNAL_ERROR_MSG.to_owned(),
+            kind: ErrorKind::Internal(InternalError::EmailFile(inner)),
+        }
+    }
+}
+
+impl From<lettre::address::AddressError> for AppError {
+    fn from(inner: lettre::address::AddressError) -> Self {
+        AppError {
+            message: INTERNAL_ERROR_MSG.to_owned(),
+            kind: ErrorKind::Internal(InternalError::EmailAddress(inner)),
+        }
+    }
+}
+
+impl From<lettre::error::Error> for AppError {
+    fn from(inner: lettre::error::Error) -> Self {
+        AppError {
+            message: INTERNAL_ERROR_MSG.to_owned(),
+            kind: ErrorKind::Internal(InternalError::EmailMessage(inner)),
+        }
+    }
+}
+
 impl From<std::io::Error> for AppError {
     fn from(inner: std::io::Error) -> Self {
         AppError {
@@ -299,6 +398,24 @@ impl From<s3::error::S3Error> for AppError {
     }
 }
 
+impl From<reqwest::Error> for AppError {
+    fn from(inner: reqwest::Error) -> Self {
+        AppError {
+            message: INTERNAL_ERROR_MSG.to_owned(),
+            kind: ErrorKind::Internal(InternalError::Http(inner)),
+        }
+    }
+}
+
+impl From<tera::Error> for AppError {
+    fn from(inner: tera::Error) -> Self {
+        AppError {
+            message: INTERNAL_ERROR_MSG.to_owned(),
+            kind: ErrorKind::Internal(InternalError::Template(inner)),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let AppError { message, kind } = self;
@@ -306,6 +423,7 @@ impl IntoResponse for AppError {
         let status = match kind {
             ErrorKind::Auth => StatusCode::UNAUTHORIZED,
             ErrorKind::NotFound { .. } => StatusCode::NOT_FOUND,
+            ErrorKind::User(UserError::TooManyRequests) => StatusCode::TOO_MANY_REQUESTS,
             ErrorKind::User(_) => StatusCode::BAD_REQUEST,
             ErrorKind::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+};
+use async_graphql::parser::types::{DocumentOperations, ExecutableDocument, OperationType};
+use async_graphql::{Pos, Request, ServerResult, Variables};
+use async_trait::async_trait;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{AppError, Claims, ACCESS_TOKEN_TTL_MINUTES};
+use crate::{api::UserRepo, SECRET};
+
+/// Impersonation tokens can't be refreshed and are revoked like access tokens,
+/// so they must not outlive them.
+pub const IMPERSONATION_TTL_MINUTES: i64 = ACCESS_TOKEN_TTL_MINUTES;
+
+/// The admin acting as the subject of the token, as in the `act` claim of RFC 8693.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+}
+
+/// Creates an access token for `user_id` that also names the admin behind it, together
+/// with its claims. `session_id` identifies the impersonation in the audit log.
+pub fn create_token(
+    admin_id: Uuid,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<(String, Claims), AppError> {
+    let claims = Claims {
+        sub: user_id.to_string(),
+        sid: session_id.to_string(),
+        exp: (Utc::now() + chrono::Duration::minutes(IMPERSONATION_TTL_MINUTES)).timestamp(),
+        scope: None,
+        act: Some(Actor {
+            sub: admin_id.to_string(),
+        }),
+        two_factor_setup: false,
+    };
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(SECRET.as_ref()),
+    )?;
+
+    Ok((token, claims))
+}
+
+/// Writes `action` to the audit log when the claims belong to an impersonation token.
+pub async fn record(
+    data_loader: &DataLoader<DatabaseConnection>,
+    claims: &Claims,
+    action: &str,
+) -> Result<(), AppError> {
+    let Some(actor) = &claims.act else {
+        return Ok(());
+    };
+
+    UserRepo::create_impersonation_log(
+        data_loader,
+        ::entity::impersonation_log::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            session_id: Set(Uuid::parse_str(&claims.sid)?),
+            admin_id: Set(Uuid::parse_str(&actor.sub)?),
+            user_id: Set(Uuid::parse_str(&claims.sub)?),
+            action: Set(action.to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Schema extension writing every operation run with an impersonation token to the audit
+/// log, as its type and name like `mutation UpdateUser`.
+pub struct ImpersonationAudit;
+
+impl ExtensionFactory for ImpersonationAudit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ImpersonationAuditExtension::default())
+    }
+}
+
+#[derive(Default)]
+struct ImpersonationAuditExtension {
+    /// Picks the operation to run when the document has more than one.
+    operation_name: Mutex<Option<String>>,
+}
+
+#[async_trait]
+impl Extension for ImpersonationAuditExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        *self.operation_name.lock().expect("lock is not poisoned") = request.operation_name.clone();
+        next.run(ctx, request).await
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        // HTTP requests carry the claims as query data, websockets as session data
+        let claims = ctx.data_opt::<Option<Claims>>();
+        let data_loader = ctx.data_opt::<DataLoader<DatabaseConnection>>();
+        if let (Some(Some(claims)), Some(data_loader)) = (claims, data_loader) {
+            let operation_name = self
+                .operation_name
+                .lock()
+                .expect("lock is not poisoned")
+                .clone();
+            let action = describe_operation(&document, operation_name.as_deref());
+            record(data_loader, claims, &action)
+                .await
+                .map_err(|err| async_graphql::Error::from(err).into_server_error(Pos::default()))?;
+        }
+
+        Ok(document)
+    }
+}
+
+/// The type and name of the operation the request runs, like `query Me`.
+fn describe_operation(document: &ExecutableDocument, operation_name: Option<&str>) -> String {
+    let operation = match &document.operations {
+        DocumentOperations::Single(operation) => Some((None, operation)),
+        DocumentOperations::Multiple(operations) => match operation_name {
+            Some(operation_name) => operations
+                .iter()
+                .find(|(name, _)| name.as_str() == operation_name),
+            None if operations.len() == 1 => operations.iter().next(),
+            None => None,
+        }
+        .map(|(name, operation)| (Some(name.as_str()), operation)),
+    };
+
+    let Some((name, operation)) = operation else {
+        return "unknown operation".to_string();
+    };
+    let ty = match operation.node.ty {
+        OperationType::Query => "query",
+        OperationType::Mutation => "mutation",
+        OperationType::Subscription => "subscription",
+    };
+    format!("{ty} {}", name.unwrap_or("anonymous"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::parser::parse_query;
+
+    #[test]
+    fn operations_are_described_by_type_and_name() {
+        let describe = |query: &str, operation_name: Option<&str>| {
+            let document = parse_query(query).expect("query should parse");
+            describe_operation(&document, operation_name)
+        };
+
+        assert_eq!(describe("{ me { id } }", None), "query anonymous");
+        assert_eq!(
+            describe("mutation UpdateUser { updateUser { id } }", None),
+            "mutation UpdateUser"
+        );
+        let document = "query Me { me { id } } subscription Messages { messageCreated { id } }";
+        assert_eq!(
+            describe(document, Some("Messages")),
+            "subscription Messages"
+        );
+        assert_eq!(describe(document, None), "unknown operation");
+    }
+}
@@ -0,0 +1,155 @@
+//! Join codes and the rules deciding whether an invite can still be redeemed.
+
+use chrono::NaiveDateTime;
+use data_encoding::BASE32_NOPAD;
+use deadpool_redis::Pool;
+use entity::invite;
+use uuid::Uuid;
+
+use super::{rate_limit, AppError, UserError};
+
+/// Addresses a single `createEmailInvites` call can invite.
+pub const MAX_EMAIL_INVITES: usize = 50;
+/// `createEmailInvites` calls a user can make per hour.
+pub const EMAIL_INVITE_LIMIT: u32 = 10;
+/// Join codes a user can try per hour, to keep codes from being guessed.
+pub const JOIN_CODE_LIMIT: u32 = 20;
+
+/// Creates a join code like `K7QX-M2TB`. The base32 alphabet leaves out 0, 1, 8 and 9,
+/// which are easily mistaken for letters when typed.
+pub fn generate_join_code() -> String {
+    let bytes: [u8; 5] = rand::random();
+    let code = BASE32_NOPAD.encode(&bytes);
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+/// Accepts codes typed in lowercase, with spaces or without the dash.
+pub fn normalize_join_code(code: &str) -> String {
+    let code = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+    if code.len() == 8 {
+        format!("{}-{}", &code[..4], &code[4..])
+    } else {
+        code
+    }
+}
+
+/// How many times the invite can be redeemed in total, `None` if there is no limit.
+pub fn max_uses(invite: &invite::Model) -> Option<i32> {
+    if invite.multiuse {
+        invite.max_uses
+    } else {
+        Some(1)
+    }
+}
+
+/// Counts a join code attempt of the user against [`JOIN_CODE_LIMIT`].
+pub async fn throttle_join_code(redis_pool: &Pool, user_id: Uuid) -> Result<(), AppError> {
+    let key = format!("join_code:{user_id}");
+    if !rate_limit::hit(redis_pool, &key, JOIN_CODE_LIMIT, 60 * 60).await? {
+        return Err(AppError::user(
+            "Too many join codes tried",
+            UserError::TooManyRequests,
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that `email` can use the invite to join `class_id` at `now`.
+pub fn check_invite(
+    invite: &invite::Model,
+    class_id: Uuid,
+    email: &str,
+    now: NaiveDateTime,
+) -> Result<(), AppError> {
+    let problem = if invite.class_id != class_id {
+        Some("Invite belongs to another class")
+    } else if invite.revoked_at.is_some() {
+        Some("Invite was revoked")
+    } else if invite.expires_at.map(|e| e < now).unwrap_or(false) {
+        Some("Invite has expired")
+    } else if max_uses(invite)
+        .map(|max| invite.uses >= max)
+        .unwrap_or(false)
+    {
+        Some("Invite has been used up")
+    } else if matches!(&invite.email, Some(to) if !to.eq_ignore_ascii_case(email)) {
+        Some("Invite was sent to another email address")
+    } else {
+        None
+    };
+
+    match problem {
+        Some(problem) => Err(AppError::user(
+            problem,
+            UserError::BadInput {
+                parameter: "invite_id",
+                given_value: invite.id.to_string(),
+            },
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn join_codes_survive_sloppy_typing() {
+        let code = generate_join_code();
+        assert_eq!(code.len(), 9);
+        assert_eq!(
+            normalize_join_code(&code.to_lowercase().replace('-', " ")),
+            code
+        );
+    }
+
+    #[test]
+    fn invites_are_checked() {
+        let now = Utc::now().naive_utc();
+        let class_id = Uuid::new_v4();
+        let invite = invite::Model {
+            id: Uuid::new_v4(),
+            expires_at: Some(now + chrono::Duration::days(1)),
+            class_id,
+            multiuse: true,
+            code: None,
+            email: Some("Jane@example.com".into()),
+            max_uses: Some(2),
+            uses: 1,
+            created_by: None,
+            created_at: now,
+            revoked_by: None,
+            revoked_at: None,
+        };
+        assert!(check_invite(&invite, class_id, "jane@example.com", now).is_ok());
+
+        for unusable in [
+            invite::Model {
+                uses: 2,
+                ..invite.clone()
+            },
+            invite::Model {
+                multiuse: false,
+                ..invite.clone()
+            },
+            invite::Model {
+                revoked_at: Some(now),
+                ..invite.clone()
+            },
+            invite::Model {
+                expires_at: Some(now - chrono::Duration::seconds(1)),
+                ..invite.clone()
+            },
+        ] {
+            assert!(check_invite(&unusable, class_id, "jane@example.com", now).is_err());
+        }
+        assert!(check_invite(&invite, class_id, "john@example.com", now).is_err());
+        assert!(check_invite(&invite, Uuid::new_v4(), "jane@example.com", now).is_err());
+    }
+}
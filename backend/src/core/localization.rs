@@ -0,0 +1,77 @@
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use entity::user;
+use entity::user_preferences::HourCycle;
+
+use super::email_templates::resolve_locale;
+
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// Accepts IANA names like `Europe/Ljubljana`.
+pub fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.parse::<Tz>().is_ok()
+}
+
+/// Formats a UTC timestamp the way a reader in `timezone` and `locale` expects it.
+/// Unknown timezones fall back to UTC, `hour_cycle` to the convention of the locale.
+pub fn format_datetime(
+    at: NaiveDateTime,
+    timezone: &str,
+    locale: &str,
+    hour_cycle: Option<HourCycle>,
+) -> String {
+    let timezone = timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+    let local = timezone.from_utc_datetime(&at);
+
+    let locale = resolve_locale(locale);
+    let hour_cycle = hour_cycle.unwrap_or(match locale {
+        "en" => HourCycle::H12,
+        _ => HourCycle::H24,
+    });
+    let date = match locale {
+        "sl" => "%-d. %-m. %Y",
+        _ => "%B %-d, %Y",
+    };
+    let time = match hour_cycle {
+        HourCycle::H12 => "%-I:%M %p",
+        HourCycle::H24 => "%H:%M",
+    };
+
+    local.format(&format!("{date}, {time} %Z")).to_string()
+}
+
+/// [`format_datetime`] with the timezone, locale and hour cycle of `user`.
+pub fn format_datetime_for(at: NaiveDateTime, user: &user::Model) -> String {
+    format_datetime(
+        at,
+        &user.timezone,
+        &user.locale,
+        user.preferences.display.hour_cycle,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_in_timezone_and_locale() {
+        let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 5)
+            .unwrap()
+            .and_hms_opt(13, 5, 0)
+            .unwrap();
+
+        assert_eq!(
+            format_datetime(at, "Europe/Ljubljana", "en", None),
+            "January 5, 2024, 2:05 PM CET"
+        );
+        assert_eq!(
+            format_datetime(at, "Europe/Ljubljana", "sl-SI", None),
+            "5. 1. 2024, 14:05 CET"
+        );
+        assert_eq!(
+            format_datetime(at, "Mars/Olympus", "en", Some(HourCycle::H24)),
+            "January 5, 2024, 13:05 UTC"
+        );
+    }
+}
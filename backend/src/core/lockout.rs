@@ -0,0 +1,237 @@
+use std::net::IpAddr;
+
+use chrono::Utc;
+use deadpool_redis::{
+    redis::{self, AsyncCommands},
+    Pool,
+};
+use uuid::Uuid;
+
+use super::{generate_opaque_token, hash_opaque_token, AppError, UserError};
+
+const LOGIN_FAILURES: &str = "login_failures";
+const LOCKOUT: &str = "login_lockout";
+const LOCKOUTS: &str = "login_lockouts";
+const UNLOCK_TOKEN: &str = "login_unlock";
+
+/// Failed logins are counted over this sliding window.
+pub const FAILURE_WINDOW_SECONDS: i64 = 15 * 60;
+/// Failures per username after which every further attempt has to wait.
+const DELAY_AFTER_FAILURES: u32 = 3;
+const MAX_DELAY_SECONDS: i64 = 60;
+/// Failures per username that lock the account.
+pub const LOCKOUT_THRESHOLD: u32 = 10;
+pub const LOCKOUT_MINUTES: i64 = 30;
+/// Failures per client address, across all usernames, before the address is throttled.
+const IP_FAILURE_LIMIT: u32 = 50;
+
+/// Rejects the attempt while the username or the client address is throttled.
+pub async fn check_login_allowed(
+    redis_pool: &Pool,
+    username: &str,
+    ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    if let Some(ip) = ip {
+        let (failures, _) = failures_in_window(redis_pool, &ip_key(ip)).await?;
+        if failures >= IP_FAILURE_LIMIT {
+            tracing::warn!(
+                target: "backend::security",
+                event = "login_ip_throttled",
+                %ip,
+                failures,
+            );
+            return Err(AppError::user(
+                "Too many failed login attempts",
+                UserError::TooManyRequests,
+            ));
+        }
+    }
+
+    let (failures, last_failure) = failures_in_window(redis_pool, &username_key(username)).await?;
+    if let Some(last_failure) = last_failure {
+        let retry_at = last_failure + delay_seconds(failures) * 1000;
+        let wait_ms = retry_at - Utc::now().timestamp_millis();
+        if wait_ms > 0 {
+            return Err(AppError::user(
+                format!(
+                    "Too many failed login attempts, try again in {} seconds",
+                    (wait_ms + 999) / 1000
+                ),
+                UserError::TooManyRequests,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Doubles the wait with every failure past the free ones.
+fn delay_seconds(failures: u32) -> i64 {
+    if failures < DELAY_AFTER_FAILURES {
+        return 0;
+    }
+    let exponent = (failures - DELAY_AFTER_FAILURES).min(6);
+    (1i64 << exponent).min(MAX_DELAY_SECONDS)
+}
+
+/// Records a failed login and returns the number of failures for the username
+/// in the current window.
+pub async fn record_login_failure(
+    redis_pool: &Pool,
+    username: &str,
+    ip: Option<IpAddr>,
+) -> Result<u32, AppError> {
+    if let Some(ip) = ip {
+        record_failure(redis_pool, &ip_key(ip)).await?;
+    }
+    record_failure(redis_pool, &username_key(username)).await
+}
+
+pub async fn clear_login_failures(redis_pool: &Pool, username: &str) -> Result<(), AppError> {
+    let mut conn = redis_pool.get().await?;
+    let _: () = conn.del(username_key(username)).await?;
+    Ok(())
+}
+
+pub async fn is_locked(redis_pool: &Pool, user_id: Uuid) -> Result<bool, AppError> {
+    let mut conn = redis_pool.get().await?;
+    let locked: bool = conn.exists(format!("{}:{}", LOCKOUT, user_id)).await?;
+    Ok(locked)
+}
+
+/// Locks the account for `LOCKOUT_MINUTES` and returns a token that lifts the lock early.
+pub async fn lock_account(redis_pool: &Pool, user_id: Uuid) -> Result<String, AppError> {
+    let ttl = LOCKOUT_MINUTES * 60;
+    let locked_until = Utc::now().timestamp() + ttl;
+    let token = generate_opaque_token();
+
+    let mut conn = redis_pool.get().await?;
+    let _: () = redis::pipe()
+        .atomic()
+        .set_ex(
+            format!("{}:{}", LOCKOUT, user_id),
+            locked_until,
+            ttl as usize,
+        )
+        .ignore()
+        .zadd(LOCKOUTS, user_id.to_string(), locked_until)
+        .ignore()
+        .set_ex(
+            format!("{}:{}", UNLOCK_TOKEN, hash_opaque_token(&token)),
+            user_id.to_string(),
+            ttl as usize,
+        )
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(token)
+}
+
+/// Lifts the lock and forgets the failures that led to it.
+pub async fn unlock_account(
+    redis_pool: &Pool,
+    user_id: Uuid,
+    username: &str,
+) -> Result<(), AppError> {
+    let mut conn = redis_pool.get().await?;
+    let _: () = redis::pipe()
+        .atomic()
+        .del(format!("{}:{}", LOCKOUT, user_id))
+        .ignore()
+        .zrem(LOCKOUTS, user_id.to_string())
+        .ignore()
+        .del(username_key(username))
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the user the unlock token was issued for. Every token can be used only once.
+pub async fn take_unlock_token(redis_pool: &Pool, token: &str) -> Result<Uuid, AppError> {
+    let key = format!("{}:{}", UNLOCK_TOKEN, hash_opaque_token(token));
+    let mut conn = redis_pool.get().await?;
+    let user_id: Option<String> = conn.get(&key).await?;
+    let _: () = conn.del(&key).await?;
+
+    user_id
+        .and_then(|id| Uuid::parse_str(&id).ok())
+        .ok_or_else(|| AppError::auth("Unknown or expired unlock token"))
+}
+
+/// Currently locked accounts with the unix time their lock runs out.
+pub async fn locked_accounts(redis_pool: &Pool) -> Result<Vec<(Uuid, i64)>, AppError> {
+    let mut conn = redis_pool.get().await?;
+    let now = Utc::now().timestamp();
+    let (lockouts,): (Vec<(String, i64)>,) = redis::pipe()
+        .atomic()
+        .zrembyscore(LOCKOUTS, "-inf", now)
+        .ignore()
+        .zrange_withscores(LOCKOUTS, 0, -1)
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(lockouts
+        .into_iter()
+        .filter_map(|(id, until)| Some((Uuid::parse_str(&id).ok()?, until)))
+        .collect())
+}
+
+fn username_key(username: &str) -> String {
+    format!("{}:user:{}", LOGIN_FAILURES, username.to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("{}:ip:{}", LOGIN_FAILURES, ip)
+}
+
+/// Adds a failure to the sliding window kept in a sorted set scored by time.
+async fn record_failure(redis_pool: &Pool, key: &str) -> Result<u32, AppError> {
+    let now = Utc::now().timestamp_millis();
+    let mut conn = redis_pool.get().await?;
+    let (failures,): (u32,) = redis::pipe()
+        .atomic()
+        .zrembyscore(key, "-inf", now - FAILURE_WINDOW_SECONDS * 1000)
+        .ignore()
+        .zadd(key, format!("{}:{}", now, Uuid::new_v4()), now)
+        .ignore()
+        .expire(key, FAILURE_WINDOW_SECONDS as usize)
+        .ignore()
+        .zcard(key)
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(failures)
+}
+
+/// Number of failures in the window and the time of the latest one in milliseconds.
+async fn failures_in_window(redis_pool: &Pool, key: &str) -> Result<(u32, Option<i64>), AppError> {
+    let now = Utc::now().timestamp_millis();
+    let mut conn = redis_pool.get().await?;
+    let (failures, latest): (u32, Vec<(String, i64)>) = redis::pipe()
+        .atomic()
+        .zrembyscore(key, "-inf", now - FAILURE_WINDOW_SECONDS * 1000)
+        .ignore()
+        .zcard(key)
+        .zrevrange_withscores(key, 0, 0)
+        .query_async(&mut conn)
+        .await?;
+
+    Ok((failures, latest.first().map(|(_, at)| *at)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_after_free_attempts() {
+        assert_eq!(delay_seconds(0), 0);
+        assert_eq!(delay_seconds(DELAY_AFTER_FAILURES - 1), 0);
+        assert_eq!(delay_seconds(DELAY_AFTER_FAILURES), 1);
+        assert_eq!(delay_seconds(DELAY_AFTER_FAILURES + 3), 8);
+        assert_eq!(delay_seconds(LOCKOUT_THRESHOLD + 20), MAX_DELAY_SECONDS);
+    }
+}
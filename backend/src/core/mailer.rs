@@ -0,0 +1,218 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use lettre::{
+    message::MultiPart, transport::smtp::authentication::Credentials, AsyncFileTransport,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::AppError;
+use crate::{
+    MAIL_DIR, MAIL_FROM, MAIL_HOST, MAIL_PASSWORD, MAIL_PORT, MAIL_TLS, MAIL_TRANSPORT,
+    MAIL_USERNAME,
+};
+
+pub type DynMailer = Arc<dyn Mailer>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), AppError>;
+}
+
+/// Builds the transport selected with `MAIL_TRANSPORT`. Debug builds write emails to
+/// `MAIL_DIR` unless told otherwise, release builds use SMTP.
+pub fn from_env() -> DynMailer {
+    let default_transport = if cfg!(debug_assertions) {
+        "file"
+    } else {
+        "smtp"
+    };
+
+    match MAIL_TRANSPORT.as_deref().unwrap_or(default_transport) {
+        "smtp" => Arc::new(SmtpMailer::from_env()),
+        "file" => Arc::new(FileMailer::new(MAIL_DIR.as_str())),
+        "memory" => Arc::new(InMemoryMailer::default()),
+        other => panic!("Unknown MAIL_TRANSPORT `{other}`"),
+    }
+}
+
+fn build_message(email: Email) -> Result<Message, AppError> {
+    let message = Message::builder()
+        .from(MAIL_FROM.parse()?)
+        .to(email.to.parse()?)
+        .subject(email.subject)
+        .multipart(MultiPart::alternative_plain_html(
+            email.text_body,
+            email.html_body,
+        ))?;
+
+    Ok(message)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    StartTls,
+    /// No encryption at all, only meant for local relays.
+    None,
+}
+
+impl std::str::FromStr for TlsMode {
+    type Err = ();
+    fn from_str(input: &str) -> Result<TlsMode, Self::Err> {
+        match input {
+            "tls" => Ok(Self::Tls),
+            "starttls" => Ok(Self::StartTls),
+            "none" => Ok(Self::None),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        tls: TlsMode,
+        credentials: Option<Credentials>,
+    ) -> Self {
+        let mut builder = match tls {
+            TlsMode::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(host).expect("Relay should be valid")
+            }
+            TlsMode::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .expect("Relay should be valid"),
+            TlsMode::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let Some(credentials) = credentials {
+            builder = builder.credentials(credentials);
+        }
+
+        Self {
+            transport: builder.build(),
+        }
+    }
+
+    fn from_env() -> Self {
+        let tls = MAIL_TLS
+            .parse::<TlsMode>()
+            .expect("MAIL_TLS should be one of `tls`, `starttls` or `none`");
+        let credentials = match (MAIL_USERNAME.as_ref(), MAIL_PASSWORD.as_ref()) {
+            (Some(username), Some(password)) => {
+                Some(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => None,
+        };
+
+        Self::new(&MAIL_HOST, *MAIL_PORT, tls, credentials)
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        self.transport.send(build_message(email)?).await?;
+        Ok(())
+    }
+}
+
+/// Writes every email as an `.eml` file, handy for local development.
+pub struct FileMailer {
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str) -> Self {
+        std::fs::create_dir_all(dir).expect("Mail directory should be creatable");
+        Self {
+            transport: AsyncFileTransport::new(dir),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        self.transport.send(build_message(email)?).await?;
+        Ok(())
+    }
+}
+
+/// Keeps sent emails in memory so tests can inspect them.
+#[derive(Default)]
+pub struct InMemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl InMemoryMailer {
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent
+            .lock()
+            .expect("Mailer lock is not poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        // fails the same way the real transports would
+        build_message(email.clone())?;
+        self.sent
+            .lock()
+            .expect("Mailer lock is not poisoned")
+            .push(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn in_memory_mailer_keeps_sent_emails() {
+        let mailer = InMemoryMailer::default();
+        let email = Email {
+            to: "student@example.com".into(),
+            subject: "Account activation".into(),
+            html_body: "<p>Hello</p>".into(),
+            text_body: "Hello".into(),
+        };
+
+        mailer.send(email.clone()).await.unwrap();
+
+        assert_eq!(mailer.sent(), vec![email]);
+    }
+
+    #[tokio::test]
+    async fn invalid_addresses_are_errors() {
+        let mailer = InMemoryMailer::default();
+        let email = Email {
+            to: "not an address".into(),
+            subject: "Account activation".into(),
+            html_body: "<p>Hello</p>".into(),
+            text_body: "Hello".into(),
+        };
+
+        assert!(mailer.send(email).await.is_err());
+        assert!(mailer.sent().is_empty());
+    }
+}
@@ -1,6 +1,23 @@
 pub mod auth;
+pub mod calendar;
+pub mod email_templates;
 pub mod errors;
+pub mod impersonation;
+pub mod invites;
+pub mod localization;
+pub mod lockout;
+pub mod mailer;
+pub mod oidc;
+pub mod pagination;
+pub mod password_policy;
+pub mod personal_access_token;
+pub mod rate_limit;
+pub mod recommendations;
+pub mod search;
+pub mod tags;
+pub mod totp;
 
+use async_graphql::MaybeUndefined;
 pub use auth::*;
 pub use errors::*;
 use sea_orm::{ActiveValue::NotSet, Set};
@@ -11,3 +28,17 @@ pub fn option_to_active_value<T: Into<sea_orm::Value>>(opt: Option<T>) -> sea_or
         None => NotSet,
     }
 }
+
+/// Leaves undefined values untouched and clears the column for `null`.
+pub fn maybe_undefined_to_active_value<T>(
+    value: MaybeUndefined<T>,
+) -> sea_orm::ActiveValue<Option<T>>
+where
+    Option<T>: Into<sea_orm::Value>,
+{
+    match value {
+        MaybeUndefined::Value(v) => Set(Some(v)),
+        MaybeUndefined::Null => Set(None),
+        MaybeUndefined::Undefined => NotSet,
+    }
+}
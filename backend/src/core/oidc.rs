@@ -0,0 +1,441 @@
+use base64::Engine;
+use deadpool_redis::{redis::AsyncCommands, Pool};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{generate_opaque_token, AppError, InternalError};
+use crate::{HOST_URL, OIDC_CLIENT_ID, OIDC_CLIENT_SECRET, OIDC_ISSUER_URL};
+
+const AUTHORIZATION_REQUEST: &str = "oidc_authorization_request";
+const AUTHORIZATION_REQUEST_TTL_MINUTES: usize = 10;
+/// Binds the login state to the browser that started the login.
+pub const STATE_COOKIE: &str = "oidc_state";
+const STATE_COOKIE_PATH: &str = "/api/v1/user/oidc";
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The part of the authorization request that has to survive the redirect to the IdP.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+impl AuthorizationRequest {
+    pub fn generate() -> Self {
+        Self {
+            code_verifier: generate_opaque_token(),
+            nonce: generate_opaque_token(),
+        }
+    }
+
+    /// S256 code challenge as described in RFC 7636.
+    pub fn code_challenge(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub nonce: Option<String>,
+    pub preferred_username: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub locale: Option<String>,
+}
+
+pub struct OidcClient {
+    http: reqwest::Client,
+    metadata: ProviderMetadata,
+    client_id: String,
+    client_secret: Option<String>,
+}
+
+impl OidcClient {
+    /// Fetches the provider metadata of the configured issuer. Discovery runs on every login
+    /// so that changes on the IdP side don't require a restart.
+    pub async fn discover() -> Result<Self, AppError> {
+        let (issuer, client_id) = match (OIDC_ISSUER_URL.as_ref(), OIDC_CLIENT_ID.as_ref()) {
+            (Some(issuer), Some(client_id)) => (issuer.trim_end_matches('/'), client_id),
+            _ => {
+                return Err(AppError::internal(
+                    "OpenID Connect login is not configured",
+                    InternalError::Other,
+                ))
+            }
+        };
+
+        Self::discover_issuer(issuer, client_id, OIDC_CLIENT_SECRET.clone()).await
+    }
+
+    async fn discover_issuer(
+        issuer: &str,
+        client_id: &str,
+        client_secret: Option<String>,
+    ) -> Result<Self, AppError> {
+        let http = reqwest::Client::new();
+        let metadata = http
+            .get(format!("{issuer}/.well-known/openid-configuration"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ProviderMetadata>()
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(AppError::internal(
+                "OpenID Connect issuer does not match discovery document",
+                InternalError::Other,
+            ));
+        }
+
+        Ok(Self {
+            http,
+            metadata,
+            client_id: client_id.to_owned(),
+            client_secret,
+        })
+    }
+
+    pub fn redirect_url() -> String {
+        format!("{}/api/v1/user/oidc/callback", HOST_URL.as_str())
+    }
+
+    pub fn authorization_url(&self, state: &str, request: &AuthorizationRequest) -> String {
+        let url = reqwest::Url::parse_with_params(
+            &self.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("scope", "openid email profile"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &Self::redirect_url()),
+                ("state", state),
+                ("nonce", &request.nonce),
+                ("code_challenge", &request.code_challenge()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .expect("Authorization endpoint should be a valid url");
+
+        url.into()
+    }
+
+    /// Redeems the authorization code and returns the claims of the validated ID token.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        request: &AuthorizationRequest,
+    ) -> Result<IdTokenClaims, AppError> {
+        let redirect_url = Self::redirect_url();
+        let mut token_request = self.http.post(&self.metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", &request.code_verifier),
+        ]);
+        if let Some(secret) = &self.client_secret {
+            token_request = token_request.basic_auth(&self.client_id, Some(secret));
+        }
+
+        let response = token_request.send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::auth("Authorization code was rejected"));
+        }
+        let token = response.json::<TokenResponse>().await?;
+
+        self.validate_id_token(&token.id_token, &request.nonce)
+            .await
+    }
+
+    async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let header = decode_header(id_token).map_err(|_| AppError::auth("Malformed ID token"))?;
+        // symmetric algorithms would let anyone holding the client secret forge tokens
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(AppError::auth("Unsupported ID token algorithm"));
+        }
+
+        let jwks = self
+            .http
+            .get(&self.metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        };
+        let jwk = match jwk {
+            Some(jwk) => jwk,
+            None => return Err(AppError::auth("Unknown ID token signing key")),
+        };
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|_| AppError::auth("Invalid ID token"))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AppError::auth("Invalid ID token nonce"));
+        }
+
+        Ok(claims)
+    }
+}
+
+/// `Set-Cookie` value carrying the state for as long as the authorization request is kept.
+pub fn state_cookie(state: &str) -> String {
+    let secure = match HOST_URL.starts_with("https://") {
+        true => "; Secure",
+        false => "",
+    };
+    format!(
+        "{STATE_COOKIE}={state}; Path={STATE_COOKIE_PATH}; Max-Age={}; HttpOnly; SameSite=Lax{secure}",
+        AUTHORIZATION_REQUEST_TTL_MINUTES * 60
+    )
+}
+
+pub fn clear_state_cookie() -> String {
+    format!("{STATE_COOKIE}=; Path={STATE_COOKIE_PATH}; Max-Age=0; HttpOnly; SameSite=Lax")
+}
+
+pub async fn store_authorization_request(
+    redis_pool: &Pool,
+    state: &str,
+    request: &AuthorizationRequest,
+) -> Result<(), AppError> {
+    let mut conn = redis_pool.get().await?;
+    let _: () = conn
+        .set_ex(
+            format!("{}:{}", AUTHORIZATION_REQUEST, state),
+            serde_json::to_string(request).expect("AuthorizationRequest should serialize"),
+            AUTHORIZATION_REQUEST_TTL_MINUTES * 60,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the request started with `state`. Every state can be used only once.
+pub async fn take_authorization_request(
+    redis_pool: &Pool,
+    state: &str,
+) -> Result<AuthorizationRequest, AppError> {
+    let key = format!("{}:{}", AUTHORIZATION_REQUEST, state);
+    let mut conn = redis_pool.get().await?;
+    let request: Option<String> = conn.get(&key).await?;
+    let _: () = conn.del(&key).await?;
+
+    request
+        .and_then(|r| serde_json::from_str(&r).ok())
+        .ok_or_else(|| AppError::auth("Unknown or expired login state"))
+}
+
+/// Identity provider answering discovery, token and JWKS requests, for tests of the login.
+#[cfg(test)]
+pub mod mock_idp {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use chrono::Utc;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    pub const CLIENT_ID: &str = "goelearn";
+    const SIGNING_KEY: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/oidc_signing_key.pem"
+    ));
+    const JWKS: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/oidc_jwks.json"
+    ));
+
+    /// What the IdP keeps between the login and the token request.
+    struct Grant {
+        nonce: String,
+        code_challenge: String,
+        email: String,
+    }
+
+    #[derive(Clone)]
+    pub struct MockIdp {
+        pub issuer: String,
+        grants: Arc<Mutex<HashMap<String, Grant>>>,
+    }
+
+    impl MockIdp {
+        /// Also sets `HOST_URL` when it isn't, as redirect urls are built from it.
+        pub async fn start() -> Self {
+            if std::env::var("HOST_URL").is_err() {
+                std::env::set_var("HOST_URL", "http://localhost:8080");
+            }
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let idp = Self {
+                issuer: format!("http://{}", listener.local_addr().unwrap()),
+                grants: Default::default(),
+            };
+            let app = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                .route("/token", post(token))
+                .route("/jwks", get(jwks))
+                .with_state(idp.clone());
+            let server = axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service());
+            tokio::spawn(server);
+
+            idp
+        }
+
+        /// Logs `email` in at the authorization url and returns the code and state the IdP
+        /// redirects back with.
+        pub fn authorize(&self, authorization_url: &str, email: &str) -> (String, String) {
+            let url = reqwest::Url::parse(authorization_url).unwrap();
+            let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+            let code = generate_opaque_token();
+            self.grants.lock().unwrap().insert(
+                code.clone(),
+                Grant {
+                    nonce: params["nonce"].clone(),
+                    code_challenge: params["code_challenge"].clone(),
+                    email: email.into(),
+                },
+            );
+
+            (code, params["state"].clone())
+        }
+    }
+
+    async fn discovery(State(idp): State<MockIdp>) -> Json<Value> {
+        Json(json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+        }))
+    }
+
+    async fn jwks() -> Json<Value> {
+        Json(serde_json::from_str(JWKS).unwrap())
+    }
+
+    async fn token(
+        State(idp): State<MockIdp>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let grant = form
+            .get("code")
+            .and_then(|code| idp.grants.lock().unwrap().remove(code))
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        let verifier = AuthorizationRequest {
+            code_verifier: form.get("code_verifier").cloned().unwrap_or_default(),
+            nonce: String::new(),
+        };
+        if verifier.code_challenge() != grant.code_challenge {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "iss": idp.issuer,
+            "aud": CLIENT_ID,
+            "sub": format!("mock|{}", grant.email),
+            "iat": now,
+            "exp": now + 300,
+            "email": grant.email,
+            "email_verified": true,
+            "nonce": grant.nonce,
+        });
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test".into());
+        let key = EncodingKey::from_rsa_pem(SIGNING_KEY.as_bytes()).unwrap();
+        let id_token = encode(&header, &claims, &key).unwrap();
+
+        Ok(Json(
+            json!({ "id_token": id_token, "token_type": "Bearer" }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock_idp::{MockIdp, CLIENT_ID};
+    use super::*;
+
+    #[tokio::test]
+    async fn codes_are_exchanged_for_validated_claims() {
+        let idp = MockIdp::start().await;
+        let client = OidcClient::discover_issuer(&idp.issuer, CLIENT_ID, None)
+            .await
+            .unwrap();
+
+        let request = AuthorizationRequest::generate();
+        let url = client.authorization_url("state", &request);
+        let (code, state) = idp.authorize(&url, "ana@example.com");
+        assert_eq!(state, "state");
+
+        let claims = client.exchange_code(&code, &request).await.unwrap();
+        assert_eq!(claims.email.as_deref(), Some("ana@example.com"));
+        assert!(claims.email_verified);
+    }
+
+    #[tokio::test]
+    async fn codes_are_bound_to_the_login_that_started_them() {
+        let idp = MockIdp::start().await;
+        let client = OidcClient::discover_issuer(&idp.issuer, CLIENT_ID, None)
+            .await
+            .unwrap();
+        let request = AuthorizationRequest::generate();
+
+        let (code, _) = idp.authorize(&client.authorization_url("state", &request), "a@b.c");
+        let other_verifier = AuthorizationRequest {
+            code_verifier: generate_opaque_token(),
+            nonce: request.nonce.clone(),
+        };
+        assert!(client.exchange_code(&code, &other_verifier).await.is_err());
+
+        let (code, _) = idp.authorize(&client.authorization_url("state", &request), "a@b.c");
+        let other_nonce = AuthorizationRequest {
+            code_verifier: request.code_verifier.clone(),
+            nonce: generate_opaque_token(),
+        };
+        assert!(client.exchange_code(&code, &other_nonce).await.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Offset based paging for connections whose order can be picked by the client.
+//!
+//! Timestamp cursors like the ones used for messages only work for a fixed order,
+//! so the admin directories use the position of an item in the ordered result as cursor.
+
+use async_graphql::{Enum, SimpleObject};
+use sea_orm::Order;
+
+pub const DEFAULT_PAGE_SIZE: usize = 25;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
+impl From<OrderDirection> for Order {
+    fn from(direction: OrderDirection) -> Self {
+        match direction {
+            OrderDirection::Asc => Order::Asc,
+            OrderDirection::Desc => Order::Desc,
+        }
+    }
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct TotalCount {
+    pub total_count: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: usize,
+    pub has_previous_page: bool,
+    pub has_next_page: bool,
+}
+
+/// Resolves relay arguments to a window of at most [`MAX_PAGE_SIZE`] items out of `total`.
+pub fn page(
+    total: usize,
+    after: Option<usize>,
+    before: Option<usize>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> Page {
+    let mut start = after.map(|after| after + 1).unwrap_or(0).min(total);
+    let mut end = before.unwrap_or(total).clamp(start, total);
+
+    if let Some(first) = first {
+        end = end.min(start + first.min(MAX_PAGE_SIZE));
+    }
+    if let Some(last) = last {
+        start = start.max(end.saturating_sub(last.min(MAX_PAGE_SIZE)));
+    }
+    if first.is_none() && last.is_none() {
+        end = end.min(start + DEFAULT_PAGE_SIZE);
+    }
+
+    Page {
+        offset: start,
+        limit: end - start,
+        has_previous_page: start > 0,
+        has_next_page: end < total,
+    }
+}
+
+/// Case insensitive `LIKE` pattern matching `query` anywhere in a value.
+pub fn contains_pattern(query: &str) -> String {
+    let escaped = query
+        .trim()
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_windows() {
+        assert_eq!(
+            page(60, None, None, None, None),
+            Page {
+                offset: 0,
+                limit: DEFAULT_PAGE_SIZE,
+                has_previous_page: false,
+                has_next_page: true,
+            }
+        );
+        assert_eq!(
+            page(60, Some(9), None, Some(10), None),
+            Page {
+                offset: 10,
+                limit: 10,
+                has_previous_page: true,
+                has_next_page: true,
+            }
+        );
+        assert_eq!(
+            page(60, None, Some(50), None, Some(5)),
+            Page {
+                offset: 45,
+                limit: 5,
+                has_previous_page: true,
+                has_next_page: true,
+            }
+        );
+        assert_eq!(page(500, None, None, Some(1000), None).limit, MAX_PAGE_SIZE);
+        assert_eq!(page(3, Some(10), None, Some(5), None).limit, 0);
+    }
+
+    #[test]
+    fn contains_pattern_escapes_wildcards() {
+        assert_eq!(contains_pattern(" Ada "), "%ada%");
+        assert_eq!(contains_pattern("100%_"), "%100\\%\\_%");
+    }
+}
@@ -0,0 +1,248 @@
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use sha1::{Digest, Sha1};
+
+use super::{AppError, UserError};
+use crate::{PASSWORD_BREACH_DIR, PASSWORD_CHARACTER_CLASSES, PASSWORD_MIN_LENGTH};
+
+/// Upper bound that keeps hashing cheap, not something users should ever hit.
+const MAX_LENGTH: usize = 100;
+/// Usernames and email names shorter than this are too common to forbid inside passwords.
+const MIN_REUSE_LENGTH: usize = 3;
+
+lazy_static! {
+    static ref POLICY: PasswordPolicy = PasswordPolicy::from_env();
+}
+
+/// Checks the configuration up front, so a typo or a missing breach list fails at startup.
+pub fn init() {
+    lazy_static::initialize(&POLICY);
+    if let Some(dir) = PASSWORD_BREACH_DIR.as_ref() {
+        assert!(
+            Path::new(dir).is_dir(),
+            "PASSWORD_BREACH_DIR `{dir}` should be a directory"
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharacterClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Digit => c.is_ascii_digit(),
+            CharacterClass::Symbol => !c.is_alphanumeric(),
+        }
+    }
+
+    fn violation(&self) -> &'static str {
+        match self {
+            CharacterClass::Lowercase => "missing_lowercase",
+            CharacterClass::Uppercase => "missing_uppercase",
+            CharacterClass::Digit => "missing_digit",
+            CharacterClass::Symbol => "missing_symbol",
+        }
+    }
+}
+
+impl std::str::FromStr for CharacterClass {
+    type Err = ();
+    fn from_str(input: &str) -> Result<CharacterClass, Self::Err> {
+        match input {
+            "lower" => Ok(Self::Lowercase),
+            "upper" => Ok(Self::Uppercase),
+            "digit" => Ok(Self::Digit),
+            "symbol" => Ok(Self::Symbol),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub required_classes: Vec<CharacterClass>,
+}
+
+impl PasswordPolicy {
+    fn from_env() -> Self {
+        let required_classes = PASSWORD_CHARACTER_CLASSES
+            .split(',')
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+            .map(|class| {
+                class
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Unknown password character class `{class}`"))
+            })
+            .collect();
+
+        Self {
+            min_length: *PASSWORD_MIN_LENGTH,
+            required_classes,
+        }
+    }
+
+    /// Returns the rules the password breaks, without consulting the breach list.
+    pub fn violations(&self, password: &str, username: &str, email: &str) -> Vec<&'static str> {
+        let mut violations = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push("too_short");
+        }
+        if length > MAX_LENGTH {
+            violations.push("too_long");
+        }
+
+        for class in &self.required_classes {
+            if !password.chars().any(|c| class.matches(c)) {
+                violations.push(class.violation());
+            }
+        }
+
+        let password = password.to_lowercase();
+        let contains = |part: &str| {
+            part.chars().count() >= MIN_REUSE_LENGTH && password.contains(&part.to_lowercase())
+        };
+        if contains(username) {
+            violations.push("contains_username");
+        }
+        let email_name = email.split('@').next().unwrap_or_default();
+        if contains(email_name) {
+            violations.push("contains_email");
+        }
+
+        violations
+    }
+}
+
+/// Looks the password up in a local copy of the Pwned Passwords range files: one
+/// `{PREFIX}.txt` file per first five hex digits of the SHA-1 hash, holding the
+/// `SUFFIX:COUNT` lines of every breached hash with that prefix.
+pub async fn is_breached(dir: &str, password: &str) -> Result<bool, AppError> {
+    let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(5);
+
+    let path = Path::new(dir).join(format!("{prefix}.txt"));
+    let range = match tokio::fs::read_to_string(path).await {
+        Ok(range) => range,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(range.lines().any(|line| {
+        let breached = line.split(':').next().unwrap_or_default();
+        breached.trim().eq_ignore_ascii_case(suffix)
+    }))
+}
+
+/// Checks a new password against the configured policy and breach list. `parameter` is the
+/// name of the argument the password came in, so the frontend knows which field to mark.
+pub async fn validate(
+    parameter: &'static str,
+    password: &str,
+    username: &str,
+    email: &str,
+) -> Result<(), AppError> {
+    let mut violations = POLICY.violations(password, username, email);
+    if let Some(dir) = PASSWORD_BREACH_DIR.as_ref() {
+        if is_breached(dir, password).await? {
+            violations.push("breached");
+        }
+    }
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    // the rules that were broken are reported instead of the password
+    Err(AppError::user(
+        "Password does not meet the password policy",
+        UserError::PolicyViolation {
+            parameter,
+            violations,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            required_classes: vec![
+                CharacterClass::Lowercase,
+                CharacterClass::Uppercase,
+                CharacterClass::Digit,
+            ],
+        }
+    }
+
+    #[test]
+    fn accepts_password_following_every_rule() {
+        let violations = policy().violations("Correct4Horse", "jane.doe", "jane@example.com");
+        assert!(violations.is_empty());
+    }
+
+    #[test]
+    fn reports_every_broken_rule() {
+        let violations = policy().violations("jane", "jane", "jane@example.com");
+        assert_eq!(
+            violations,
+            vec![
+                "too_short",
+                "missing_uppercase",
+                "missing_digit",
+                "contains_username",
+                "contains_email",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn violations_are_listed_in_the_error() {
+        use async_graphql::{ErrorExtensions, Value};
+
+        let err = validate("new_password", "jane", "jane", "jane@example.com")
+            .await
+            .unwrap_err()
+            .extend();
+        let extensions = err.extensions.unwrap();
+        assert_eq!(
+            extensions.get("parameter"),
+            Some(&Value::from("new_password"))
+        );
+        let Some(Value::List(violations)) = extensions.get("violations") else {
+            panic!("violations should be a list");
+        };
+        assert!(violations.contains(&Value::from("contains_username")));
+        assert!(extensions.get("given_value").is_none());
+    }
+
+    #[tokio::test]
+    async fn finds_password_in_range_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // SHA-1 of "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        std::fs::write(
+            dir.path().join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n",
+        )
+        .unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        assert!(is_breached(path, "password").await.unwrap());
+        assert!(!is_breached(path, "Correct4Horse").await.unwrap());
+    }
+}
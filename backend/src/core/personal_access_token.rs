@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, OperationType};
+use async_graphql::{Pos, ServerResult, Variables};
+use async_trait::async_trait;
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{generate_opaque_token, hash_opaque_token, AppError, Claims};
+use crate::api::UserRepo;
+
+/// Lets `Claims` tell personal access tokens apart from JWTs without a database lookup.
+pub const TOKEN_PREFIX: &str = "pat_";
+/// `last_used_at` is only written when it is older than this, not on every request.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+/// What a personal access token may do. Tokens without a scope can do everything
+/// their user can.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenScope {
+    pub read_only: bool,
+    /// Classes the token is limited to, `None` for every class of the user.
+    pub class_ids: Option<Vec<Uuid>>,
+}
+
+impl TokenScope {
+    pub fn allows_class(&self, class_id: Uuid) -> bool {
+        match &self.class_ids {
+            Some(class_ids) => class_ids.contains(&class_id),
+            None => true,
+        }
+    }
+}
+
+pub fn is_personal_access_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+/// Creates a new token. Only its hash, the second value, should ever be stored.
+pub fn generate() -> (String, String) {
+    let token = format!("{}{}", TOKEN_PREFIX, generate_opaque_token());
+    let hash = hash_opaque_token(&token);
+    (token, hash)
+}
+
+/// Read-only tokens can run queries and subscriptions, but no mutations.
+pub fn check_read_only(claims: &Claims, document: &ExecutableDocument) -> Result<(), AppError> {
+    if !claims.is_read_only() {
+        return Ok(());
+    }
+
+    let has_mutation = document
+        .operations
+        .iter()
+        .any(|(_, operation)| operation.node.ty == OperationType::Mutation);
+    if has_mutation {
+        return Err(AppError::auth("Personal access token is read-only"));
+    }
+
+    Ok(())
+}
+
+/// Schema extension running [`check_read_only`] on every document, whichever transport
+/// it came in through.
+pub struct ReadOnlyTokens;
+
+impl ExtensionFactory for ReadOnlyTokens {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ReadOnlyTokensExtension)
+    }
+}
+
+struct ReadOnlyTokensExtension;
+
+#[async_trait]
+impl Extension for ReadOnlyTokensExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        // HTTP requests carry the claims as query data, websockets as session data
+        if let Some(Some(claims)) = ctx.data_opt::<Option<Claims>>() {
+            check_read_only(claims, &document)
+                .map_err(|err| async_graphql::Error::from(err).into_server_error(Pos::default()))?;
+        }
+
+        Ok(document)
+    }
+}
+
+/// Resolves a personal access token to claims carrying its scope.
+pub async fn validate(
+    data_loader: &DataLoader<DatabaseConnection>,
+    token: &str,
+) -> Result<Claims, AppError> {
+    let stored = UserRepo::find_personal_access_token(data_loader, hash_opaque_token(token))
+        .await?
+        .ok_or_else(|| AppError::auth("Unknown personal access token"))?;
+
+    let now = Utc::now().naive_utc();
+    if stored.revoked_at.is_some() {
+        return Err(AppError::auth("Personal access token was revoked"));
+    }
+    if matches!(stored.expires_at, Some(expires_at) if expires_at <= now) {
+        return Err(AppError::auth("Personal access token expired"));
+    }
+
+    let user = UserRepo::find_by_id(data_loader, stored.user_id).await?;
+    if !matches!(user, Some(user) if user.active && user.deleted_at.is_none()) {
+        return Err(AppError::auth(
+            "Personal access token belongs to an inactive user",
+        ));
+    }
+
+    let is_stale = stored
+        .last_used_at
+        .map(|last_used_at| (now - last_used_at).num_seconds() >= LAST_USED_RESOLUTION_SECONDS)
+        .unwrap_or(true);
+    if is_stale {
+        UserRepo::touch_personal_access_token(data_loader, stored.id, now).await?;
+    }
+
+    Ok(Claims {
+        sub: stored.user_id.to_string(),
+        sid: stored.id.to_string(),
+        exp: stored
+            .expires_at
+            .map(|expires_at| expires_at.timestamp())
+            .unwrap_or(i64::MAX),
+        scope: Some(TokenScope {
+            read_only: stored.read_only,
+            class_ids: stored.class_ids,
+        }),
+        act: None,
+        two_factor_setup: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::parser::parse_query;
+
+    #[test]
+    fn generated_tokens_are_recognized() {
+        let (token, hash) = generate();
+        assert!(is_personal_access_token(&token));
+        assert_eq!(hash, hash_opaque_token(&token));
+        assert!(!is_personal_access_token("eyJhbGciOiJIUzI1NiJ9.e30.sig"));
+    }
+
+    #[test]
+    fn read_only_tokens_cannot_mutate() {
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            sid: Uuid::new_v4().to_string(),
+            exp: i64::MAX,
+            scope: Some(TokenScope {
+                read_only: true,
+                class_ids: None,
+            }),
+            act: None,
+            two_factor_setup: false,
+        };
+
+        let check = |claims: &Claims, query: &str| {
+            check_read_only(claims, &parse_query(query).expect("query should parse"))
+        };
+        assert!(check(&claims, "query { me { id } }").is_ok());
+        assert!(check(&claims, "{ me { id } }").is_ok());
+        assert!(check(&claims, "query A { me { id } } mutation B { logout }").is_err());
+        assert!(check(
+            &Claims {
+                scope: None,
+                ..claims
+            },
+            "mutation { logout }"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn class_scope_limits_classes() {
+        let class_id = Uuid::new_v4();
+        let scoped = TokenScope {
+            read_only: false,
+            class_ids: Some(vec![class_id]),
+        };
+        assert!(scoped.allows_class(class_id));
+        assert!(!scoped.allows_class(Uuid::new_v4()));
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            sid: Uuid::new_v4().to_string(),
+            exp: i64::MAX,
+            scope: Some(scoped),
+            act: None,
+            two_factor_setup: false,
+        };
+        assert!(claims.is_class_scoped());
+
+        let unscoped = TokenScope {
+            read_only: true,
+            class_ids: None,
+        };
+        assert!(unscoped.allows_class(Uuid::new_v4()));
+    }
+}
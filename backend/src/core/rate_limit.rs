@@ -0,0 +1,39 @@
+use std::net::IpAddr;
+
+use deadpool_redis::{redis, Pool};
+
+use super::AppError;
+
+const RATE_LIMIT: &str = "rate_limit";
+
+/// Address of the client that sent the GraphQL request.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Counts an attempt for `key` in a fixed window and tells whether it is still within `limit`.
+pub async fn hit(
+    redis_pool: &Pool,
+    key: &str,
+    limit: u32,
+    window_seconds: usize,
+) -> Result<bool, AppError> {
+    let key = format!("{}:{}", RATE_LIMIT, key);
+    let mut conn = redis_pool.get().await?;
+
+    // the window starts with the first attempt, in one transaction so the counter can't be
+    // left behind without an expiry
+    let (attempts,): (u32,) = redis::pipe()
+        .atomic()
+        .cmd("SET")
+        .arg(&key)
+        .arg(0)
+        .arg("EX")
+        .arg(window_seconds)
+        .arg("NX")
+        .ignore()
+        .incr(&key, 1)
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(attempts <= limit)
+}
@@ -0,0 +1,62 @@
+//! Signals used to recommend public classes and the explanation shown with each of them.
+//!
+//! Classes are scored by the tags they share with the classes a user is already in, how many
+//! people joined them lately and how busy their channels are. Growth and activity are damped
+//! logarithmically, so a single huge class can't push out everything sharing the user's tags.
+
+/// Members who joined within this many days count towards the growth of a class.
+pub const GROWTH_WINDOW_DAYS: i64 = 30;
+/// Messages posted within this many days count towards the activity of a class.
+pub const ACTIVITY_WINDOW_DAYS: i64 = 14;
+
+/// Why a class was recommended, going by its strongest signal.
+pub fn reason(
+    shared_tags: &[String],
+    new_members: i64,
+    recent_messages: i64,
+    members: i64,
+) -> String {
+    if !shared_tags.is_empty() {
+        format!("Tagged {} like your classes", shared_tags.join(", "))
+    } else if new_members > 0 {
+        format!(
+            "{} joined in the last {} days",
+            plural(new_members, "person", "people"),
+            GROWTH_WINDOW_DAYS
+        )
+    } else if recent_messages > 0 {
+        format!(
+            "{} in the last {} days",
+            plural(recent_messages, "message", "messages"),
+            ACTIVITY_WINDOW_DAYS
+        )
+    } else {
+        format!("Public class with {}", plural(members, "member", "members"))
+    }
+}
+
+fn plural(count: i64, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {}", one),
+        _ => format!("{} {}", count, many),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasons_follow_the_strongest_signal() {
+        assert_eq!(
+            reason(&["rust".into(), "math".into()], 5, 40, 12),
+            "Tagged rust, math like your classes"
+        );
+        assert_eq!(
+            reason(&[], 1, 40, 12),
+            "1 person joined in the last 30 days"
+        );
+        assert_eq!(reason(&[], 0, 40, 12), "40 messages in the last 14 days");
+        assert_eq!(reason(&[], 0, 0, 1), "Public class with 1 member");
+    }
+}
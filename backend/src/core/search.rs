@@ -0,0 +1,105 @@
+//! Full text search over the `search` column of classes and highlighting of what matched.
+//!
+//! Queries use the `websearch_to_tsquery` syntax, so quoted phrases, `or` and `-word` work.
+//! They are parsed with the `english` configuration for names and descriptions and with the
+//! `simple` one for tags, matching how the column is built.
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+
+/// Marks where a match starts in `ts_headline` output. Control characters are used instead
+/// of markup so that the text itself never has to be trusted by the client.
+const HIGHLIGHT_START: char = '\u{2}';
+/// Marks where a match ends in `ts_headline` output.
+const HIGHLIGHT_STOP: char = '\u{3}';
+
+/// Classes whose name, description or tags match `query`.
+pub fn matches(query: &str) -> SimpleExpr {
+    Expr::cust_with_values(
+        "(search @@ websearch_to_tsquery('english', $1) \
+            or search @@ websearch_to_tsquery('simple', $1))",
+        [query],
+    )
+}
+
+/// How well a class matches `query`, higher is better.
+pub fn rank(query: &str) -> SimpleExpr {
+    Expr::cust_with_values(
+        "ts_rank(search, websearch_to_tsquery('english', $1)) \
+            + ts_rank(search, websearch_to_tsquery('simple', $1))",
+        [query],
+    )
+}
+
+/// Classes tagged with every one of `tags`, ignoring case.
+pub fn has_tags(tags: Vec<String>) -> SimpleExpr {
+    let tags = tags
+        .into_iter()
+        .map(|t| t.to_lowercase())
+        .collect::<Vec<_>>();
+    Expr::cust_with_values("string_to_array(lower(tags), ' ') @> $1::text[]", [tags])
+}
+
+/// `column` with the words matching `query` marked. Texts are cut down to at most
+/// `max_fragments` pieces around the matches, or kept whole when it is 0.
+pub fn headline(column: &'static str, query: &str, max_fragments: u32) -> SimpleExpr {
+    let options = format!(
+        "StartSel={}, StopSel={}, HighlightAll={}, MaxFragments={}, MaxWords=30, MinWords=10, \
+            FragmentDelimiter=\" … \"",
+        HIGHLIGHT_START,
+        HIGHLIGHT_STOP,
+        max_fragments == 0,
+        max_fragments
+    );
+    // The words of a query don't have to appear together in a single text to match a class,
+    // so any of them is highlighted instead of only passages containing all of them
+    Expr::cust_with_values(
+        &format!(
+            "ts_headline('english', {}, replace((websearch_to_tsquery('english', $1) \
+                || websearch_to_tsquery('simple', $1))::text, ' & ', ' | ')::tsquery, $2)",
+            column
+        ),
+        [query.to_owned(), options],
+    )
+}
+
+/// Splits `ts_headline` output into pieces of text and whether they matched the query.
+pub fn split_highlights(headline: &str) -> Vec<(String, bool)> {
+    let mut fragments = Vec::new();
+    let mut highlighted = false;
+    let mut rest = headline;
+    while let Some(i) = rest.find([HIGHLIGHT_START, HIGHLIGHT_STOP]) {
+        if i > 0 {
+            fragments.push((rest[..i].to_owned(), highlighted));
+        }
+        highlighted = rest[i..].starts_with(HIGHLIGHT_START);
+        // Both markers are a single byte long
+        rest = &rest[i + 1..];
+    }
+    if !rest.is_empty() {
+        fragments.push((rest.to_owned(), highlighted));
+    }
+    fragments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_are_split() {
+        assert_eq!(
+            split_highlights("Intro to \u{2}Rust\u{3} and \u{2}C\u{3}"),
+            vec![
+                ("Intro to ".to_owned(), false),
+                ("Rust".to_owned(), true),
+                (" and ".to_owned(), false),
+                ("C".to_owned(), true),
+            ]
+        );
+        assert_eq!(
+            split_highlights("\u{2}Rust\u{3}"),
+            vec![("Rust".to_owned(), true)]
+        );
+        assert!(split_highlights("").is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+//! Spelling rules for class tags, so that `#Rust`, `rust` and `RUST` end up as one tag.
+//!
+//! Tags of a class are also kept joined by spaces in `class.tags`, which feeds the search
+//! vector, so a tag itself never contains whitespace.
+
+use super::{AppError, UserError};
+
+pub const MAX_CLASS_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Lowercases the tag, drops leading `#` and joins words with dashes.
+/// `None` when nothing is left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag
+        .trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Splits tags separated by spaces or commas and normalizes them, keeping the first
+/// occurrence of duplicates.
+pub fn normalize_tags(tags: &str) -> Result<Vec<String>, AppError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.split(|c: char| c == ',' || c.is_whitespace()) {
+        let Some(tag) = normalize_tag(tag) else {
+            continue;
+        };
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(AppError::user(
+                "Tag is too long",
+                UserError::BadInput {
+                    parameter: "tags",
+                    given_value: tag,
+                },
+            ));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.len() > MAX_CLASS_TAGS {
+        return Err(AppError::user(
+            "Too many tags",
+            UserError::BadInput {
+                parameter: "tags",
+                given_value: tags.to_owned(),
+            },
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Names of the tags stored in `class.tags`.
+pub fn split_class_tags(tags: &str) -> Vec<String> {
+    tags.split(' ')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(
+            normalize_tag(" #Linear  Algebra "),
+            Some("linear-algebra".into())
+        );
+        assert_eq!(normalize_tag("##"), None);
+        assert_eq!(
+            normalize_tags("Rust, #rust  C++,math").unwrap(),
+            vec!["rust", "c++", "math"]
+        );
+        assert!(normalize_tags("").unwrap().is_empty());
+        assert!(normalize_tags(&"a".repeat(MAX_TAG_LENGTH + 1)).is_err());
+        assert!(normalize_tags(
+            &(0..=MAX_CLASS_TAGS)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+        .is_err());
+    }
+}
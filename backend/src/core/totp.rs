@@ -0,0 +1,197 @@
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::TOTP_ENCRYPTION_KEY;
+
+const ISSUER: &str = "Go e-Learn";
+const DIGITS: u32 = 6;
+const STEP_SECONDS: i64 = 30;
+// accept codes from one step before and after the current one to tolerate clock drift
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Prefix of stored secrets, so the scheme can change without guessing what a value holds.
+const ENCRYPTED_PREFIX: &str = "v1:";
+const NONCE_LEN: usize = 12;
+
+/// Creates a new random 160 bit secret encoded in base32, as expected by authenticator apps.
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = rand::random();
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// Encrypts the secret for storage. Anyone reading the database could otherwise generate
+/// codes of every user.
+pub fn encrypt_secret(secret: &str) -> String {
+    seal(&cipher(&TOTP_ENCRYPTION_KEY), secret)
+}
+
+/// Reverses [`encrypt_secret`]. Returns `None` for values that weren't encrypted with the
+/// current key.
+pub fn decrypt_secret(stored: &str) -> Option<String> {
+    open(&cipher(&TOTP_ENCRYPTION_KEY), stored)
+}
+
+pub fn is_encrypted(stored: &str) -> bool {
+    stored.starts_with(ENCRYPTED_PREFIX)
+}
+
+pub fn otpauth_uri(secret: &str, account_name: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = urlencode(ISSUER),
+        account = urlencode(account_name),
+    )
+}
+
+pub fn current_step(unix_time: i64) -> i64 {
+    unix_time / STEP_SECONDS
+}
+
+/// Checks the code against the steps around `unix_time` and returns the matching step.
+/// Steps lower or equal to `last_used_step` are rejected so a code can't be replayed.
+pub fn verify(
+    secret: &str,
+    code: &str,
+    unix_time: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let step = current_step(unix_time);
+    (step - ALLOWED_DRIFT_STEPS..=step + ALLOWED_DRIFT_STEPS)
+        .filter(|s| last_used_step.map(|last| *s > last).unwrap_or(true))
+        .find(|s| hotp(&key, *s as u64, DIGITS) == code)
+}
+
+/// Creates one-time recovery codes formatted as `xxxxx-xxxxx`. Like other opaque tokens,
+/// only their hashes should be stored.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let bytes: [u8; 6] = rand::random();
+            let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+fn hotp(key: &[u8], counter: u64, digits: u32) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(digits),
+        width = digits as usize
+    )
+}
+
+fn cipher(key: &str) -> Aes256Gcm {
+    // `KeyInit` isn't imported, its `new_from_slice` would clash with the one of `Mac`
+    <Aes256Gcm as aes_gcm::KeyInit>::new(&Sha256::digest(key.as_bytes()))
+}
+
+fn seal(cipher: &Aes256Gcm, secret: &str) -> String {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+            .expect("Secrets are short enough to encrypt"),
+    );
+    format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(sealed))
+}
+
+fn open(cipher: &Aes256Gcm, stored: &str) -> Option<String> {
+    let sealed = STANDARD
+        .decode(stored.strip_prefix(ENCRYPTED_PREFIX)?)
+        .ok()?;
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let secret = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+    String::from_utf8(secret).ok()
+}
+
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA1 variant
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        let cases = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+        ];
+
+        for (time, expected) in cases {
+            assert_eq!(hotp(RFC_KEY, current_step(time) as u64, 8), expected);
+        }
+    }
+
+    #[test]
+    fn verifies_with_drift_and_rejects_replay() {
+        let secret = BASE32_NOPAD.encode(RFC_KEY);
+        let code = hotp(RFC_KEY, current_step(59) as u64, DIGITS);
+
+        assert_eq!(verify(&secret, &code, 59, None), Some(1));
+        assert_eq!(verify(&secret, &code, 59 + STEP_SECONDS, None), Some(1));
+        assert_eq!(verify(&secret, &code, 59 + 2 * STEP_SECONDS, None), None);
+        assert_eq!(verify(&secret, &code, 59, Some(1)), None);
+        assert_eq!(verify(&secret, "12345", 59, None), None);
+    }
+
+    #[test]
+    fn secrets_round_trip_only_with_their_key() {
+        let secret = generate_secret();
+        let sealed = seal(&cipher("key"), &secret);
+
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains(&secret));
+        assert_ne!(sealed, seal(&cipher("key"), &secret));
+        assert_eq!(open(&cipher("key"), &sealed), Some(secret.clone()));
+        assert_eq!(open(&cipher("other key"), &sealed), None);
+        assert_eq!(open(&cipher("key"), &secret), None);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_graphql::dataloader::DataLoader;
+use chrono::Utc;
+use deadpool_redis::{redis::AsyncCommands, Pool};
+use entity::sea_orm_active_enums::FileType;
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use crate::api::{
+    ClassCloneProgress, ClassRepo, CloneStage, UserRepo, CLASS_CLONE_PROGRESS,
+    CLASS_CLONE_REQUESTER,
+};
+use crate::core::{totp, AppError, UNACTIVATED_ACCOUNT_TTL_DAYS};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long the latest progress of a clone stays around for late subscribers.
+const CLONE_PROGRESS_TTL_SECONDS: usize = 60 * 60;
+
+/// Periodically removes accounts that were never activated, freeing their usernames and emails.
+pub async fn cleanup_unactivated_users(conn: DatabaseConnection) {
+    let data_loader = DataLoader::new(conn, tokio::spawn);
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let created_before =
+            Utc::now().naive_utc() - chrono::Duration::days(UNACTIVATED_ACCOUNT_TTL_DAYS);
+        match UserRepo::delete_unactivated_users(&data_loader, created_before).await {
+            Ok(0) => {}
+            Ok(deleted) => tracing::info!("Removed {deleted} unactivated accounts"),
+            Err(err) => tracing::error!("Failed to remove unactivated accounts: {err}"),
+        }
+    }
+}
+
+/// Encrypts two-factor secrets that were stored before secrets were encrypted.
+pub async fn encrypt_totp_secrets(conn: DatabaseConnection) -> Result<(), AppError> {
+    let data_loader = DataLoader::new(conn, tokio::spawn);
+
+    let mut encrypted = 0;
+    for (user_id, secret) in UserRepo::find_plaintext_totp_secrets(&data_loader).await? {
+        let new_secret = totp::encrypt_secret(&secret);
+        if UserRepo::replace_totp_secret(&data_loader, user_id, secret, new_secret).await? {
+            encrypted += 1;
+        }
+    }
+    if encrypted > 0 {
+        tracing::info!("Encrypted the two-factor secrets of {encrypted} accounts");
+    }
+
+    Ok(())
+}
+
+/// Copies channels, files and assignments of `source_id` into the freshly created class
+/// `target_id`, reporting every step on `classCloneProgress`.
+pub async fn clone_class(
+    conn: DatabaseConnection,
+    s3_bucket: s3::Bucket,
+    redis_pool: Pool,
+    source_id: Uuid,
+    target_id: Uuid,
+    due_offset: Option<chrono::Duration>,
+) {
+    let data_loader = DataLoader::new(conn, tokio::spawn);
+    let res = copy_class_content(
+        &data_loader,
+        &s3_bucket,
+        &redis_pool,
+        source_id,
+        target_id,
+        due_offset,
+    )
+    .await;
+
+    if let Err(err) = res {
+        tracing::error!("Failed to clone class {source_id} into {target_id}: {err:?}");
+        // a partial copy would pass for the real thing, so it goes away like any deleted class
+        if let Err(err) = ClassRepo::delete_class(&data_loader, target_id).await {
+            tracing::error!("Failed to delete the partial clone {target_id}: {err:?}");
+        }
+        if let Err(err) =
+            report_clone_progress(&redis_pool, target_id, CloneStage::Failed, 0, 0).await
+        {
+            tracing::error!("Failed to report clone progress: {err:?}");
+        }
+    }
+
+    if let Err(err) = expire_clone_requester(&redis_pool, target_id).await {
+        tracing::error!("Failed to expire the requester of clone {target_id}: {err:?}");
+    }
+}
+
+async fn copy_class_content(
+    data_loader: &DataLoader<DatabaseConnection>,
+    s3_bucket: &s3::Bucket,
+    redis_pool: &Pool,
+    source_id: Uuid,
+    target_id: Uuid,
+    due_offset: Option<chrono::Duration>,
+) -> Result<(), AppError> {
+    report_clone_progress(redis_pool, target_id, CloneStage::Channels, 0, 0).await?;
+    let channels = ClassRepo::copy_channels(data_loader, source_id, target_id).await? as u64;
+    report_clone_progress(
+        redis_pool,
+        target_id,
+        CloneStage::Channels,
+        channels,
+        channels,
+    )
+    .await?;
+
+    let files = ClassRepo::copy_files(data_loader, source_id, target_id).await?;
+    let objects = files
+        .iter()
+        .filter(|(file, _)| file.file_type == FileType::File)
+        .collect::<Vec<_>>();
+    let total = objects.len() as u64;
+    report_clone_progress(redis_pool, target_id, CloneStage::Files, 0, total).await?;
+    for (completed, (file, id)) in objects.into_iter().enumerate() {
+        s3_bucket
+            .copy_object_internal(
+                format!("class-files/{source_id}/{}", file.id),
+                format!("class-files/{target_id}/{id}"),
+            )
+            .await?;
+        report_clone_progress(
+            redis_pool,
+            target_id,
+            CloneStage::Files,
+            completed as u64 + 1,
+            total,
+        )
+        .await?;
+    }
+
+    let file_ids = files
+        .iter()
+        .map(|(file, id)| (file.id, *id))
+        .collect::<HashMap<_, _>>();
+    report_clone_progress(redis_pool, target_id, CloneStage::Assignments, 0, 0).await?;
+    let assignments =
+        ClassRepo::copy_assignments(data_loader, source_id, target_id, file_ids, due_offset).await?
+            as u64;
+    report_clone_progress(
+        redis_pool,
+        target_id,
+        CloneStage::Assignments,
+        assignments,
+        assignments,
+    )
+    .await?;
+
+    report_clone_progress(redis_pool, target_id, CloneStage::Done, 0, 0).await?;
+    Ok(())
+}
+
+async fn report_clone_progress(
+    redis_pool: &Pool,
+    class_id: Uuid,
+    stage: CloneStage,
+    completed: u64,
+    total: u64,
+) -> Result<(), AppError> {
+    let progress = ClassCloneProgress {
+        class_id: class_id.into(),
+        stage,
+        completed,
+        total,
+    };
+    let channel = format!("{}:{}", CLASS_CLONE_PROGRESS, class_id);
+    let payload = serde_json::to_string(&progress).expect("Progress should serialize");
+
+    let mut conn = redis_pool.get().await?;
+    let _: () = conn
+        .set_ex(&channel, &payload, CLONE_PROGRESS_TTL_SECONDS)
+        .await?;
+    let _: () = conn.publish(channel, payload).await?;
+
+    Ok(())
+}
+
+/// Keeps late subscribers in for as long as the latest progress is kept.
+async fn expire_clone_requester(redis_pool: &Pool, class_id: Uuid) -> Result<(), AppError> {
+    let key = format!("{}:{}", CLASS_CLONE_REQUESTER, class_id);
+
+    let mut conn = redis_pool.get().await?;
+    let _: () = conn.expire(key, CLONE_PROGRESS_TTL_SECONDS).await?;
+
+    Ok(())
+}
@@ -110,6 +110,9 @@ pub async fn current_meeting(
     Path(class_id): Path<uuid::Uuid>,
     claims: Claims,
 ) -> Result<Json<MeetingData>, AppError> {
+    if !claims.allows_class(class_id) {
+        return Err(AppError::auth("Token is not allowed to access this class"));
+    }
     let mut conn = redis_pool.get().await.unwrap();
     let user_id = claims.sub;
     let peer_ids: HashMap<String, u32> = conn.hgetall(format!("meeting:{}", class_id)).await?;
@@ -167,7 +170,8 @@ async fn handle_socket(
 
     let connection_data = match msg {
         InMessageType::Auth { token, class_id } => {
-            let claims = crate::core::auth::validate_token(&token)?;
+            let claims = crate::core::auth::validate_session(&redis_pool, &token).await?;
+            crate::core::impersonation::record(&data_loader, &claims, "rtc").await?;
             let user_id = uuid::Uuid::parse_str(claims.sub.as_str()).expect("id is valid uuid");
             let class_id = uuid::Uuid::parse_str(class_id.as_str())?;
 
@@ -0,0 +1,86 @@
+//! Fixtures shared by the tests that need a database at `DATABASE_URL`.
+
+use chrono::Utc;
+use entity::sea_orm_active_enums::{MemberRole, UserType};
+use entity::{class, membership, user};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, IntoActiveModel};
+use uuid::Uuid;
+
+/// Connects to `DATABASE_URL` and brings the schema up to date.
+pub async fn connect() -> DatabaseConnection {
+    let conn = Database::connect(std::env::var("DATABASE_URL").unwrap())
+        .await
+        .unwrap();
+    Migrator::up(&conn, None).await.unwrap();
+    conn
+}
+
+/// An active regular user with a unique username and email, and no usable password.
+pub fn user_model() -> user::Model {
+    let id = Uuid::new_v4();
+    user::Model {
+        id,
+        username: id.to_string(),
+        first_name: "Jane".into(),
+        last_name: "Doe".into(),
+        has_avatar: false,
+        email: format!("{id}@example.com"),
+        password: String::new(),
+        created_at: Utc::now().naive_utc(),
+        deleted_at: None,
+        active: true,
+        user_type: UserType::Regular,
+        totp_secret: None,
+        totp_enabled: false,
+        totp_required: false,
+        totp_last_step: None,
+        locale: "en".into(),
+        bio: None,
+        pronouns: None,
+        timezone: "UTC".into(),
+        preferences: Default::default(),
+    }
+}
+
+pub async fn insert_user(conn: &DatabaseConnection) -> user::Model {
+    user_model().into_active_model().insert(conn).await.unwrap()
+}
+
+/// A public class owned by `owner_id`. The owner is not made a member.
+pub async fn insert_class(conn: &DatabaseConnection, owner_id: Uuid) -> class::Model {
+    class::Model {
+        id: Uuid::new_v4(),
+        name: "Mathematics".into(),
+        description: String::new(),
+        tags: String::new(),
+        has_image: false,
+        owner_id,
+        public: true,
+        deleted_at: None,
+        archived_at: None,
+        search: None,
+    }
+    .into_active_model()
+    .insert(conn)
+    .await
+    .unwrap()
+}
+
+pub async fn insert_member(
+    conn: &DatabaseConnection,
+    class_id: Uuid,
+    user_id: Uuid,
+    role: MemberRole,
+) -> membership::Model {
+    membership::Model {
+        user_id,
+        class_id,
+        role,
+        joined_at: Some(Utc::now().naive_utc()),
+    }
+    .into_active_model()
+    .insert(conn)
+    .await
+    .unwrap()
+}
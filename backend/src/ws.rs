@@ -20,10 +20,7 @@ use futures_util::future::BoxFuture;
 use serde::Deserialize;
 use tower_service::Service;
 
-use crate::{
-    core::{validate_token, Claims},
-    AppState,
-};
+use crate::{core::authenticate, AppState};
 
 /// A GraphQL subscription service.
 pub struct GraphQLSubscription<E> {
@@ -88,22 +85,22 @@ where
                 .protocols(ALL_WEBSOCKET_PROTOCOLS)
                 .on_upgrade(move |stream| {
                     GraphQLWebSocket::new(stream, executor, protocol)
-                        .on_connection_init(|value| {
+                        .on_connection_init(|value| async move {
                             let payload = match serde_json::from_value::<ConnestionInitPayload>(value) {
                                 Ok(payload) => payload,
                                 Err(err) => {
                                     tracing::debug!(err = ?err, "Failed to parse connection init payload");
-                                    return futures_util::future::ready(Err(err.into()));
+                                    return Err(err.into());
                                 }
                             };
 
                             tracing::debug!(token = ?payload.token, "Validating token");
-                            
-                            let claims = match validate_token(&payload.token) {
+
+                            let claims = match authenticate(&app_data.redis_pool, app_data.conn.clone(), &payload.token, Some("websocket")).await {
                                 Ok(claims) => claims,
                                 Err(err) => {
                                     tracing::debug!(err = ?err, "Failed to validate token");
-                                    return futures_util::future::ready(Err(err.into()));
+                                    return Err(err.into());
                                 }
                             };
 
@@ -113,7 +110,7 @@ where
                             data.insert(conn_dataloader);
                             data.insert(Some(claims));
 
-                            futures_util::future::ready(Ok(data))
+                            Ok(data)
                         })
                         .serve()
                 });
@@ -126,3 +123,59 @@ where
 struct ConnestionInitPayload {
     token: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::{channel::mpsc, SinkExt, StreamExt};
+    use async_graphql::http::{WebSocket, WebSocketProtocols, WsMessage};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::core::{personal_access_token::TokenScope, Claims};
+
+    #[tokio::test]
+    async fn read_only_tokens_cannot_mutate_over_websockets() {
+        let schema = crate::api::schema_builder().finish();
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            sid: Uuid::new_v4().to_string(),
+            exp: i64::MAX,
+            scope: Some(TokenScope {
+                read_only: true,
+                class_ids: None,
+            }),
+            act: None,
+            two_factor_setup: false,
+        };
+
+        let (mut client, server) = mpsc::unbounded::<String>();
+        let mut ws = WebSocket::new(schema, server, WebSocketProtocols::GraphQLWS)
+            .on_connection_init(move |_| async move {
+                let mut data = Data::default();
+                data.insert(Some(claims));
+                Ok(data)
+            });
+
+        client
+            .send(r#"{"type":"connection_init","payload":{}}"#.into())
+            .await
+            .unwrap();
+        client
+            .send(
+                r#"{"type":"subscribe","id":"1","payload":{"query":"mutation { logout }"}}"#.into(),
+            )
+            .await
+            .unwrap();
+
+        let mut rejected = false;
+        while let Ok(Some(WsMessage::Text(text))) =
+            tokio::time::timeout(std::time::Duration::from_secs(5), ws.next()).await
+        {
+            if text.contains(r#""id":"1""#) {
+                rejected = text.contains("Personal access token is read-only");
+                break;
+            }
+        }
+        assert!(rejected);
+    }
+}
@@ -14,6 +14,7 @@ pub struct Model {
     pub owner_id: Uuid,
     pub public: bool,
     pub deleted_at: Option<DateTime>,
+    pub archived_at: Option<DateTime>,
     #[sea_orm(ignore)]
     pub search: Option<String>,
 }
@@ -26,12 +27,20 @@ pub enum Relation {
     Channel,
     #[sea_orm(has_many = "super::class_blacklist::Entity")]
     ClassBlacklist,
+    #[sea_orm(has_many = "super::class_event::Entity")]
+    ClassEvent,
+    #[sea_orm(has_many = "super::class_tag::Entity")]
+    ClassTag,
     #[sea_orm(has_many = "super::file::Entity")]
     File,
     #[sea_orm(has_many = "super::invite::Entity")]
     Invite,
+    #[sea_orm(has_many = "super::join_request::Entity")]
+    JoinRequest,
     #[sea_orm(has_many = "super::membership::Entity")]
     Membership,
+    #[sea_orm(has_one = "super::ownership_transfer::Entity")]
+    OwnershipTransfer,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::OwnerId",
@@ -60,6 +69,18 @@ impl Related<super::class_blacklist::Entity> for Entity {
     }
 }
 
+impl Related<super::class_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ClassTag.def()
+    }
+}
+
+impl Related<super::class_event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ClassEvent.def()
+    }
+}
+
 impl Related<super::file::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::File.def()
@@ -72,12 +93,33 @@ impl Related<super::invite::Entity> for Entity {
     }
 }
 
+impl Related<super::join_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::JoinRequest.def()
+    }
+}
+
 impl Related<super::membership::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Membership.def()
     }
 }
 
+impl Related<super::ownership_transfer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OwnershipTransfer.def()
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::class_tag::Relation::Tag.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::class_tag::Relation::Class.def().rev())
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
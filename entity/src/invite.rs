@@ -10,6 +10,15 @@ pub struct Model {
     pub expires_at: Option<DateTime>,
     pub class_id: Uuid,
     pub multiuse: bool,
+    #[sea_orm(unique)]
+    pub code: Option<String>,
+    pub email: Option<String>,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime,
+    pub revoked_by: Option<Uuid>,
+    pub revoked_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -22,6 +31,24 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     Class,
+    #[sea_orm(has_many = "super::invite_redemption::Entity")]
+    InviteRedemption,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::CreatedBy",
+        to = "super::user::Column::Id",
+        on_update = "Restrict",
+        on_delete = "SetNull"
+    )]
+    User2,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::RevokedBy",
+        to = "super::user::Column::Id",
+        on_update = "Restrict",
+        on_delete = "SetNull"
+    )]
+    User1,
 }
 
 impl Related<super::class::Entity> for Entity {
@@ -30,4 +57,10 @@ impl Related<super::class::Entity> for Entity {
     }
 }
 
+impl Related<super::invite_redemption::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::InviteRedemption.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
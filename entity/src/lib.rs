@@ -2,19 +2,33 @@
 
 pub mod prelude;
 
+pub mod activation_token;
 pub mod assignment;
 pub mod assignment_file;
 pub mod assignment_submission;
 pub mod assignment_submission_feedback;
 pub mod assignment_submission_file;
+pub mod calendar_token;
 pub mod channel;
 pub mod class;
 pub mod class_blacklist;
+pub mod class_event;
+pub mod class_tag;
+pub mod email_change_token;
 pub mod file;
+pub mod impersonation_log;
 pub mod invite;
+pub mod invite_redemption;
+pub mod join_request;
 pub mod membership;
 pub mod message;
+pub mod ownership_transfer;
 pub mod password_reset_token;
+pub mod personal_access_token;
+pub mod recovery_code;
+pub mod refresh_token;
 pub mod report;
 pub mod sea_orm_active_enums;
+pub mod tag;
 pub mod user;
+pub mod user_preferences;
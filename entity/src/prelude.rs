@@ -1,17 +1,30 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 
+pub use super::activation_token::Entity as ActivationToken;
 pub use super::assignment::Entity as Assignment;
 pub use super::assignment_file::Entity as AssignmentFile;
 pub use super::assignment_submission::Entity as AssignmentSubmission;
 pub use super::assignment_submission_feedback::Entity as AssignmentSubmissionFeedback;
 pub use super::assignment_submission_file::Entity as AssignmentSubmissionFile;
+pub use super::calendar_token::Entity as CalendarToken;
 pub use super::channel::Entity as Channel;
 pub use super::class::Entity as Class;
 pub use super::class_blacklist::Entity as ClassBlacklist;
+pub use super::class_event::Entity as ClassEvent;
+pub use super::class_tag::Entity as ClassTag;
+pub use super::email_change_token::Entity as EmailChangeToken;
 pub use super::file::Entity as File;
+pub use super::impersonation_log::Entity as ImpersonationLog;
 pub use super::invite::Entity as Invite;
+pub use super::invite_redemption::Entity as InviteRedemption;
+pub use super::join_request::Entity as JoinRequest;
 pub use super::membership::Entity as Membership;
 pub use super::message::Entity as Message;
+pub use super::ownership_transfer::Entity as OwnershipTransfer;
 pub use super::password_reset_token::Entity as PasswordResetToken;
+pub use super::personal_access_token::Entity as PersonalAccessToken;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::refresh_token::Entity as RefreshToken;
 pub use super::report::Entity as Report;
+pub use super::tag::Entity as Tag;
 pub use super::user::Entity as User;
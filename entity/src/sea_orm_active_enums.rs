@@ -2,6 +2,18 @@
 
 use sea_orm::entity::prelude::*;
 
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "class_event_kind")]
+pub enum ClassEventKind {
+    #[sea_orm(string_value = "Exam")]
+    Exam,
+    #[sea_orm(string_value = "Lecture")]
+    Lecture,
+    #[sea_orm(string_value = "OfficeHours")]
+    OfficeHours,
+    #[sea_orm(string_value = "Other")]
+    Other,
+}
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "file_type")]
 pub enum FileType {
@@ -20,3 +32,25 @@ pub enum UserType {
     #[sea_orm(string_value = "Regular")]
     Regular,
 }
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "join_request_status")]
+pub enum JoinRequestStatus {
+    #[sea_orm(string_value = "Approved")]
+    Approved,
+    #[sea_orm(string_value = "Denied")]
+    Denied,
+    #[sea_orm(string_value = "Pending")]
+    Pending,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "member_role")]
+pub enum MemberRole {
+    #[sea_orm(string_value = "CoTeacher")]
+    CoTeacher,
+    #[sea_orm(string_value = "Observer")]
+    Observer,
+    #[sea_orm(string_value = "Student")]
+    Student,
+    #[sea_orm(string_value = "TeachingAssistant")]
+    TeachingAssistant,
+}
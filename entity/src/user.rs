@@ -1,6 +1,7 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 
 use super::sea_orm_active_enums::UserType;
+use super::user_preferences::UserPreferences;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -20,32 +21,71 @@ pub struct Model {
     pub deleted_at: Option<DateTime>,
     pub active: bool,
     pub user_type: UserType,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_required: bool,
+    pub totp_last_step: Option<i64>,
+    pub locale: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub timezone: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub preferences: UserPreferences,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::activation_token::Entity")]
+    ActivationToken,
     #[sea_orm(has_many = "super::assignment_submission::Entity")]
     AssignmentSubmission,
+    #[sea_orm(has_one = "super::calendar_token::Entity")]
+    CalendarToken,
     #[sea_orm(has_many = "super::class::Entity")]
     Class,
     #[sea_orm(has_many = "super::class_blacklist::Entity")]
     ClassBlacklist,
+    #[sea_orm(has_many = "super::email_change_token::Entity")]
+    EmailChangeToken,
+    #[sea_orm(has_many = "super::invite_redemption::Entity")]
+    InviteRedemption,
     #[sea_orm(has_many = "super::membership::Entity")]
     Membership,
     #[sea_orm(has_many = "super::message::Entity")]
     Message,
+    #[sea_orm(has_many = "super::ownership_transfer::Entity")]
+    OwnershipTransfer,
     #[sea_orm(has_many = "super::password_reset_token::Entity")]
     PasswordResetToken,
+    #[sea_orm(has_many = "super::personal_access_token::Entity")]
+    PersonalAccessToken,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
     #[sea_orm(has_many = "super::report::Entity")]
     Report,
 }
 
+impl Related<super::activation_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActivationToken.def()
+    }
+}
+
 impl Related<super::assignment_submission::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::AssignmentSubmission.def()
     }
 }
 
+impl Related<super::calendar_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CalendarToken.def()
+    }
+}
+
 impl Related<super::class::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Class.def()
@@ -58,6 +98,18 @@ impl Related<super::class_blacklist::Entity> for Entity {
     }
 }
 
+impl Related<super::email_change_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailChangeToken.def()
+    }
+}
+
+impl Related<super::invite_redemption::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::InviteRedemption.def()
+    }
+}
+
 impl Related<super::membership::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Membership.def()
@@ -70,12 +122,36 @@ impl Related<super::message::Entity> for Entity {
     }
 }
 
+impl Related<super::ownership_transfer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OwnershipTransfer.def()
+    }
+}
+
 impl Related<super::password_reset_token::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::PasswordResetToken.def()
     }
 }
 
+impl Related<super::personal_access_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PersonalAccessToken.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
 impl Related<super::report::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Report.def()
@@ -0,0 +1,55 @@
+//! Typed contents of the `user.preferences` json column. Every field has a default,
+//! so rows written before a preference existed still deserialize.
+
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+#[serde(default)]
+pub struct UserPreferences {
+    pub notifications: NotificationPreferences,
+    pub display: DisplayPreferences,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationPreferences {
+    pub email_digest: bool,
+    pub mentions: bool,
+    pub assignment_reminders: bool,
+    pub submission_feedback: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            email_digest: true,
+            mentions: true,
+            assignment_reminders: true,
+            submission_feedback: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayPreferences {
+    pub theme: Theme,
+    /// `None` uses the convention of the user's locale.
+    pub hour_cycle: Option<HourCycle>,
+    pub compact: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HourCycle {
+    H12,
+    H24,
+}
@@ -1,3 +1,5 @@
+import { getToken } from "@/client";
+
 type SignalServerEventData =
   | MeetingStartedData
   | MeetingStoppedData
@@ -49,9 +51,9 @@ export class ClassMeetingWS {
     // TODO: use env variable
     this.ws = new WebSocket("ws://localhost:3000/rtc-ws");
     this.classId = classId;
-    const token = localStorage.getItem("token");
 
     this.ws.addEventListener("open", async (event) => {
+      const token = await getToken();
       this.ws.send(JSON.stringify({ type: "Auth", token, class_id: classId }));
       this.ws.send(
         JSON.stringify({ type: "Subscribe", target_class_id: classId })
@@ -130,7 +132,7 @@ export class ClassMeetingWS {
       {
         method: "GET",
         headers: {
-          Authorization: `Bearer ${localStorage.getItem("token")}`,
+          Authorization: `Bearer ${await getToken()}`,
         },
       }
     );
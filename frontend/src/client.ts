@@ -1,5 +1,4 @@
 import {
-  ApolloLink,
   ApolloClient,
   InMemoryCache,
   from,
@@ -12,31 +11,112 @@ import { GraphQLWsLink } from "@apollo/client/link/subscriptions";
 import jwt_decode from "jwt-decode";
 import gql from "graphql-tag";
 import { onError } from "@apollo/client/link/error";
+import { setContext } from "@apollo/client/link/context";
 import { Ref, computed, ref } from "vue";
 
-const token = localStorage.getItem("token");
-if (token) {
+// access tokens are refreshed a minute before they expire, so requests in flight still pass
+const REFRESH_MARGIN_MS = 60 * 1000;
+
+const expiresSoon = (token: string) => {
   const decoded: { exp: number } = jwt_decode(token);
-  if (decoded.exp * 1000 < Date.now()) {
-    localStorage.removeItem("token");
-  }
+  return decoded.exp * 1000 - REFRESH_MARGIN_MS < Date.now();
+};
+
+const token = localStorage.getItem("token");
+if (token && expiresSoon(token) && !localStorage.getItem("refreshToken")) {
+  localStorage.removeItem("token");
 }
 
-const authMiddleware = new ApolloLink((operation, forward) => {
+export const storeTokens = (token: string, refreshToken: string) => {
+  localStorage.setItem("token", token);
+  localStorage.setItem("refreshToken", refreshToken);
+};
+
+export const clearTokens = () => {
+  localStorage.removeItem("token");
+  localStorage.removeItem("refreshToken");
+};
+
+const REFRESH_TOKEN = `
+  mutation RefreshToken($refreshToken: String!) {
+    refreshToken(refreshToken: $refreshToken) {
+      token
+      refreshToken
+    }
+  }
+`;
+
+const refresh = async (refreshToken: string) => {
+  let tokens: { token?: string; refreshToken?: string } | undefined;
+  try {
+    const res = await fetch(import.meta.env.VITE_GRAPHQL_ENDPOINT, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({
+        query: REFRESH_TOKEN,
+        variables: { refreshToken },
+      }),
+    });
+    tokens = (await res.json()).data?.refreshToken;
+  } catch (err) {
+    // offline, the next request tries again
+    return localStorage.getItem("token");
+  }
+
+  if (!tokens?.token || !tokens?.refreshToken) {
+    clearTokens();
+    cache.writeQuery({
+      query: IS_LOGGED_IN,
+      data: {
+        isLoggedIn: false,
+      },
+    });
+    return null;
+  }
+  storeTokens(tokens.token, tokens.refreshToken);
+  return tokens.token;
+};
+
+let refreshing: Promise<string | null> | null = null;
+
+// Trades the refresh token for new tokens right away. Concurrent callers share one refresh,
+// as a refresh token can only be used once.
+export const refreshTokens = async () => {
+  const refreshToken = localStorage.getItem("refreshToken");
+  if (!refreshToken) {
+    return localStorage.getItem("token");
+  }
+
+  refreshing ??= refresh(refreshToken).finally(() => {
+    refreshing = null;
+  });
+  return refreshing;
+};
+
+// The access token, refreshed first when it is about to expire.
+export const getToken = async () => {
   const token = localStorage.getItem("token");
-  operation.setContext({
+  if (token && !expiresSoon(token)) {
+    return token;
+  }
+  return refreshTokens();
+};
+
+const authMiddleware = setContext(async (_, { headers }) => {
+  const token = await getToken();
+  return {
     headers: {
+      ...headers,
       authorization: token ? `Bearer ${token}` : "",
     },
-  });
-  return forward(operation);
+  };
 });
 
 const wsLink = new GraphQLWsLink(
   createClient({
     url: import.meta.env.VITE_GRAPHQL_ENDPOINT_WS,
-    connectionParams: () => {
-      const token = localStorage.getItem("token");
+    connectionParams: async () => {
+      const token = await getToken();
       return {
         token: token,
       };
@@ -84,7 +164,8 @@ const IS_LOGGED_IN = gql`
 cache.writeQuery({
   query: IS_LOGGED_IN,
   data: {
-    isLoggedIn: !!localStorage.getItem("token"),
+    isLoggedIn:
+      !!localStorage.getItem("token") || !!localStorage.getItem("refreshToken"),
   },
 });
 
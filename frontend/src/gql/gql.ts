@@ -54,9 +54,9 @@ const documents = {
     "\n  mutation BanMemberMutation($classId: ID!, $userId: ID!) {\n    banMember(classId: $classId, userId: $userId)\n  }\n": types.BanMemberMutationDocument,
     "\n  mutation UnbanMemberMutation($classId: ID!, $userId: ID!) {\n    unbanMember(classId: $classId, userId: $userId)\n  }\n": types.UnbanMemberMutationDocument,
     "\n  mutation LeaveClassMutation($classId: ID!) {\n    leaveClass(classId: $classId)\n  }\n": types.LeaveClassMutationDocument,
-    "\n  query AdminClassQuery($query: String!) {\n    classesBySearch(query: $query) {\n      id\n      name\n      deletedAt\n    }\n  }\n": types.AdminClassQueryDocument,
+    "\n  query AdminClassQuery($search: String, $after: String) {\n    classes(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          name\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n": types.AdminClassQueryDocument,
     "\n  mutation AdminClassUpdate($classId: ID!, $deletedState: Boolean!) {\n    adminDeleteClass(classId: $classId, deletedState: $deletedState)\n  }\n": types.AdminClassUpdateDocument,
-    "\n  query AdminUsersQuery {\n    users {\n      id\n      username\n      userType\n      deletedAt\n    }\n  }\n": types.AdminUsersQueryDocument,
+    "\n  query AdminUsersQuery($search: String, $after: String) {\n    users(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          username\n          userType\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n": types.AdminUsersQueryDocument,
     "\n  mutation AdminUserUpdate(\n    $userId: ID!\n    $userType: UserType!\n    $deletedAt: NaiveDateTime\n  ) {\n    adminUserUpdate(userId: $userId, userType: $userType, deletedAt: $deletedAt)\n  }\n": types.AdminUserUpdateDocument,
     "\n  query AppBarMeQuery {\n    me {\n      id\n      username\n      hasAvatar\n      userType\n    }\n  }\n": types.AppBarMeQueryDocument,
     "\n  query routerClassById($id: ID!) {\n    classById(id: $id) {\n      id\n      owner {\n        id\n      }\n    }\n  }\n": types.RouterClassByIdDocument,
@@ -70,12 +70,12 @@ const documents = {
     "\n  fragment UserFragment on User {\n    id\n    username\n  }\n": types.UserFragmentFragmentDoc,
     "\n  subscription ClassResourceCreateSubscription($classId: ID!) {\n    classResourceCreated(classId: $classId) {\n      __typename\n      ... on Channel {\n        ...ChannelsFragment\n      }\n      ... on File {\n        ...FileFragment\n      }\n      ... on FileBatch {\n        files {\n          ...FileFragment\n        }\n      }\n      ... on Assignment {\n        ...AssignmentFragment\n      }\n      ... on User {\n        ...UserFragment\n      }\n    }\n  }\n": types.ClassResourceCreateSubscriptionDocument,
     "\n  subscription ClassResourceUpdateSubscription($classId: ID!) {\n    classResourceUpdated(classId: $classId) {\n      __typename\n      ... on Channel {\n        ...ChannelsFragment\n      }\n      ... on Class {\n        ...ClassDataFragment\n      }\n      ... on Assignment {\n        ...AssignmentFragment\n      }\n    }\n  }\n": types.ClassResourceUpdateSubscriptionDocument,
-    "\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n    }\n  }\n": types.ClassResourceDeletedSubscriptionDocument,
+    "\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n      ... on MessageDeleteInfo {\n        id\n      }\n    }\n  }\n": types.ClassResourceDeletedSubscriptionDocument,
     "\n  query classesBySearch($query: String!) {\n    classesBySearch(query: $query) {\n      id\n      name\n      description\n      hasImage\n    }\n  }\n": types.ClassesBySearchDocument,
     "\n  mutation JoinClass($classId: ID!) {\n    joinClass(classId: $classId)\n  }\n": types.JoinClassDocument,
     "\n  query InviteClassQuery($inviteId: ID!) {\n    classByInviteId(inviteId: $inviteId) {\n      id\n      name\n      description\n    }\n  }\n": types.InviteClassQueryDocument,
     "\n  mutation JoinClassMutation($inviteId: ID!, $classId: ID!) {\n    joinClass(inviteId: $inviteId, classId: $classId)\n  }\n": types.JoinClassMutationDocument,
-    "\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n    }\n  }\n": types.LoginDocument,
+    "\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n      refreshToken\n      twoFactorToken\n      twoFactorSetupRequired\n    }\n  }\n": types.LoginDocument,
     "\n  mutation CreateResetTokenMutation($email: String!) {\n    createPasswordChangeToken(email: $email)\n  }\n": types.CreateResetTokenMutationDocument,
     "\n  mutation Signup($input: SignupInput!) {\n    signup(input: $input)\n  }\n": types.SignupDocument,
     "\n  query MyAssignmentsMeQuery {\n    me {\n      id\n      assignments {\n        id\n        classId\n        name\n        content\n        dueAt\n        createdAt\n        files {\n          id\n          name\n        }\n        submissions {\n          id\n          createdAt\n          files {\n            id\n            name\n          }\n          feedback {\n            id\n            content\n            createdAt\n          }\n        }\n      }\n    }\n  }\n": types.MyAssignmentsMeQueryDocument,
@@ -83,6 +83,10 @@ const documents = {
     "\n  query UserSettingsMeQuery {\n    me {\n      id\n      username\n      firstName\n      lastName\n      email\n    }\n  }\n": types.UserSettingsMeQueryDocument,
     "\n  mutation UpdateUserDataMutation(\n    $userId: ID!\n    $firstName: String\n    $lastName: String\n    $avatar: Upload\n    $password: String!\n  ) {\n    updateUser(\n      userId: $userId\n      firstName: $firstName\n      lastName: $lastName\n      avatar: $avatar\n      password: $password\n    ) {\n      id\n      firstName\n      lastName\n    }\n  }\n": types.UpdateUserDataMutationDocument,
     "\n  mutation ChangePasswordMutation(\n    $userId: ID!\n    $oldPassword: String!\n    $newPassword: String!\n  ) {\n    changePassword(\n      userId: $userId\n      oldPassword: $oldPassword\n      newPassword: $newPassword\n    ) {\n      id\n    }\n  }\n": types.ChangePasswordMutationDocument,
+    "\n  mutation LogoutMutation {\n    logout\n  }\n": types.LogoutMutationDocument,
+    "\n  mutation LoginTwoFactor($twoFactorToken: String!, $code: String!) {\n    loginTwoFactor(twoFactorToken: $twoFactorToken, code: $code) {\n      token\n      refreshToken\n      twoFactorSetupRequired\n    }\n  }\n": types.LoginTwoFactorDocument,
+    "\n  mutation SetupTwoFactorMutation {\n    setupTwoFactor {\n      otpauthUri\n      secret\n    }\n  }\n": types.SetupTwoFactorMutationDocument,
+    "\n  mutation ConfirmTwoFactorMutation($code: String!) {\n    confirmTwoFactor(code: $code)\n  }\n": types.ConfirmTwoFactorMutationDocument,
 };
 
 /**
@@ -266,7 +270,7 @@ export function graphql(source: "\n  mutation LeaveClassMutation($classId: ID!)
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
-export function graphql(source: "\n  query AdminClassQuery($query: String!) {\n    classesBySearch(query: $query) {\n      id\n      name\n      deletedAt\n    }\n  }\n"): (typeof documents)["\n  query AdminClassQuery($query: String!) {\n    classesBySearch(query: $query) {\n      id\n      name\n      deletedAt\n    }\n  }\n"];
+export function graphql(source: "\n  query AdminClassQuery($search: String, $after: String) {\n    classes(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          name\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n"): (typeof documents)["\n  query AdminClassQuery($search: String, $after: String) {\n    classes(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          name\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n"];
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
@@ -274,7 +278,7 @@ export function graphql(source: "\n  mutation AdminClassUpdate($classId: ID!, $d
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
-export function graphql(source: "\n  query AdminUsersQuery {\n    users {\n      id\n      username\n      userType\n      deletedAt\n    }\n  }\n"): (typeof documents)["\n  query AdminUsersQuery {\n    users {\n      id\n      username\n      userType\n      deletedAt\n    }\n  }\n"];
+export function graphql(source: "\n  query AdminUsersQuery($search: String, $after: String) {\n    users(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          username\n          userType\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n"): (typeof documents)["\n  query AdminUsersQuery($search: String, $after: String) {\n    users(filter: { search: $search }, after: $after, first: 25) {\n      totalCount\n      edges {\n        node {\n          id\n          username\n          userType\n          deletedAt\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n"];
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
@@ -330,7 +334,7 @@ export function graphql(source: "\n  subscription ClassResourceUpdateSubscriptio
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
-export function graphql(source: "\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n    }\n  }\n"): (typeof documents)["\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n    }\n  }\n"];
+export function graphql(source: "\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n      ... on MessageDeleteInfo {\n        id\n      }\n    }\n  }\n"): (typeof documents)["\n  subscription ClassResourceDeletedSubscription($classId: ID!) {\n    classResourceDeleted(classId: $classId) {\n      __typename\n      ... on ChannelDeleteInfo {\n        id\n      }\n      ... on AssignmentDeleteInfo {\n        id\n      }\n      ... on FileDeleteInfo {\n        id\n      }\n      ... on MemberDeleteInfo {\n        id\n      }\n      ... on MessageDeleteInfo {\n        id\n      }\n    }\n  }\n"];
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
@@ -350,7 +354,7 @@ export function graphql(source: "\n  mutation JoinClassMutation($inviteId: ID!,
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
-export function graphql(source: "\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n    }\n  }\n"): (typeof documents)["\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n    }\n  }\n"];
+export function graphql(source: "\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n      refreshToken\n      twoFactorToken\n      twoFactorSetupRequired\n    }\n  }\n"): (typeof documents)["\n  mutation Login($password: String!, $username: String!) {\n    login(input: { password: $password, username: $username }) {\n      token\n      refreshToken\n      twoFactorToken\n      twoFactorSetupRequired\n    }\n  }\n"];
 /**
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
@@ -379,6 +383,22 @@ export function graphql(source: "\n  mutation UpdateUserDataMutation(\n    $user
  * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
 export function graphql(source: "\n  mutation ChangePasswordMutation(\n    $userId: ID!\n    $oldPassword: String!\n    $newPassword: String!\n  ) {\n    changePassword(\n      userId: $userId\n      oldPassword: $oldPassword\n      newPassword: $newPassword\n    ) {\n      id\n    }\n  }\n"): (typeof documents)["\n  mutation ChangePasswordMutation(\n    $userId: ID!\n    $oldPassword: String!\n    $newPassword: String!\n  ) {\n    changePassword(\n      userId: $userId\n      oldPassword: $oldPassword\n      newPassword: $newPassword\n    ) {\n      id\n    }\n  }\n"];
+/**
+ * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
+ */
+export function graphql(source: "\n  mutation LogoutMutation {\n    logout\n  }\n"): (typeof documents)["\n  mutation LogoutMutation {\n    logout\n  }\n"];
+/**
+ * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
+ */
+export function graphql(source: "\n  mutation LoginTwoFactor($twoFactorToken: String!, $code: String!) {\n    loginTwoFactor(twoFactorToken: $twoFactorToken, code: $code) {\n      token\n      refreshToken\n      twoFactorSetupRequired\n    }\n  }\n"): (typeof documents)["\n  mutation LoginTwoFactor($twoFactorToken: String!, $code: String!) {\n    loginTwoFactor(twoFactorToken: $twoFactorToken, code: $code) {\n      token\n      refreshToken\n      twoFactorSetupRequired\n    }\n  }\n"];
+/**
+ * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
+ */
+export function graphql(source: "\n  mutation SetupTwoFactorMutation {\n    setupTwoFactor {\n      otpauthUri\n      secret\n    }\n  }\n"): (typeof documents)["\n  mutation SetupTwoFactorMutation {\n    setupTwoFactor {\n      otpauthUri\n      secret\n    }\n  }\n"];
+/**
+ * The graphql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
+ */
+export function graphql(source: "\n  mutation ConfirmTwoFactorMutation($code: String!) {\n    confirmTwoFactor(code: $code)\n  }\n"): (typeof documents)["\n  mutation ConfirmTwoFactorMutation($code: String!) {\n    confirmTwoFactor(code: $code)\n  }\n"];
 
 export function graphql(source: string) {
   return (documents as any)[source] ?? {};
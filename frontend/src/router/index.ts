@@ -92,6 +92,20 @@ const routes = [
         component: () =>
           import(/* webpackChunkName: "login" */ "@/views/Login.vue"),
       },
+      {
+        path: "/login/oidc",
+        name: "OIDC Login",
+        component: () =>
+          import(/* webpackChunkName: "login" */ "@/views/OidcLogin.vue"),
+      },
+      {
+        path: "/two-factor-setup",
+        name: "Two-Factor Setup",
+        component: () =>
+          import(
+            /* webpackChunkName: "two-factor-setup" */ "@/views/TwoFactorSetup.vue"
+          ),
+      },
       {
         path: "/register",
         name: "Register",
@@ -2,7 +2,7 @@ import { useQuery } from "@vue/apollo-composable";
 import { graphql } from "./gql";
 import { until } from "@vueuse/core";
 import { useSubscription } from "@vue/apollo-composable";
-import { cache } from "@/client";
+import { cache, getToken } from "@/client";
 import router from "@/router";
 
 export const MyIdQuery = graphql(/* GraphQL */ `
@@ -26,7 +26,7 @@ export const download = async (classId: string, item: any) => {
 export const downloadFile = async (url: string, filename: string) => {
   const data = await fetch(url, {
     headers: {
-      Authorization: `Bearer ${localStorage.getItem("token")} `,
+      Authorization: `Bearer ${await getToken()} `,
     },
   });
   const blob = await data.blob();
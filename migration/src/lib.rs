@@ -4,6 +4,25 @@ mod m20220101_000001_create_table;
 mod m20230319_120845_create_relation;
 mod m20230504_111801_create_tsvector;
 mod m20231115_161824_create_admin;
+mod m20231204_143012_create_refresh_token;
+mod m20231211_094530_create_two_factor;
+mod m20231218_152204_create_activation_token;
+mod m20231220_103317_add_password_reset_token_expiry;
+mod m20231222_091455_add_user_locale;
+mod m20231228_140512_add_membership_role;
+mod m20231229_101533_create_personal_access_token;
+mod m20231230_094218_create_impersonation_log;
+mod m20240102_103824_add_user_profile;
+mod m20240104_142310_create_email_change_token;
+mod m20240106_091207_add_class_archived_at;
+mod m20240108_153641_create_ownership_transfer;
+mod m20240110_101522_extend_invite;
+mod m20240112_084419_create_join_request;
+mod m20240114_103052_add_membership_joined_at;
+mod m20240116_091344_create_tag;
+mod m20240118_140237_create_class_event;
+mod m20240120_083145_hash_password_reset_token;
+mod m20240121_094512_add_user_email_lower_index;
 
 pub struct Migrator;
 
@@ -15,6 +34,25 @@ impl MigratorTrait for Migrator {
             Box::new(m20230319_120845_create_relation::Migration),
             Box::new(m20230504_111801_create_tsvector::Migration),
             Box::new(m20231115_161824_create_admin::Migration),
+            Box::new(m20231204_143012_create_refresh_token::Migration),
+            Box::new(m20231211_094530_create_two_factor::Migration),
+            Box::new(m20231218_152204_create_activation_token::Migration),
+            Box::new(m20231220_103317_add_password_reset_token_expiry::Migration),
+            Box::new(m20231222_091455_add_user_locale::Migration),
+            Box::new(m20231228_140512_add_membership_role::Migration),
+            Box::new(m20231229_101533_create_personal_access_token::Migration),
+            Box::new(m20231230_094218_create_impersonation_log::Migration),
+            Box::new(m20240102_103824_add_user_profile::Migration),
+            Box::new(m20240104_142310_create_email_change_token::Migration),
+            Box::new(m20240106_091207_add_class_archived_at::Migration),
+            Box::new(m20240108_153641_create_ownership_transfer::Migration),
+            Box::new(m20240110_101522_extend_invite::Migration),
+            Box::new(m20240112_084419_create_join_request::Migration),
+            Box::new(m20240114_103052_add_membership_joined_at::Migration),
+            Box::new(m20240116_091344_create_tag::Migration),
+            Box::new(m20240118_140237_create_class_event::Migration),
+            Box::new(m20240120_083145_hash_password_reset_token::Migration),
+            Box::new(m20240121_094512_add_user_email_lower_index::Migration),
         ]
     }
 }
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // existing tokens had no expiry, defaulting both columns to now expires them right away
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordResetToken::Table)
+                    .add_column(
+                        ColumnDef::new(PasswordResetToken::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column(
+                        ColumnDef::new(PasswordResetToken::ExpiresAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordResetToken::Table)
+                    .drop_column(PasswordResetToken::CreatedAt)
+                    .drop_column(PasswordResetToken::ExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum PasswordResetToken {
+    Table,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -0,0 +1,121 @@
+use sea_orm_migration::{prelude::*, sea_query::extension::postgres::Type};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(MemberRole::Type)
+                    .values([
+                        MemberRole::CoTeacher,
+                        MemberRole::TeachingAssistant,
+                        MemberRole::Student,
+                        MemberRole::Observer,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Membership::Table)
+                    .add_column(
+                        ColumnDef::new(Membership::Role)
+                            .custom(MemberRole::Type)
+                            .not_null()
+                            .default(SimpleExpr::Custom("'Student'".into())),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // owners keep their membership row, it should not make them look like students
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Membership::Table)
+                    .value(
+                        Membership::Role,
+                        SimpleExpr::Custom("'CoTeacher'".into()),
+                    )
+                    .and_where(
+                        Expr::tuple([
+                            Expr::col(Membership::UserId).into(),
+                            Expr::col(Membership::ClassId).into(),
+                        ])
+                        .in_subquery(
+                            Query::select()
+                                .columns([Class::OwnerId, Class::Id])
+                                .from(Class::Table)
+                                .to_owned(),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Membership::Table)
+                    .drop_column(Membership::Role)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(MemberRole::Type).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Membership {
+    Table,
+    UserId,
+    ClassId,
+    Role,
+}
+
+#[derive(Iden)]
+enum Class {
+    Table,
+    Id,
+    OwnerId,
+}
+
+enum MemberRole {
+    Type,
+    CoTeacher,
+    TeachingAssistant,
+    Student,
+    Observer,
+}
+
+impl Iden for MemberRole {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Type => "member_role",
+                Self::CoTeacher => "CoTeacher",
+                Self::TeachingAssistant => "TeachingAssistant",
+                Self::Student => "Student",
+                Self::Observer => "Observer",
+            }
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PersonalAccessToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::Id)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PersonalAccessToken::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::TokenHash)
+                            .string()
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PersonalAccessToken::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::ReadOnly)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::ClassIds)
+                            .array(ColumnType::Uuid)
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::ExpiresAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::LastUsedAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(PersonalAccessToken::RevokedAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_personal_access_token_user_id")
+                            .from(PersonalAccessToken::Table, PersonalAccessToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("personal_access_token_user_id_idx")
+                    .table(PersonalAccessToken::Table)
+                    .col(PersonalAccessToken::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PersonalAccessToken::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum PersonalAccessToken {
+    Table,
+    Id,
+    Name,
+    TokenHash,
+    UserId,
+    ReadOnly,
+    ClassIds,
+    CreatedAt,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+}
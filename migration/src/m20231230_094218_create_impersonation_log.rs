@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImpersonationLog::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImpersonationLog::Id)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImpersonationLog::SessionId).uuid().not_null())
+                    .col(ColumnDef::new(ImpersonationLog::AdminId).uuid().not_null())
+                    .col(ColumnDef::new(ImpersonationLog::UserId).uuid().not_null())
+                    .col(ColumnDef::new(ImpersonationLog::Action).string().not_null())
+                    .col(
+                        ColumnDef::new(ImpersonationLog::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_impersonation_log_admin_id")
+                            .from(ImpersonationLog::Table, ImpersonationLog::AdminId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_impersonation_log_user_id")
+                            .from(ImpersonationLog::Table, ImpersonationLog::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("impersonation_log_created_at_idx")
+                    .table(ImpersonationLog::Table)
+                    .col(ImpersonationLog::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ImpersonationLog::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum ImpersonationLog {
+    Table,
+    Id,
+    SessionId,
+    AdminId,
+    UserId,
+    Action,
+    CreatedAt,
+}
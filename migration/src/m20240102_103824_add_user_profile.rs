@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(User::Bio).text().null())
+                    .add_column(ColumnDef::new(User::Pronouns).string().null())
+                    .add_column(
+                        ColumnDef::new(User::Timezone)
+                            .string()
+                            .not_null()
+                            .default("UTC"),
+                    )
+                    .add_column(
+                        ColumnDef::new(User::Preferences)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::Bio)
+                    .drop_column(User::Pronouns)
+                    .drop_column(User::Timezone)
+                    .drop_column(User::Preferences)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Bio,
+    Pronouns,
+    Timezone,
+    Preferences,
+}
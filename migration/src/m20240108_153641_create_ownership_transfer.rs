@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::{Class, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OwnershipTransfer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OwnershipTransfer::ClassId)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OwnershipTransfer::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(OwnershipTransfer::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_ownership_transfer_class_id")
+                            .from(OwnershipTransfer::Table, OwnershipTransfer::ClassId)
+                            .to(Class::Table, Class::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_ownership_transfer_user_id")
+                            .from(OwnershipTransfer::Table, OwnershipTransfer::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OwnershipTransfer::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum OwnershipTransfer {
+    Table,
+    ClassId,
+    UserId,
+    CreatedAt,
+}
@@ -0,0 +1,143 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Invite::Table)
+                    .add_column(ColumnDef::new(Invite::Code).string().unique_key().null())
+                    .add_column(ColumnDef::new(Invite::Email).string().null())
+                    .add_column(ColumnDef::new(Invite::MaxUses).integer().null())
+                    .add_column(ColumnDef::new(Invite::Uses).integer().not_null().default(0))
+                    .add_column(ColumnDef::new(Invite::CreatedBy).uuid().null())
+                    .add_column(
+                        ColumnDef::new(Invite::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column(ColumnDef::new(Invite::RevokedBy).uuid().null())
+                    .add_column(ColumnDef::new(Invite::RevokedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("FK_invite_created_by")
+                    .from(Invite::Table, Invite::CreatedBy)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("FK_invite_revoked_by")
+                    .from(Invite::Table, Invite::RevokedBy)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(InviteRedemption::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InviteRedemption::Id)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(InviteRedemption::InviteId).uuid().not_null())
+                    .col(ColumnDef::new(InviteRedemption::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(InviteRedemption::RedeemedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_invite_redemption_invite_id")
+                            .from(InviteRedemption::Table, InviteRedemption::InviteId)
+                            .to(Invite::Table, Invite::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_invite_redemption_user_id")
+                            .from(InviteRedemption::Table, InviteRedemption::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(InviteRedemption::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Invite::Table)
+                    .drop_column(Invite::Code)
+                    .drop_column(Invite::Email)
+                    .drop_column(Invite::MaxUses)
+                    .drop_column(Invite::Uses)
+                    .drop_column(Invite::CreatedBy)
+                    .drop_column(Invite::CreatedAt)
+                    .drop_column(Invite::RevokedBy)
+                    .drop_column(Invite::RevokedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Invite {
+    Table,
+    Id,
+    Code,
+    Email,
+    MaxUses,
+    Uses,
+    CreatedBy,
+    CreatedAt,
+    RevokedBy,
+    RevokedAt,
+}
+
+#[derive(Iden)]
+pub enum InviteRedemption {
+    Table,
+    Id,
+    InviteId,
+    UserId,
+    RedeemedAt,
+}
@@ -0,0 +1,140 @@
+use sea_orm_migration::{prelude::*, sea_query::extension::postgres::Type};
+
+use crate::m20220101_000001_create_table::{Class, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(JoinRequestStatus::Type)
+                    .values([
+                        JoinRequestStatus::Pending,
+                        JoinRequestStatus::Approved,
+                        JoinRequestStatus::Denied,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(JoinRequest::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(JoinRequest::Id)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(JoinRequest::ClassId).uuid().not_null())
+                    .col(ColumnDef::new(JoinRequest::UserId).uuid().not_null())
+                    .col(ColumnDef::new(JoinRequest::Note).text().null())
+                    .col(
+                        ColumnDef::new(JoinRequest::Status)
+                            .custom(JoinRequestStatus::Type)
+                            .not_null()
+                            .default(SimpleExpr::Custom("'Pending'".into())),
+                    )
+                    .col(
+                        ColumnDef::new(JoinRequest::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(JoinRequest::DecidedBy).uuid().null())
+                    .col(ColumnDef::new(JoinRequest::DecidedAt).timestamp().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_join_request_class_id")
+                            .from(JoinRequest::Table, JoinRequest::ClassId)
+                            .to(Class::Table, Class::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_join_request_user_id")
+                            .from(JoinRequest::Table, JoinRequest::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_join_request_decided_by")
+                            .from(JoinRequest::Table, JoinRequest::DecidedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_join_request_class_id_status")
+                    .table(JoinRequest::Table)
+                    .col(JoinRequest::ClassId)
+                    .col(JoinRequest::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(JoinRequest::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(JoinRequestStatus::Type).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum JoinRequest {
+    Table,
+    Id,
+    ClassId,
+    UserId,
+    Note,
+    Status,
+    CreatedAt,
+    DecidedBy,
+    DecidedAt,
+}
+
+enum JoinRequestStatus {
+    Type,
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl Iden for JoinRequestStatus {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Type => "join_request_status",
+                Self::Pending => "Pending",
+                Self::Approved => "Approved",
+                Self::Denied => "Denied",
+            }
+        )
+        .unwrap();
+    }
+}
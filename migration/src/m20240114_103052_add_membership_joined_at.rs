@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Memberships made before this migration have no known join date and stay null,
+        // a default on the new column would stamp all of them with the current time
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Membership::Table)
+                    .add_column(ColumnDef::new(Membership::JoinedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // `timestamp` columns hold UTC like everything the backend writes, while
+        // `current_timestamp` would be converted to the session's timezone
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "alter table membership alter column joined_at set default (now() at time zone 'utc');",
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_membership_class_id_joined_at")
+                    .table(Membership::Table)
+                    .col(Membership::ClassId)
+                    .col(Membership::JoinedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("IDX_membership_class_id_joined_at")
+                    .table(Membership::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Membership::Table)
+                    .drop_column(Membership::JoinedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Membership {
+    Table,
+    ClassId,
+    JoinedAt,
+}
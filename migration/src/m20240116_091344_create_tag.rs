@@ -0,0 +1,138 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::Class;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tag::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Tag::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Tag::Name).string().not_null().unique_key())
+                    .col(
+                        ColumnDef::new(Tag::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ClassTag::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ClassTag::ClassId).uuid().not_null())
+                    .col(ColumnDef::new(ClassTag::TagId).uuid().not_null())
+                    .primary_key(
+                        index::Index::create()
+                            .col(ClassTag::ClassId)
+                            .col(ClassTag::TagId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_class_tag_class_id")
+                            .from(ClassTag::Table, ClassTag::ClassId)
+                            .to(Class::Table, Class::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_class_tag_tag_id")
+                            .from(ClassTag::Table, ClassTag::TagId)
+                            .to(Tag::Table, Tag::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_class_tag_tag_id")
+                    .table(ClassTag::Table)
+                    .col(ClassTag::TagId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Turns the free-form tags typed so far into tags shared between classes, following
+        // the rules of `tags::normalize_tags` in the backend: tags are split on spaces and
+        // commas, lowercased, lose leading `#` and keep their first position. Tags that would be
+        // rejected as too long are dropped and a class keeps at most its first 20 tags.
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "create temporary table class_tag_name as
+                select class_id, name, row_number() over (partition by class_id order by position) as position
+                from (
+                    select class.id as class_id, lower(ltrim(t, '#')) as name, min(position) as position
+                    from class
+                    cross join regexp_split_to_table(class.tags, '[\\s,]+') with ordinality u(t, position)
+                    group by 1, 2
+                ) names
+                where name <> '' and char_length(name) <= 32;
+
+            delete from class_tag_name where position > 20;
+
+            insert into tag (id, name)
+                select gen_random_uuid(), name
+                from (select distinct name from class_tag_name) names;
+
+            insert into class_tag (class_id, tag_id)
+                select class_tag_name.class_id, tag.id
+                from class_tag_name
+                join tag on tag.name = class_tag_name.name;
+
+            update class
+                set tags = coalesce((
+                    select string_agg(name, ' ' order by position)
+                    from class_tag_name
+                    where class_tag_name.class_id = class.id
+                ), '');
+
+            drop table class_tag_name;
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ClassTag::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Tag::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Tag {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum ClassTag {
+    Table,
+    ClassId,
+    TagId,
+}
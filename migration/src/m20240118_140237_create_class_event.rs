@@ -0,0 +1,197 @@
+use sea_orm_migration::{prelude::*, sea_query::extension::postgres::Type};
+
+use crate::m20220101_000001_create_table::{Class, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(ClassEventKind::Type)
+                    .values([
+                        ClassEventKind::Lecture,
+                        ClassEventKind::OfficeHours,
+                        ClassEventKind::Exam,
+                        ClassEventKind::Other,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ClassEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ClassEvent::Id)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ClassEvent::ClassId).uuid().not_null())
+                    .col(ColumnDef::new(ClassEvent::Title).string().not_null())
+                    .col(ColumnDef::new(ClassEvent::Description).text().null())
+                    .col(
+                        ColumnDef::new(ClassEvent::Kind)
+                            .custom(ClassEventKind::Type)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ClassEvent::Location).string().null())
+                    .col(ColumnDef::new(ClassEvent::StartsAt).timestamp().not_null())
+                    .col(ColumnDef::new(ClassEvent::EndsAt).timestamp().not_null())
+                    .col(
+                        ColumnDef::new(ClassEvent::Timezone)
+                            .string()
+                            .not_null()
+                            .default("UTC"),
+                    )
+                    .col(ColumnDef::new(ClassEvent::Recurrence).string().null())
+                    .col(ColumnDef::new(ClassEvent::CreatedBy).uuid().null())
+                    .col(
+                        ColumnDef::new(ClassEvent::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_class_event_class_id")
+                            .from(ClassEvent::Table, ClassEvent::ClassId)
+                            .to(Class::Table, Class::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_class_event_created_by")
+                            .from(ClassEvent::Table, ClassEvent::CreatedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_class_event_class_id")
+                    .table(ClassEvent::Table)
+                    .col(ClassEvent::ClassId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CalendarToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CalendarToken::UserId)
+                            .not_null()
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CalendarToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CalendarToken::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(CalendarToken::LastUsedAt).timestamp().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_calendar_token_user_id")
+                            .from(CalendarToken::Table, CalendarToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CalendarToken::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ClassEvent::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(ClassEventKind::Type).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum ClassEvent {
+    Table,
+    Id,
+    ClassId,
+    Title,
+    Description,
+    Kind,
+    Location,
+    StartsAt,
+    EndsAt,
+    /// IANA name of the zone the event repeats in, so it keeps its local time over DST changes.
+    Timezone,
+    /// An `RRULE` like `FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20240601T000000Z`, null for one-off events.
+    Recurrence,
+    CreatedBy,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum CalendarToken {
+    Table,
+    UserId,
+    TokenHash,
+    CreatedAt,
+    LastUsedAt,
+}
+
+enum ClassEventKind {
+    Type,
+    Lecture,
+    OfficeHours,
+    Exam,
+    Other,
+}
+
+impl Iden for ClassEventKind {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Type => "class_event_kind",
+                Self::Lecture => "Lecture",
+                Self::OfficeHours => "OfficeHours",
+                Self::Exam => "Exam",
+                Self::Other => "Other",
+            }
+        )
+        .unwrap();
+    }
+}